@@ -15,6 +15,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         width: 9,
         height: 9,
         bombs: 10,
+        ..Default::default()
     };
 
     let game_id = client.create_game(game_params).await?;
@@ -33,6 +34,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         height,
         bombs,
         field,
+        ..
     }) = ws.receive_message().await?
     {
         println!(
@@ -59,7 +61,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Receive the response
     if let Some(message) = ws.receive_message().await? {
         match message {
-            ServerMessage::Update { updates, won, lost } => {
+            ServerMessage::Update {
+                updates, won, lost, ..
+            } => {
                 println!("Received update: {} cells updated", updates.len());
                 for update in updates {
                     println!(
@@ -83,7 +87,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Receive the flag response
     if let Some(message) = ws.receive_message().await? {
         match message {
-            ServerMessage::Update { updates, won, lost } => {
+            ServerMessage::Update {
+                updates, won, lost, ..
+            } => {
                 println!("Received flag update: {} cells updated", updates.len());
                 for update in updates {
                     println!(
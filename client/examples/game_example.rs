@@ -1,5 +1,4 @@
-use minesweeper_client::{GameEvent, GameParams, MinesweeperGame};
-use minesweeper_common::models::Pos;
+use minesweeper_client::{Cell, ConnectOptions, GameEvent, GameParams, MinesweeperGame};
 use tokio::time::{Duration, sleep};
 
 #[tokio::main]
@@ -8,7 +7,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     tracing_subscriber::fmt::init();
 
     // Create a high-level game client
-    let game = MinesweeperGame::new("http://localhost:8000")?;
+    let game =
+        MinesweeperGame::new("http://localhost:8000")?.with_connect_options(ConnectOptions {
+            cascade_depth: true,
+            ..Default::default()
+        });
+    game.enable_auto_reconnect(5);
 
     // Subscribe to game events for background listening
     let mut event_receiver = game.subscribe_to_events().await;
@@ -27,12 +31,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         width, height, bombs
                     );
                 }
-                GameEvent::BoardUpdated { changed_positions } => {
-                    println!(
-                        "📋 {} cells updated: {:?}",
-                        changed_positions.len(),
-                        changed_positions
-                    );
+                GameEvent::BoardUpdated { changes } => {
+                    println!("📋 {} cells updated", changes.len());
+                    for change in changes {
+                        let symbol = match change.value {
+                            Cell::Hidden => "▢".to_string(),
+                            Cell::Marked => "?".to_string(),
+                            Cell::Flagged => "🚩".to_string(),
+                            Cell::Revealed { adjacent } => adjacent.to_string(),
+                            Cell::Bomb => "💣".to_string(),
+                        };
+                        println!("   ({}, {}) → {}", change.pos.x, change.pos.y, symbol);
+                    }
+                }
+                GameEvent::CascadeRevealed { path } => {
+                    let depth = path.last().map_or(0, |(_, depth)| *depth);
+                    println!("🌊 Cascade opened {} cells, depth {}", path.len(), depth);
                 }
                 GameEvent::GameStatusChanged { won, lost } => {
                     if won {
@@ -41,6 +55,57 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         println!("💣 Game over!");
                     }
                 }
+                GameEvent::GameSummary {
+                    elapsed_ms,
+                    moves,
+                    board_cleared_pct,
+                    ..
+                } => {
+                    println!(
+                        "📊 {} moves in {:.1}s, {:.0}% of the board cleared",
+                        moves,
+                        elapsed_ms as f64 / 1000.0,
+                        board_cleared_pct
+                    );
+                }
+                GameEvent::GameRestarted {
+                    params_changed,
+                    new_layout,
+                    ..
+                } => {
+                    println!(
+                        "🔄 Game restarted (params changed: {}, new layout: {})",
+                        params_changed, new_layout
+                    );
+                }
+                GameEvent::ExpiryWarning { seconds_remaining } => {
+                    println!("⏳ Still there? Game expires in {}s", seconds_remaining);
+                }
+                GameEvent::PresenceChanged { players } => {
+                    println!("👥 {} connected", players);
+                }
+                GameEvent::PausedChanged { paused } => {
+                    println!(
+                        "{}",
+                        if paused {
+                            "⏸️ Paused"
+                        } else {
+                            "▶️ Resumed"
+                        }
+                    );
+                }
+                GameEvent::ChatReceived { from, text } => {
+                    println!("💬 {}: {}", from, text);
+                }
+                GameEvent::Error { code, message } => {
+                    println!("⚠️ Server error ({}): {}", code, message);
+                }
+                GameEvent::Reconnecting { attempt } => {
+                    println!("🔁 Reconnecting (attempt {})...", attempt);
+                }
+                GameEvent::Reconnected => {
+                    println!("✅ Reconnected");
+                }
                 GameEvent::ConnectionLost => {
                     println!("🔌 Connection lost!");
                     break;
@@ -54,6 +119,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         width: 8,
         height: 8,
         bombs: 10,
+        ..Default::default()
     };
 
     game.start_game(params).await?;
@@ -82,7 +148,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     // Reveal the corner cell (0, 0)
     println!("Revealing cell (0, 0)...");
-    game.reveal(Pos { x: 0, y: 0 }).await?;
+    game.reveal_xy(0, 0).await?;
     sleep(Duration::from_millis(100)).await;
 
     if let Some(state) = game.get_state().await {
@@ -94,7 +160,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     // Try to flag a cell (1, 1)
     println!("\nFlagging cell (1, 1)...");
-    game.flag(Pos { x: 1, y: 1 }).await?;
+    game.flag_xy(1, 1).await?;
     sleep(Duration::from_millis(100)).await;
 
     if let Some(state) = game.get_state().await {
@@ -103,7 +169,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     // Try to reveal another cell (2, 2)
     println!("\nRevealing cell (2, 2)...");
-    game.reveal(Pos { x: 2, y: 2 }).await?;
+    game.reveal_xy(2, 2).await?;
     sleep(Duration::from_millis(100)).await;
 
     if let Some(state) = game.get_state().await {
@@ -115,7 +181,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     // Flag the same cell again (should unflag it)
     println!("\nUnflagging cell (1, 1)...");
-    game.flag(Pos { x: 1, y: 1 }).await?;
+    game.flag_xy(1, 1).await?;
     sleep(Duration::from_millis(100)).await;
 
     if let Some(state) = game.get_state().await {
@@ -136,44 +202,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 }
 
 fn display_board(state: &minesweeper_client::GameState) {
-    println!("Board state:");
-    for (y, row) in state.board.iter().enumerate() {
-        print!("  ");
-        for (_x, cell) in row.iter().enumerate() {
-            let symbol = match cell {
-                minesweeper_client::Cell::Hidden => "·",
-                minesweeper_client::Cell::Marked => "?",
-                minesweeper_client::Cell::Flagged => "F",
-                minesweeper_client::Cell::Revealed { adjacent } => {
-                    match adjacent {
-                        0 => " ",
-                        n => {
-                            // Use a simple character representation for numbers
-                            match n {
-                                1 => "1",
-                                2 => "2",
-                                3 => "3",
-                                4 => "4",
-                                5 => "5",
-                                6 => "6",
-                                7 => "7",
-                                8 => "8",
-                                _ => "X",
-                            }
-                        }
-                    }
-                }
-                minesweeper_client::Cell::Bomb => "💣",
-            };
-            print!("{:2}", symbol);
-        }
-        println!("  {}", y);
-    }
-
-    // Print x coordinates
-    print!("  ");
-    for x in 0..state.width {
-        print!("{:2}", x);
-    }
-    println!();
+    println!(
+        "Board state (💣 {} remaining, ⏱ {}s):",
+        state.mines_remaining(),
+        state.elapsed().as_secs()
+    );
+    print!("{}", state);
 }
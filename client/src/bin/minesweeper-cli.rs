@@ -0,0 +1,178 @@
+//! Play minesweeper from the terminal on top of `MinesweeperGame`.
+//!
+//! ```text
+//! minesweeper-cli [--ascii] [URL] [GAME_ID]
+//! ```
+//!
+//! `URL` defaults to `MINESWEEPER_URL` or `http://localhost:8000`. With a `GAME_ID` (or
+//! `MINESWEEPER_GAME_ID`) the game is joined, otherwise a new default game is created.
+//! `--ascii` draws the board with `render_ascii` instead of the Unicode glyphs.
+
+#[cfg(not(target_arch = "wasm32"))]
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    cli::run().await
+}
+
+/// The CLI needs a terminal and tokio's IO, neither of which exist in the browser
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod cli {
+    use std::env;
+
+    use minesweeper_client::{GameEvent, GameParams, GameState, MinesweeperGame, Pos, Result};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    const DEFAULT_URL: &str = "http://localhost:8000";
+    const HELP: &str = "Commands: r X Y (reveal), f X Y (flag), c X Y (chord), restart, quit";
+
+    enum Command {
+        Reveal(Pos),
+        Flag(Pos),
+        Chord(Pos),
+        Restart,
+        Quit,
+    }
+
+    fn parse_command(line: &str) -> Option<Command> {
+        let mut words = line.split_whitespace();
+        let command = words.next()?;
+        let mut pos = || -> Option<Pos> {
+            let x = words.next()?.parse().ok()?;
+            let y = words.next()?.parse().ok()?;
+            Some(Pos { x, y })
+        };
+
+        match command {
+            "r" | "reveal" => pos().map(Command::Reveal),
+            "f" | "flag" => pos().map(Command::Flag),
+            "c" | "chord" => pos().map(Command::Chord),
+            "restart" => Some(Command::Restart),
+            "q" | "quit" | "exit" => Some(Command::Quit),
+            _ => None,
+        }
+    }
+
+    struct Args {
+        url: String,
+        game_id: Option<String>,
+        ascii: bool,
+    }
+
+    fn parse_args() -> Args {
+        let mut ascii = false;
+        let mut positional = Vec::new();
+        for arg in env::args().skip(1) {
+            if arg == "--ascii" {
+                ascii = true;
+            } else {
+                positional.push(arg);
+            }
+        }
+        let mut positional = positional.into_iter();
+
+        Args {
+            url: positional
+                .next()
+                .or_else(|| env::var("MINESWEEPER_URL").ok())
+                .unwrap_or_else(|| DEFAULT_URL.to_string()),
+            game_id: positional
+                .next()
+                .or_else(|| env::var("MINESWEEPER_GAME_ID").ok()),
+            ascii,
+        }
+    }
+
+    fn draw(state: &GameState, ascii: bool) {
+        if ascii {
+            print!("{}", state.render_ascii());
+        } else {
+            print!("{}", state);
+        }
+        println!(
+            "  {} bombs, {} flagged, {}s",
+            state.bombs, state.flags_placed, state.elapsed_secs
+        );
+    }
+
+    /// Prints what an event means for the player and redraws the board when it changed
+    async fn handle_event(game: &MinesweeperGame, event: GameEvent, ascii: bool) {
+        match event {
+            GameEvent::GameInitialized { .. } | GameEvent::BoardUpdated { .. } => {
+                game.with_state(|state| draw(state, ascii)).await;
+            }
+            GameEvent::GameStatusChanged { won: true, .. } => println!("You won!"),
+            GameEvent::GameStatusChanged { lost: true, .. } => {
+                println!("Boom! Type `restart` to play again.")
+            }
+            GameEvent::Error { message, .. } => println!("Server: {}", message),
+            GameEvent::Reconnecting { attempt } => println!("Reconnecting (attempt {})", attempt),
+            GameEvent::ConnectionLost => println!("Connection lost"),
+            _ => {}
+        }
+    }
+
+    /// The current board's size and bomb count with default settings otherwise
+    async fn current_params(game: &MinesweeperGame) -> GameParams {
+        game.with_state(|state| GameParams {
+            width: state.width,
+            height: state.height,
+            bombs: state.bombs,
+            ..Default::default()
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    pub async fn run() -> Result<()> {
+        let args = parse_args();
+        let game = MinesweeperGame::new(&args.url)?;
+        game.enable_auto_reconnect(5);
+        let mut events = game.subscribe_to_events().await;
+
+        match args.game_id {
+            Some(game_id) => game.join_game(game_id).await?,
+            None => game.start_game(GameParams::default()).await?,
+        }
+        if let Some(game_id) = game.get_game_id().await {
+            println!("Playing game {} on {}", game_id, args.url);
+        }
+        println!("{}", HELP);
+
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Some(event) => handle_event(&game, event, args.ascii).await,
+                    None => break,
+                },
+                line = lines.next_line() => {
+                    // End of input quits like `quit`
+                    let Some(line) = line? else { break };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    let result = match parse_command(&line) {
+                        Some(Command::Reveal(pos)) => game.reveal(pos).await,
+                        Some(Command::Flag(pos)) => game.flag(pos).await,
+                        Some(Command::Chord(pos)) => game.chord(pos).await,
+                        Some(Command::Restart) => game.restart(current_params(&game).await).await,
+                        Some(Command::Quit) => break,
+                        None => {
+                            println!("{}", HELP);
+                            continue;
+                        }
+                    };
+                    if let Err(e) = result {
+                        println!("{}", e);
+                    }
+                }
+            }
+        }
+
+        game.disconnect().await
+    }
+}
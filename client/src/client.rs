@@ -1,41 +1,177 @@
-use minesweeper_common::models::{CreateResponse, GameParams};
+use minesweeper_common::{
+    mask::RevealedMask,
+    models::{BoardSolution, CreateResponse, GameParams, GameStatus, JoinResponse},
+};
 use reqwest::Client;
 use url::Url;
+use uuid::Uuid;
 
 use crate::Result;
 
+/// Options sent as query parameters when opening a game WebSocket
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectOptions {
+    /// Ask the server to include breadth-first cascade depths in reveal updates
+    pub cascade_depth: bool,
+    /// Join read-only: receive updates but never change the board
+    pub spectate: bool,
+    /// Resume as the player this token was issued to (see `GameState::player_token`)
+    pub token: Option<Uuid>,
+    /// With `token`, the last revision seen (see `GameState::revision`); the server then
+    /// sends only the changes since instead of the whole board, if it still has them
+    pub revision: Option<u64>,
+}
+
+/// Builds the HTTP client with the TLS backend selected by the crate features, matching
+/// the one `tokio-tungstenite` picks for `wss://` connections
+fn http_client() -> Result<Client> {
+    let builder = Client::builder();
+    #[cfg(all(feature = "native-tls", not(feature = "wasm")))]
+    let builder = builder.use_native_tls();
+    #[cfg(all(feature = "rustls", not(feature = "native-tls"), not(feature = "wasm")))]
+    let builder = builder.use_rustls_tls();
+    Ok(builder.build()?)
+}
+
 /// HTTP client for minesweeper server API
 pub struct MinesweeperClient {
     client: Client,
     base_url: Url,
+    api_token: Option<String>,
 }
 
 impl MinesweeperClient {
     /// Create a new client connecting to the specified server URL
     pub fn new(base_url: &str) -> Result<Self> {
         let base_url = Url::parse(base_url)?;
-        let client = Client::new();
+        let client = http_client()?;
 
-        Ok(Self { client, base_url })
+        Ok(Self {
+            client,
+            base_url,
+            api_token: None,
+        })
+    }
+
+    /// Send this token as `Authorization: Bearer` when creating games, for servers that
+    /// set `CREATE_API_TOKEN`
+    pub fn with_api_token(mut self, token: impl Into<String>) -> Self {
+        self.api_token = Some(token.into());
+        self
+    }
+
+    /// POST `params` to the create endpoint, authenticated when an API token is set
+    async fn post_create(&self, create_url: Url, params: &GameParams) -> Result<CreateResponse> {
+        let mut request = self.client.post(create_url).json(params);
+        if let Some(token) = &self.api_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Failed to create game: {}", response.status()).into());
+        }
+
+        Ok(response.json().await?)
     }
 
     /// Create a new game with the specified parameters
     /// Returns the game ID that can be used to connect via WebSocket
     pub async fn create_game(&self, params: GameParams) -> Result<String> {
+        Ok(self.create_game_detailed(params).await?.id)
+    }
+
+    /// Create a new game and return its ID together with the parameters the server
+    /// actually used, which may differ from the requested ones after clamping
+    pub async fn create_game_detailed(&self, params: GameParams) -> Result<CreateResponse> {
+        params.validate()?;
         let create_url = self.base_url.join("/create")?;
 
-        let response = self.client.post(create_url).json(&params).send().await?;
+        self.post_create(create_url, &params).await
+    }
+
+    /// Get the ID of a running public game with room for another player. The server
+    /// creates a new public game when none is open.
+    pub async fn join_public_game(&self) -> Result<JoinResponse> {
+        let join_url = self.base_url.join("/join")?;
+
+        let mut request = self.client.post(join_url);
+        if let Some(token) = &self.api_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?;
 
         if !response.status().is_success() {
-            return Err(format!("Failed to create game: {}", response.status()).into());
+            return Err(format!("Failed to join a public game: {}", response.status()).into());
         }
 
-        let create_response: CreateResponse = response.json().await?;
-        Ok(create_response.id)
+        Ok(response.json().await?)
+    }
+
+    /// Get whether a game is finished and won without opening a WebSocket
+    pub async fn get_game_status(&self, game_id: &str) -> Result<GameStatus> {
+        let status_url = self.base_url.join(&format!("/games/{}/status", game_id))?;
+
+        let response = self.client.get(status_url).send().await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Failed to get game status: {}", response.status()).into());
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Fetch the compact revealed/flagged mask of a game.
+    /// Use [`RevealedMask::decode`] to unpack it into per-cell states.
+    pub async fn get_revealed_mask(&self, game_id: &str) -> Result<RevealedMask> {
+        let mask_url = self.base_url.join(&format!("/games/{}/mask", game_id))?;
+
+        let response = self.client.get(mask_url).send().await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Failed to get revealed mask: {}", response.status()).into());
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Fetch the bomb layout of a finished game. The server refuses with `403 Forbidden`
+    /// while the game is still running.
+    pub async fn get_solution(&self, game_id: &str) -> Result<BoardSolution> {
+        let solution_url = self.base_url.join(&format!("/games/{}/reveal", game_id))?;
+
+        let response = self.client.get(solution_url).send().await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Failed to get game solution: {}", response.status()).into());
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Create a new game and ask the server to return the seed it was generated from,
+    /// so the same board can be recreated later by passing the seed in `GameParams`
+    pub async fn create_practice_game(&self, params: GameParams) -> Result<CreateResponse> {
+        params.validate()?;
+        let mut create_url = self.base_url.join("/create")?;
+        create_url.set_query(Some("return_seed=true"));
+
+        self.post_create(create_url, &params).await
     }
 
     /// Get the WebSocket URL for a game
     pub fn websocket_url(&self, game_id: &str) -> Result<String> {
+        self.websocket_url_with_options(game_id, &ConnectOptions::default())
+    }
+
+    /// Get the WebSocket URL for a game with additional connection options
+    pub fn websocket_url_with_options(
+        &self,
+        game_id: &str,
+        options: &ConnectOptions,
+    ) -> Result<String> {
         let mut ws_url = self.base_url.clone();
         ws_url
             .set_scheme(match self.base_url.scheme() {
@@ -45,6 +181,24 @@ impl MinesweeperClient {
             .map_err(|_| "Failed to set WebSocket scheme")?;
         ws_url.set_path("/ws");
         ws_url.set_query(Some(&format!("id={}", game_id)));
+        if options.cascade_depth {
+            ws_url
+                .query_pairs_mut()
+                .append_pair("cascade_depth", "true");
+        }
+        if options.spectate {
+            ws_url.query_pairs_mut().append_pair("spectate", "true");
+        }
+        if let Some(token) = options.token {
+            ws_url
+                .query_pairs_mut()
+                .append_pair("token", &token.to_string());
+        }
+        if let Some(revision) = options.revision {
+            ws_url
+                .query_pairs_mut()
+                .append_pair("rev", &revision.to_string());
+        }
 
         Ok(ws_url.to_string())
     }
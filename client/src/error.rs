@@ -0,0 +1,29 @@
+use std::fmt;
+
+use minesweeper_common::models::Pos;
+
+/// Errors detected by the client before anything is sent to the server. They are
+/// returned boxed in [`crate::Result`]; use `downcast_ref::<ClientError>()` to match them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientError {
+    /// The position lies outside the board of the current game
+    OutOfBounds {
+        pos: Pos,
+        width: usize,
+        height: usize,
+    },
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { pos, width, height } => write!(
+                f,
+                "Position ({}, {}) is outside the {}x{} board",
+                pos.x, pos.y, width, height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
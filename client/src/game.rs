@@ -1,36 +1,155 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
 
 use minesweeper_common::{
     models::{Cell, GameParams, Pos},
-    protocol::{ClientMessage, ServerMessage},
+    protocol::{CellUpdate, ClientMessage, EfficiencyStats, MAX_CHAT_LENGTH, ServerMessage},
 };
-use tokio::sync::{RwLock, mpsc};
-use tokio::task::JoinHandle;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{RwLock, mpsc, oneshot};
 use tracing::{debug, info, warn};
+use uuid::Uuid;
 
-use crate::{MinesweeperClient, MinesweeperWebSocket, Result};
+use crate::{
+    ClientError, ConnectOptions, MinesweeperClient, MinesweeperWebSocket, Result,
+    runtime::{self, TaskHandle},
+    solver::{self, SolverMove},
+};
 
-/// Events emitted by the minesweeper game
-#[derive(Debug, Clone)]
+/// Delay before the first reconnection attempt, doubled after every failure
+const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+/// Upper bound for the delay between reconnection attempts
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(8);
+/// Pause between autoplay rounds so the server's updates reach the local state
+const AUTOPLAY_SETTLE_DELAY: Duration = Duration::from_millis(100);
+/// How long `reveal_many` waits for the server's updates to reach the local state
+const REVEAL_MANY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Interval at which `reveal_many` checks the local state while waiting
+const REVEAL_MANY_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Events emitted by the minesweeper game.
+/// Serializes as JSON tagged by `"event"`, e.g. `{"event": "connection_lost"}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event")]
 pub enum GameEvent {
     /// The game board was updated with new cell states
+    #[serde(rename = "board_updated")]
     BoardUpdated {
-        /// List of cell positions that changed
-        changed_positions: Vec<Pos>,
+        /// Cells that changed with their new values, so a UI can redraw just these
+        /// without reading the state
+        changes: Vec<CellUpdate>,
+    },
+    /// A reveal cascaded across several cells (requires `ConnectOptions::cascade_depth`)
+    #[serde(rename = "cascade_revealed")]
+    CascadeRevealed {
+        /// Revealed positions with their distance from the clicked cell, in reveal order
+        path: Vec<(Pos, usize)>,
     },
     /// Game status changed (won/lost)
+    #[serde(rename = "game_status_changed")]
     GameStatusChanged { won: bool, lost: bool },
     /// Game was initialized or restarted
+    #[serde(rename = "game_initialized")]
     GameInitialized {
         width: usize,
         height: usize,
         bombs: usize,
     },
+    /// The game was restarted; followed by `GameInitialized` once the new board arrives
+    #[serde(rename = "game_restarted")]
+    GameRestarted {
+        /// Effective parameters of the new game, including its seed
+        params: GameParams,
+        /// Whether width, height, bombs or other settings differ from the previous game
+        params_changed: bool,
+        /// Whether the bomb layout differs from the previous game
+        new_layout: bool,
+    },
+    /// Final statistics for a finished game
+    #[serde(rename = "game_summary")]
+    GameSummary {
+        won: bool,
+        elapsed_ms: u64,
+        moves: u64,
+        reveals: usize,
+        flags: usize,
+        board_cleared_pct: f64,
+        /// Present when the game was created with `GameParams::efficiency_stats`
+        efficiency: Option<EfficiencyStats>,
+    },
+    /// The server will remove the idle game soon unless there is activity
+    #[serde(rename = "expiry_warning")]
+    ExpiryWarning { seconds_remaining: u64 },
+    /// Someone joined or left; `players` counts every connection, spectators included
+    #[serde(rename = "presence_changed")]
+    PresenceChanged { players: usize },
+    /// Someone paused or resumed the game
+    #[serde(rename = "paused_changed")]
+    PausedChanged { paused: bool },
+    /// A chat message from a connection in this game, including this client's own
+    #[serde(rename = "chat_received")]
+    ChatReceived { from: Uuid, text: String },
+    /// The server rejected one of this client's messages
+    #[serde(rename = "error")]
+    Error {
+        /// Machine-readable reason: `invalid_message`, `out_of_bounds`, `cell_revealed`,
+        /// `cell_flagged`, `game_finished`, `no_hint`, `nothing_to_undo`, `invalid_params`,
+        /// `board_too_large`, `chat_too_long`, `too_fast`, `game_full`, `paused` or `closed`
+        code: String,
+        message: String,
+    },
+    /// The connection dropped and a reconnection attempt is about to start
+    /// (requires [`MinesweeperGame::enable_auto_reconnect`])
+    #[serde(rename = "reconnecting")]
+    Reconnecting { attempt: u32 },
+    /// The connection was re-established; a `GameInitialized` with the current board
+    /// follows, or a `BoardUpdated` with just the missed changes if the server still had them
+    #[serde(rename = "reconnected")]
+    Reconnected,
     /// Connection was lost
+    #[serde(rename = "connection_lost")]
     ConnectionLost,
 }
 
+impl GameEvent {
+    /// Positions changed by a `BoardUpdated` event, empty for every other event
+    pub fn changed_positions(&self) -> Vec<Pos> {
+        match self {
+            GameEvent::BoardUpdated { changes } => {
+                changes.iter().map(|change| change.pos).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The board of a `GameState` as a plain value, e.g. to send to a non-Rust frontend as
+/// JSON or to compare two points of a game in tests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardSnapshot {
+    pub width: usize,
+    pub height: usize,
+    pub bombs: usize,
+    /// Every cell in row-major order, `width * height` in total
+    pub cells: Vec<Cell>,
+    pub game_over: bool,
+    pub won: bool,
+}
+
+impl BoardSnapshot {
+    /// Cell at `pos`, `None` outside the board
+    pub fn cell(&self, pos: Pos) -> Option<Cell> {
+        if pos.x < self.width && pos.y < self.height {
+            self.cells.get(pos.index(self.width)).copied()
+        } else {
+            None
+        }
+    }
+}
+
 /// Represents the current state of a minesweeper game
 #[derive(Debug, Clone)]
 pub struct GameState {
@@ -40,6 +159,21 @@ pub struct GameState {
     pub board: Vec<Vec<Cell>>,
     pub game_over: bool,
     pub won: bool,
+    /// Seconds since the first move as last reported by the server
+    pub elapsed_secs: u64,
+    /// Number of flagged cells as last reported by the server
+    pub flags_placed: usize,
+    /// Connections to the game, spectators included, as last reported by the server
+    pub players: usize,
+    /// Whether the game is paused, blocking reveals and chords
+    pub paused: bool,
+    /// Whether flagging cycles Hidden → Flagged → Marked → Hidden rather than just
+    /// toggling between Hidden and Flagged
+    pub enable_question_marks: bool,
+    moves: u64,
+    revision: u64,
+    player_token: Option<Uuid>,
+    flag_order: Vec<Pos>,
 }
 
 impl GameState {
@@ -52,6 +186,127 @@ impl GameState {
             board,
             game_over: false,
             won: false,
+            elapsed_secs: 0,
+            flags_placed: 0,
+            players: 0,
+            paused: false,
+            enable_question_marks: true,
+            moves: 0,
+            revision: 0,
+            player_token: None,
+            flag_order: Vec::new(),
+        }
+    }
+
+    /// Time since the first move as of the last message from the server
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs(self.elapsed_secs)
+    }
+
+    /// Builds the state described by an `Init` message, `None` for any other message
+    pub fn from_init(message: &ServerMessage) -> Option<Self> {
+        let ServerMessage::Init {
+            width,
+            height,
+            bombs,
+            field,
+            elapsed_secs,
+            flags_placed,
+            moves,
+            your_token,
+            revision,
+            paused,
+            enable_question_marks,
+        } = message
+        else {
+            return None;
+        };
+
+        let mut state = Self::new(*width, *height, *bombs, field.clone());
+        state.elapsed_secs = *elapsed_secs;
+        state.flags_placed = *flags_placed;
+        state.moves = *moves;
+        state.player_token = *your_token;
+        state.revision = *revision;
+        state.paused = *paused;
+        state.enable_question_marks = *enable_question_marks;
+        Some(state)
+    }
+
+    /// Applies a server message to the local state. `Init` replaces the whole state
+    /// (keeping the player token if a broadcast `Init` doesn't carry one, and the player
+    /// count), `Update` patches cells and status, `Presence` sets the player count,
+    /// `Paused` the pause state, and every other message is ignored.
+    pub fn apply(&mut self, message: &ServerMessage) {
+        match message {
+            ServerMessage::Init { .. } => {
+                if let Some(mut state) = Self::from_init(message) {
+                    state.player_token = state.player_token.or(self.player_token);
+                    state.players = self.players;
+                    *self = state;
+                }
+            }
+            ServerMessage::Presence { players } => {
+                self.players = *players;
+            }
+            ServerMessage::Paused { paused } => {
+                self.paused = *paused;
+            }
+            ServerMessage::Update {
+                updates,
+                won,
+                lost,
+                elapsed_secs,
+                flags_placed,
+                moves,
+                revision,
+            } => {
+                self.prune_flag_order(updates);
+                for update in updates {
+                    self.set_cell(update.pos, update.value);
+                }
+
+                self.won = *won;
+                self.game_over = *won || *lost;
+                self.elapsed_secs = *elapsed_secs;
+                self.flags_placed = *flags_placed;
+                self.moves = *moves;
+                self.revision = *revision;
+            }
+            _ => {}
+        }
+    }
+
+    /// Token identifying this player to the server, used to resume after a reconnect
+    pub fn player_token(&self) -> Option<Uuid> {
+        self.player_token
+    }
+
+    /// Moves applied to the game so far as last reported by the server
+    pub fn moves(&self) -> u64 {
+        self.moves
+    }
+
+    /// Board revision as last reported by the server, used to resume after a reconnect
+    /// with only the changes missed in between
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Bombs minus placed flags; negative when more cells are flagged than there are bombs
+    pub fn mines_remaining(&self) -> i64 {
+        self.bombs as i64 - self.flags_placed as i64
+    }
+
+    /// The board and game status, detached from the rest of the state
+    pub fn snapshot(&self) -> BoardSnapshot {
+        BoardSnapshot {
+            width: self.width,
+            height: self.height,
+            bombs: self.bombs,
+            cells: self.board.iter().flatten().copied().collect(),
+            game_over: self.game_over,
+            won: self.won,
         }
     }
 
@@ -93,6 +348,135 @@ impl GameState {
         counts
     }
 
+    /// Number of non-bomb cells that still have to be revealed to win
+    pub fn remaining_safe(&self) -> usize {
+        let revealed = self
+            .board
+            .iter()
+            .flatten()
+            .filter(|cell| cell.is_revealed())
+            .count();
+        (self.width * self.height)
+            .saturating_sub(self.bombs)
+            .saturating_sub(revealed)
+    }
+
+    /// Fraction of non-bomb cells revealed so far, from `0.0` to `1.0`
+    pub fn completion(&self) -> f64 {
+        let safe_cells = (self.width * self.height).saturating_sub(self.bombs);
+        if safe_cells == 0 {
+            return 1.0;
+        }
+        (safe_cells - self.remaining_safe()) as f64 / safe_cells as f64
+    }
+
+    /// Compute the 3BV ("Bechtel's Board Benchmark Value") of the board: the minimum
+    /// number of clicks needed to clear it without flags. Each opening (connected region
+    /// of zero cells together with its numbered border) counts as one click, plus one
+    /// for every numbered cell not bordering an opening.
+    ///
+    /// Requires the full layout, so this returns `None` unless every cell is revealed or
+    /// known to be a bomb (after a win, all unrevealed cells are bombs).
+    pub fn compute_3bv(&self) -> Option<usize> {
+        // `None` marks a bomb, `Some(n)` a safe cell with `n` adjacent bombs
+        let mut layout = Vec::with_capacity(self.width * self.height);
+        for row in &self.board {
+            for cell in row {
+                layout.push(match cell {
+                    Cell::Revealed { adjacent } => Some(*adjacent),
+                    Cell::Bomb => None,
+                    _ if self.won => None,
+                    _ => return None,
+                });
+            }
+        }
+        if layout.len() != self.width * self.height {
+            return None;
+        }
+
+        let neighbors = |index: usize| {
+            Pos::from_index(index, self.width)
+                .neighbors(self.width, self.height)
+                .map(|pos| pos.index(self.width))
+        };
+
+        let mut visited = vec![false; layout.len()];
+        let mut clicks = 0;
+
+        for start in 0..layout.len() {
+            if visited[start] || layout[start] != Some(0) {
+                continue;
+            }
+
+            clicks += 1;
+            visited[start] = true;
+            let mut stack = vec![start];
+            while let Some(index) = stack.pop() {
+                for neighbor in neighbors(index) {
+                    if visited[neighbor] || layout[neighbor].is_none() {
+                        continue;
+                    }
+                    visited[neighbor] = true;
+                    if layout[neighbor] == Some(0) {
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        clicks += layout
+            .iter()
+            .zip(&visited)
+            .filter(|(cell, visited)| cell.is_some() && !**visited)
+            .count();
+
+        Some(clicks)
+    }
+
+    /// Cells flagged by the local player that are still flagged, oldest first
+    pub fn flag_order(&self) -> &[Pos] {
+        &self.flag_order
+    }
+
+    /// The most recent cell flagged by the local player that is still flagged
+    pub fn last_flagged(&self) -> Option<Pos> {
+        self.flag_order.last().copied()
+    }
+
+    /// Track a local flag action before it is sent, based on the cell's current state
+    fn record_flag_toggle(&mut self, pos: Pos) {
+        match self.get_cell(pos) {
+            Some(Cell::Hidden) => {
+                self.flag_order.retain(|p| *p != pos);
+                self.flag_order.push(pos);
+            }
+            Some(Cell::Flagged) => self.flag_order.retain(|p| *p != pos),
+            _ => {}
+        }
+    }
+
+    /// Drop cells from the flag history once the server reports them as no longer flagged
+    fn prune_flag_order(&mut self, updates: &[CellUpdate]) {
+        for update in updates {
+            if !update.value.is_flagged() {
+                self.flag_order.retain(|p| *p != update.pos);
+            }
+        }
+    }
+
+    /// Fails with `ClientError::OutOfBounds` unless `pos` lies on the board
+    pub fn check_bounds(&self, pos: Pos) -> std::result::Result<(), ClientError> {
+        if pos.x < self.width && pos.y < self.height {
+            Ok(())
+        } else {
+            Err(ClientError::OutOfBounds {
+                pos,
+                width: self.width,
+                height: self.height,
+            })
+        }
+    }
+
     /// Check if the game is in a completed state (won or lost)
     pub fn is_game_over(&self) -> bool {
         self.game_over
@@ -102,18 +486,78 @@ impl GameState {
     pub fn is_won(&self) -> bool {
         self.won
     }
+
+    /// Render the board like the `Display` impl, but with pure-ASCII symbols
+    /// (`.` hidden, `*` bomb) for terminals without Unicode or emoji support
+    pub fn render_ascii(&self) -> String {
+        let mut out = String::new();
+        let _ = self.render(&mut out, ".", "*");
+        out
+    }
+
+    /// Write each row followed by its `y` index, then a line of `x` indices
+    fn render(&self, out: &mut impl fmt::Write, hidden: &str, bomb: &str) -> fmt::Result {
+        for (y, row) in self.board.iter().enumerate() {
+            write!(out, "  ")?;
+            for cell in row {
+                let digit;
+                let symbol = match cell {
+                    Cell::Hidden => hidden,
+                    Cell::Marked => "?",
+                    Cell::Flagged => "F",
+                    Cell::Revealed { adjacent: 0 } => " ",
+                    Cell::Revealed { adjacent } => {
+                        digit = adjacent.to_string();
+                        &digit
+                    }
+                    Cell::Bomb => bomb,
+                };
+                write!(out, "{:2}", symbol)?;
+            }
+            writeln!(out, "  {}", y)?;
+        }
+
+        write!(out, "  ")?;
+        for x in 0..self.width {
+            write!(out, "{:2}", x)?;
+        }
+        writeln!(out)
+    }
+}
+
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, "·", "💣")
+    }
 }
 
 /// Connection state - all fields are required when connected
-struct ConnectionState {
-    websocket_sender: mpsc::UnboundedSender<ClientMessage>,
-    game_id: String,
-    background_task: JoinHandle<()>,
+pub(crate) struct ConnectionState {
+    pub(crate) websocket_sender: mpsc::UnboundedSender<ClientMessage>,
+    pub(crate) game_id: String,
+    pub(crate) background_task: TaskHandle,
+    /// Tells the background task to close its socket and finish
+    pub(crate) shutdown: oneshot::Sender<()>,
+}
+
+/// Runs `future` until it completes or `shutdown` fires, returning `None` in the latter
+/// case. Dropping the shutdown sender counts as firing it.
+pub(crate) async fn until_shutdown<F: Future>(
+    future: F,
+    shutdown: &mut oneshot::Receiver<()>,
+) -> Option<F::Output> {
+    use futures_util::future::{Either, select};
+
+    let future = std::pin::pin!(future);
+    match select(future, shutdown).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
 }
 
 impl ConnectionState {
     /// Send a message through the WebSocket connection
-    fn send_message(&self, message: ClientMessage) -> Result<()> {
+    pub(crate) fn send_message(&self, message: ClientMessage) -> Result<()> {
         self.websocket_sender
             .send(message)
             .map_err(|_| "WebSocket sender closed")?;
@@ -126,18 +570,73 @@ impl ConnectionState {
     }
 
     /// Abort the background task and wait for it to finish
-    async fn abort_and_wait_background_task(self) {
+    pub(crate) async fn abort_and_wait_background_task(mut self) {
         self.background_task.abort();
-        let _ = self.background_task.await;
+        self.background_task.join().await;
+    }
+
+    /// Close the connection without losing queued messages: the background task sends
+    /// everything still queued, closes the socket and finishes. With a `timeout`, the
+    /// task is aborted if that takes longer.
+    pub(crate) async fn close(self, timeout: Option<Duration>) {
+        let Self {
+            websocket_sender,
+            game_id,
+            mut background_task,
+            shutdown,
+        } = self;
+        drop(websocket_sender);
+        let _ = shutdown.send(());
+
+        let Some(timeout) = timeout else {
+            background_task.join().await;
+            return;
+        };
+        if runtime::timeout(timeout, background_task.join())
+            .await
+            .is_none()
+        {
+            warn!(
+                "Closing the connection to game {} took longer than {:?}, aborting",
+                game_id, timeout
+            );
+            background_task.abort();
+            background_task.join().await;
+        }
     }
 }
 
+/// Outcome of `MinesweeperGame::autoplay`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoplayResult {
+    /// The game was won
+    pub solved: bool,
+    /// Number of moves sent
+    pub steps: usize,
+}
+
+/// Outcome of `MinesweeperGame::reveal_many`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RevealManyResult {
+    /// Number of reveals sent before the batch finished or the game ended
+    pub sent: usize,
+    /// Number of requested positions revealed in the local state
+    pub revealed: usize,
+    /// The game was lost partway through the batch
+    pub lost: bool,
+    /// The local state had not caught up with every reveal when the timeout elapsed
+    pub timed_out: bool,
+}
+
 /// High-level minesweeper game client that manages game state locally
 pub struct MinesweeperGame {
     client: MinesweeperClient,
+    connect_options: ConnectOptions,
     connection_state: Arc<RwLock<Option<ConnectionState>>>,
     event_sender: Arc<RwLock<Option<mpsc::UnboundedSender<GameEvent>>>>,
+    message_sender: Arc<RwLock<Option<mpsc::UnboundedSender<ServerMessage>>>>,
     state: Arc<RwLock<Option<GameState>>>,
+    reconnect_retries: Arc<AtomicU32>,
 }
 
 impl MinesweeperGame {
@@ -146,12 +645,27 @@ impl MinesweeperGame {
         let client = MinesweeperClient::new(server_url)?;
         Ok(Self {
             client,
+            connect_options: ConnectOptions::default(),
             connection_state: Arc::new(RwLock::new(None)),
             event_sender: Arc::new(RwLock::new(None)),
+            message_sender: Arc::new(RwLock::new(None)),
             state: Arc::new(RwLock::new(None)),
+            reconnect_retries: Arc::new(AtomicU32::new(0)),
         })
     }
 
+    /// Set the options used when connecting to a game
+    pub fn with_connect_options(mut self, options: ConnectOptions) -> Self {
+        self.connect_options = options;
+        self
+    }
+
+    /// Redial the game when the connection drops, up to `max_retries` times with
+    /// exponential backoff from 250ms up to 8s. `0` disables reconnection (the default).
+    pub fn enable_auto_reconnect(&self, max_retries: u32) {
+        self.reconnect_retries.store(max_retries, Ordering::Relaxed);
+    }
+
     /// Subscribe to game events. Returns a receiver for game events.
     pub async fn subscribe_to_events(&self) -> mpsc::UnboundedReceiver<GameEvent> {
         let (sender, receiver) = mpsc::unbounded_channel();
@@ -160,6 +674,14 @@ impl MinesweeperGame {
         receiver
     }
 
+    /// Subscribe to the raw messages received from the server, before they are applied
+    /// to the local state. Used by `Recorder`.
+    pub async fn subscribe_to_messages(&self) -> mpsc::UnboundedReceiver<ServerMessage> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.message_sender.write().await = Some(sender);
+        receiver
+    }
+
     /// Start a new game with the specified parameters
     pub async fn start_game(&self, params: GameParams) -> Result<()> {
         info!(
@@ -174,9 +696,34 @@ impl MinesweeperGame {
         self.join_game(game_id).await
     }
 
+    /// Join any open public game, or a newly created one if none has room
+    pub async fn join_public_game(&self) -> Result<()> {
+        let joined = self.client.join_public_game().await?;
+        info!(
+            "Lobby assigned game {} (created: {})",
+            joined.id, joined.created
+        );
+
+        self.join_game(joined.id).await
+    }
+
     pub async fn join_game(&self, game_id: String) -> Result<()> {
         info!("Joining game with ID: {}", game_id);
+        self.connect(game_id, self.connect_options).await
+    }
 
+    /// Watch a game without playing. Updates arrive as usual, but the server answers
+    /// any action with an `Error` event carrying the `spectator` code.
+    pub async fn join_as_spectator(&self, game_id: String) -> Result<()> {
+        info!("Spectating game with ID: {}", game_id);
+        let options = ConnectOptions {
+            spectate: true,
+            ..self.connect_options
+        };
+        self.connect(game_id, options).await
+    }
+
+    async fn connect(&self, game_id: String, options: ConnectOptions) -> Result<()> {
         let mut conn_state = self.connection_state.write().await;
 
         // Stop any existing background task
@@ -186,20 +733,31 @@ impl MinesweeperGame {
         self.state.write().await.take();
 
         // Connect to the game via WebSocket
-        let ws_url = self.client.websocket_url(&game_id)?;
+        let ws_url = self.client.websocket_url_with_options(&game_id, &options)?;
         let websocket = MinesweeperWebSocket::connect(&ws_url).await?;
         let websocket_sender = websocket.get_sender();
+        let (shutdown, shutdown_receiver) = oneshot::channel();
 
         info!("Connected to game with ID: {}", game_id);
 
-        // Start background message listener
-        let background_task = self.start_background_listener(websocket);
+        // Start background message listener. Reconnects use the player token from the
+        // latest `Init` rather than the one we connected with.
+        let base_url = self.client.websocket_url_with_options(
+            &game_id,
+            &ConnectOptions {
+                token: None,
+                ..options
+            },
+        )?;
+        let background_task =
+            self.start_background_listener(websocket, base_url, shutdown_receiver);
 
         // Create new connection state
         *conn_state = Some(ConnectionState {
             websocket_sender,
             game_id,
             background_task,
+            shutdown,
         });
 
         Ok(())
@@ -218,28 +776,213 @@ impl MinesweeperGame {
         Ok(())
     }
 
-    /// Reveal a cell at the specified position
+    /// Fails with `ClientError::OutOfBounds` if `pos` is off the board. Passes while no
+    /// board has been received yet, leaving the check to the server.
+    async fn ensure_in_bounds(&self, pos: Pos) -> Result<()> {
+        match self.with_state(|state| state.check_bounds(pos)).await {
+            Some(Err(error)) => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reveal a cell at the specified position. Positions outside a known board fail with
+    /// `ClientError::OutOfBounds` without contacting the server.
     pub async fn reveal(&self, pos: Pos) -> Result<()> {
         debug!("Revealing cell at ({}, {})", pos.x, pos.y);
+        self.ensure_in_bounds(pos).await?;
 
         let message = ClientMessage::Reveal { pos };
         self.send_client_message(message).await
     }
 
-    /// Flag/unflag a cell at the specified position
+    /// Reveal several cells in one go. The reveals are sent back-to-back without waiting
+    /// for the server in between, then this waits up to 5 seconds until the local state
+    /// shows every position settled. Sending stops early once the game is over, e.g.
+    /// when one of the positions was a bomb.
+    ///
+    /// Flagged and out-of-bounds positions count as settled, since the server ignores
+    /// or rejects reveals on them. Servers drop messages beyond their per-connection
+    /// limit (50 per second by default), so larger batches can end with `timed_out`.
+    pub async fn reveal_many(&self, positions: &[Pos]) -> Result<RevealManyResult> {
+        debug!("Revealing {} cells", positions.len());
+
+        let mut sent = 0;
+        for &pos in positions {
+            if self
+                .with_state(GameState::is_game_over)
+                .await
+                .unwrap_or(false)
+            {
+                break;
+            }
+            self.send_client_message(ClientMessage::Reveal { pos })
+                .await?;
+            sent += 1;
+        }
+
+        let sent_positions = &positions[..sent];
+        let deadline = runtime::Instant::now() + REVEAL_MANY_TIMEOUT;
+        loop {
+            let (game_over, won, revealed, settled) = self
+                .with_state(|state| {
+                    let revealed = sent_positions
+                        .iter()
+                        .filter(|&&pos| state.get_cell(pos).is_some_and(Cell::is_revealed))
+                        .count();
+                    let settled = sent_positions
+                        .iter()
+                        .all(|&pos| state.get_cell(pos).is_none_or(|cell| !cell.is_hidden()));
+                    (state.is_game_over(), state.is_won(), revealed, settled)
+                })
+                .await
+                .unwrap_or((false, false, 0, false));
+
+            let timed_out = runtime::Instant::now() >= deadline;
+            if game_over || settled || timed_out {
+                let result = RevealManyResult {
+                    sent,
+                    revealed,
+                    lost: game_over && !won,
+                    timed_out: timed_out && !game_over && !settled,
+                };
+                debug!("Batch reveal finished: {:?}", result);
+                return Ok(result);
+            }
+
+            runtime::sleep(REVEAL_MANY_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Reveal the cell at column `x`, row `y`
+    pub async fn reveal_xy(&self, x: usize, y: usize) -> Result<()> {
+        self.reveal(Pos { x, y }).await
+    }
+
+    /// Flag/unflag a cell at the specified position. Positions outside a known board fail
+    /// with `ClientError::OutOfBounds` without contacting the server.
     pub async fn flag(&self, pos: Pos) -> Result<()> {
         debug!("Flagging cell at ({}, {})", pos.x, pos.y);
+        self.ensure_in_bounds(pos).await?;
+
+        if let Some(ref mut state) = *self.state.write().await {
+            state.record_flag_toggle(pos);
+        }
 
         let message = ClientMessage::Flag { pos };
         self.send_client_message(message).await
     }
 
+    /// Toggle the flag on the cell at column `x`, row `y`
+    pub async fn flag_xy(&self, x: usize, y: usize) -> Result<()> {
+        self.flag(Pos { x, y }).await
+    }
+
+    /// Chord a revealed number: reveal its hidden neighbours once enough of them are flagged
+    pub async fn chord(&self, pos: Pos) -> Result<()> {
+        debug!("Chording cell at ({}, {})", pos.x, pos.y);
+
+        let message = ClientMessage::Chord { pos };
+        self.send_client_message(message).await
+    }
+
+    /// Ask the server to revert the last action. The board is replaced by the server's
+    /// resync once it arrives.
+    pub async fn undo(&self) -> Result<()> {
+        debug!("Requesting undo");
+
+        self.send_client_message(ClientMessage::Undo).await
+    }
+
+    /// Ask the server to reveal one guaranteed-safe hidden cell
+    pub async fn hint(&self) -> Result<()> {
+        debug!("Requesting hint");
+
+        self.send_client_message(ClientMessage::Hint).await
+    }
+
+    /// Pause the game for everyone: the timer stops and reveals and chords are rejected
+    /// with a `paused` error until someone resumes. Flags follow
+    /// `GameParams::flags_while_paused`.
+    pub async fn pause(&self) -> Result<()> {
+        debug!("Requesting pause");
+
+        self.send_client_message(ClientMessage::Pause).await
+    }
+
+    /// Resume a paused game
+    pub async fn resume(&self) -> Result<()> {
+        debug!("Requesting resume");
+
+        self.send_client_message(ClientMessage::Resume).await
+    }
+
+    /// Only receive updates for cells inside the given region (inclusive). The server
+    /// replies with the current state of the region; cells outside it go stale locally.
+    pub async fn set_viewport(&self, top_left: Pos, bottom_right: Pos) -> Result<()> {
+        debug!(
+            "Setting viewport to ({}, {})-({}, {})",
+            top_left.x, top_left.y, bottom_right.x, bottom_right.y
+        );
+
+        let message = ClientMessage::Viewport {
+            top_left,
+            bottom_right,
+        };
+        self.send_client_message(message).await
+    }
+
+    /// Send a chat message to everyone in the game. It comes back as
+    /// `GameEvent::ChatReceived` once the server relays it.
+    pub async fn chat(&self, text: impl Into<String>) -> Result<()> {
+        let text = text.into();
+        if text.chars().count() > MAX_CHAT_LENGTH {
+            return Err(format!(
+                "Chat messages are limited to {} characters",
+                MAX_CHAT_LENGTH
+            )
+            .into());
+        }
+        debug!("Sending chat message");
+
+        self.send_client_message(ClientMessage::Chat { text }).await
+    }
+
+    /// Ask the server for the current board, replacing the local state once it arrives.
+    /// Useful to recover from a local state that drifted from the server.
+    pub async fn request_state(&self) -> Result<()> {
+        debug!("Requesting current game state");
+
+        self.send_client_message(ClientMessage::GetState).await
+    }
+
+    /// Unflag the most recently flagged cell, returning its position.
+    /// The cell is cycled through the marked state back to hidden, or toggled straight
+    /// back when the game has question marks disabled.
+    pub async fn unflag_last(&self) -> Result<Option<Pos>> {
+        let Some((pos, question_marks)) = self
+            .with_state(|state| Some((state.last_flagged()?, state.enable_question_marks)))
+            .await
+            .flatten()
+        else {
+            return Ok(None);
+        };
+
+        debug!("Unflagging last flagged cell at ({}, {})", pos.x, pos.y);
+        // Flagged -> Marked -> Hidden, or Flagged -> Hidden without question marks
+        let toggles = if question_marks { 2 } else { 1 };
+        for _ in 0..toggles {
+            self.flag(pos).await?;
+        }
+        Ok(Some(pos))
+    }
+
     /// Restart the game with new parameters
     pub async fn restart(&self, params: GameParams) -> Result<()> {
         info!(
             "Restarting game with new parameters: {}x{} with {} bombs",
             params.width, params.height, params.bombs
         );
+        params.validate()?;
 
         let message = ClientMessage::Restart { params };
         self.send_client_message(message).await
@@ -250,6 +993,67 @@ impl MinesweeperGame {
         self.state.read().await.clone()
     }
 
+    /// Run a closure against the current game state without cloning the board.
+    /// The state's read lock is held while `f` runs, so keep it short.
+    pub async fn with_state<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&GameState) -> R,
+    {
+        self.state.read().await.as_ref().map(f)
+    }
+
+    /// Play the moves proven by `solver::next_moves` until the game is over, `max_steps`
+    /// moves have been sent, or no deterministic move is left. Waits briefly after each
+    /// round for the server's updates. A move is never sent twice, since a repeated flag
+    /// would toggle the cell back.
+    pub async fn autoplay(&self, max_steps: usize) -> Result<AutoplayResult> {
+        let mut sent = HashSet::new();
+        let mut steps = 0;
+
+        while steps < max_steps {
+            let Some((game_over, moves)) = self
+                .with_state(|state| (state.is_game_over(), solver::next_moves(state)))
+                .await
+            else {
+                break;
+            };
+            if game_over {
+                break;
+            }
+
+            let moves: Vec<SolverMove> = moves
+                .into_iter()
+                .filter(|solver_move| !sent.contains(solver_move))
+                .take(max_steps - steps)
+                .collect();
+            if moves.is_empty() {
+                debug!(
+                    "Autoplay found no new deterministic move after {} steps",
+                    steps
+                );
+                break;
+            }
+
+            for solver_move in moves {
+                match solver_move {
+                    SolverMove::Reveal(pos) => self.reveal(pos).await?,
+                    SolverMove::Flag(pos) => self.flag(pos).await?,
+                }
+                sent.insert(solver_move);
+                steps += 1;
+            }
+
+            runtime::sleep(AUTOPLAY_SETTLE_DELAY).await;
+        }
+
+        let solved = self.with_state(GameState::is_won).await.unwrap_or(false);
+        info!(
+            "Autoplay finished after {} steps, solved: {}",
+            steps, solved
+        );
+        Ok(AutoplayResult { solved, steps })
+    }
+
     /// Get the game ID
     pub async fn get_game_id(&self) -> Option<String> {
         let conn_state = self.connection_state.read().await;
@@ -262,16 +1066,29 @@ impl MinesweeperGame {
         conn_state.is_some()
     }
 
-    /// Close the connection and clean up
+    /// Close the connection and clean up. Actions sent just before are still delivered:
+    /// this waits until the queued messages are written and the socket is closed.
     pub async fn disconnect(&self) -> Result<()> {
-        let mut conn_state = self.connection_state.write().await;
+        self.close_connection(None).await
+    }
 
-        if let Some(conn) = conn_state.take() {
-            conn.abort_and_wait_background_task().await;
+    /// Like [`MinesweeperGame::disconnect`], but aborts the connection if flushing the
+    /// queued messages and closing the socket takes longer than `timeout`, e.g. because
+    /// the server stopped reading
+    pub async fn disconnect_with_timeout(&self, timeout: Duration) -> Result<()> {
+        self.close_connection(Some(timeout)).await
+    }
+
+    async fn close_connection(&self, timeout: Option<Duration>) -> Result<()> {
+        // Release the lock before waiting, the background task takes it when it reconnects
+        let conn = self.connection_state.write().await.take();
+        if let Some(conn) = conn {
+            conn.close(timeout).await;
         }
 
-        // Clear event sender
+        // Clear event and message senders
         *self.event_sender.write().await = None;
+        *self.message_sender.write().await = None;
 
         // Clear game state
         *self.state.write().await = None;
@@ -280,108 +1097,663 @@ impl MinesweeperGame {
         Ok(())
     }
 
-    /// Start background WebSocket message listener
-    fn start_background_listener(&self, mut websocket: MinesweeperWebSocket) -> JoinHandle<()> {
+    /// Start background WebSocket message listener. When the connection drops it
+    /// reconnects if enabled, otherwise it emits `ConnectionLost` and stops. Once
+    /// `shutdown` fires it closes the socket, flushing queued messages, and stops.
+    fn start_background_listener(
+        &self,
+        mut websocket: MinesweeperWebSocket,
+        ws_url: String,
+        mut shutdown: oneshot::Receiver<()>,
+    ) -> TaskHandle {
         let state = self.state.clone();
         let event_sender = self.event_sender.clone();
+        let message_sender = self.message_sender.clone();
+        let connection_state = self.connection_state.clone();
+        let reconnect_retries = self.reconnect_retries.clone();
 
-        tokio::spawn(async move {
-            Self::background_message_handler(&mut websocket, state, event_sender).await;
+        runtime::spawn(async move {
+            loop {
+                let handler = Self::background_message_handler(
+                    &mut websocket,
+                    state.clone(),
+                    event_sender.clone(),
+                    message_sender.clone(),
+                );
+                if until_shutdown(handler, &mut shutdown).await.is_none() {
+                    let _ = websocket.close().await;
+                    return;
+                }
+
+                let resume = state
+                    .read()
+                    .await
+                    .as_ref()
+                    .and_then(|state| Some((state.player_token()?, state.revision())));
+                let url = match resume {
+                    Some((token, revision)) => {
+                        format!("{}&token={}&rev={}", ws_url, token, revision)
+                    }
+                    None => ws_url.clone(),
+                };
+                let max_retries = reconnect_retries.load(Ordering::Relaxed);
+                let reconnect = Self::reconnect(&url, max_retries, &event_sender);
+                let Some(reconnected) = until_shutdown(reconnect, &mut shutdown).await else {
+                    return;
+                };
+                let Some(reconnected) = reconnected else {
+                    if let Some(ref sender) = *event_sender.read().await {
+                        let _ = sender.send(GameEvent::ConnectionLost);
+                    }
+                    break;
+                };
+
+                if let Some(ref mut conn) = *connection_state.write().await {
+                    conn.websocket_sender = reconnected.get_sender();
+                }
+                websocket = reconnected;
+
+                if let Some(ref sender) = *event_sender.read().await {
+                    let _ = sender.send(GameEvent::Reconnected);
+                }
+            }
         })
     }
 
-    /// Background task that handles incoming WebSocket messages
+    /// Redial `ws_url` up to `max_retries` times with exponential backoff
+    async fn reconnect(
+        ws_url: &str,
+        max_retries: u32,
+        event_sender: &RwLock<Option<mpsc::UnboundedSender<GameEvent>>>,
+    ) -> Option<MinesweeperWebSocket> {
+        let mut backoff = RECONNECT_INITIAL_BACKOFF;
+
+        for attempt in 1..=max_retries {
+            if let Some(ref sender) = *event_sender.read().await {
+                let _ = sender.send(GameEvent::Reconnecting { attempt });
+            }
+
+            runtime::sleep(backoff).await;
+            match MinesweeperWebSocket::connect(ws_url).await {
+                Ok(websocket) => {
+                    info!("Reconnected after {} attempt(s)", attempt);
+                    return Some(websocket);
+                }
+                Err(e) => {
+                    warn!("Reconnection attempt {} failed: {}", attempt, e);
+                    backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Handles incoming WebSocket messages until the connection closes or fails
     async fn background_message_handler(
         websocket: &mut MinesweeperWebSocket,
         state: Arc<RwLock<Option<GameState>>>,
         event_sender: Arc<RwLock<Option<mpsc::UnboundedSender<GameEvent>>>>,
+        message_sender: Arc<RwLock<Option<mpsc::UnboundedSender<ServerMessage>>>>,
     ) {
         loop {
             let message = match websocket.receive_message().await {
                 Ok(Some(msg)) => msg,
                 Ok(None) => {
-                    // Connection closed
-                    if let Some(ref sender) = *event_sender.read().await {
-                        let _ = sender.send(GameEvent::ConnectionLost);
-                    }
+                    info!("WebSocket connection closed");
                     break;
                 }
                 Err(e) => {
                     warn!("Error receiving WebSocket message: {}", e);
-                    if let Some(ref sender) = *event_sender.read().await {
-                        let _ = sender.send(GameEvent::ConnectionLost);
-                    }
                     break;
                 }
             };
 
-            match message {
-                ServerMessage::Init {
-                    width,
-                    height,
-                    bombs,
-                    field,
-                } => {
-                    info!(
-                        "Received game initialization: {}x{} with {} bombs",
-                        width, height, bombs
-                    );
-
-                    let new_state = GameState::new(width, height, bombs, field);
-                    *state.write().await = Some(new_state);
+            if let Some(ref sender) = *message_sender.read().await {
+                let _ = sender.send(message.clone());
+            }
 
-                    if let Some(ref sender) = *event_sender.read().await {
-                        let _ = sender.send(GameEvent::GameInitialized {
-                            width,
-                            height,
-                            bombs,
-                        });
-                    }
+            let events = apply_server_message(&mut *state.write().await, message);
+            if let Some(ref sender) = *event_sender.read().await {
+                for event in events {
+                    let _ = sender.send(event);
                 }
-                ServerMessage::Update { updates, won, lost } => {
-                    debug!(
-                        "Received update: {} cells updated, won: {}, lost: {}",
-                        updates.len(),
-                        won,
-                        lost
-                    );
-
-                    let changed_positions: Vec<Pos> = updates.iter().map(|u| u.pos).collect();
-                    let status_changed;
-
-                    {
-                        let mut state_guard = state.write().await;
-                        if let Some(ref mut game_state) = *state_guard {
-                            let old_won = game_state.won;
-                            let old_game_over = game_state.game_over;
-
-                            // Apply updates to local board
-                            for update in updates {
-                                game_state.set_cell(update.pos, update.value);
-                            }
-
-                            // Update game status
-                            game_state.won = won;
-                            game_state.game_over = won || lost;
-
-                            status_changed =
-                                game_state.won != old_won || game_state.game_over != old_game_over;
-                        } else {
-                            status_changed = false;
-                        }
-                    }
+            }
+        }
+    }
+}
 
-                    if let Some(ref sender) = *event_sender.read().await {
-                        if !changed_positions.is_empty() {
-                            let _ = sender.send(GameEvent::BoardUpdated { changed_positions });
-                        }
+/// Applies a server message to the local state and returns the events it produces, in
+/// the order they should be emitted. Does no I/O, so state transitions can be driven
+/// without a connection.
+pub fn apply_server_message(
+    state: &mut Option<GameState>,
+    message: ServerMessage,
+) -> Vec<GameEvent> {
+    match message {
+        ServerMessage::Init {
+            width,
+            height,
+            bombs,
+            ..
+        } => {
+            info!(
+                "Received game initialization: {}x{} with {} bombs",
+                width, height, bombs
+            );
 
-                        if status_changed {
-                            let _ = sender.send(GameEvent::GameStatusChanged { won, lost });
-                        }
-                    }
+            match state {
+                Some(game_state) => game_state.apply(&message),
+                None => *state = GameState::from_init(&message),
+            }
+            vec![GameEvent::GameInitialized {
+                width,
+                height,
+                bombs,
+            }]
+        }
+        ServerMessage::Update {
+            ref updates,
+            won,
+            lost,
+            ..
+        } => {
+            debug!(
+                "Received update: {} cells updated, won: {}, lost: {}",
+                updates.len(),
+                won,
+                lost
+            );
+
+            let path: Vec<(Pos, usize)> = updates
+                .iter()
+                .filter_map(|u| u.depth.map(|depth| (u.pos, depth)))
+                .collect();
+
+            let status_changed = match state {
+                Some(game_state) => {
+                    let old_status = (game_state.won, game_state.game_over);
+                    game_state.apply(&message);
+                    (game_state.won, game_state.game_over) != old_status
                 }
+                None => false,
+            };
+
+            let mut events = Vec::new();
+            if !updates.is_empty() {
+                events.push(GameEvent::BoardUpdated {
+                    changes: updates.clone(),
+                });
+            }
+            if !path.is_empty() {
+                events.push(GameEvent::CascadeRevealed { path });
+            }
+            if status_changed {
+                events.push(GameEvent::GameStatusChanged { won, lost });
+            }
+            events
+        }
+        ServerMessage::GameSummary {
+            won,
+            elapsed_ms,
+            moves,
+            reveals,
+            flags,
+            board_cleared_pct,
+            efficiency,
+        } => {
+            info!(
+                "Received game summary: won: {}, {}ms, {} moves, {:.1}% cleared",
+                won, elapsed_ms, moves, board_cleared_pct
+            );
+
+            vec![GameEvent::GameSummary {
+                won,
+                elapsed_ms,
+                moves,
+                reveals,
+                flags,
+                board_cleared_pct,
+                efficiency,
+            }]
+        }
+        ServerMessage::Restarted {
+            params,
+            params_changed,
+            new_layout,
+        } => {
+            info!(
+                "Game restarted: {}x{} with {} bombs, params changed: {}, new layout: {}",
+                params.width, params.height, params.bombs, params_changed, new_layout
+            );
+
+            vec![GameEvent::GameRestarted {
+                params,
+                params_changed,
+                new_layout,
+            }]
+        }
+        ServerMessage::ExpiryWarning { seconds_remaining } => {
+            warn!("Game expires in {}s due to inactivity", seconds_remaining);
+            vec![GameEvent::ExpiryWarning { seconds_remaining }]
+        }
+        ServerMessage::Presence { players } => {
+            debug!("{} connections in game", players);
+            if let Some(game_state) = state {
+                game_state.apply(&message);
+            }
+            vec![GameEvent::PresenceChanged { players }]
+        }
+        ServerMessage::Chat { from, text } => {
+            debug!("Received chat message from {}", from);
+            vec![GameEvent::ChatReceived { from, text }]
+        }
+        ServerMessage::Paused { paused } => {
+            info!("Game {}", if paused { "paused" } else { "resumed" });
+            if let Some(game_state) = state {
+                game_state.apply(&message);
             }
+            vec![GameEvent::PausedChanged { paused }]
+        }
+        ServerMessage::Error { code, message } => {
+            warn!("Server rejected message ({}): {}", code, message);
+            vec![GameEvent::Error { code, message }]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Game connected to an in-memory channel instead of a server, returning the
+    /// messages it sends
+    async fn connected_game(
+        state: GameState,
+    ) -> (MinesweeperGame, mpsc::UnboundedReceiver<ClientMessage>) {
+        let game = MinesweeperGame::new("http://localhost:8000").unwrap();
+        let (websocket_sender, sent) = mpsc::unbounded_channel();
+        let (shutdown, _) = oneshot::channel();
+        *game.connection_state.write().await = Some(ConnectionState {
+            websocket_sender,
+            game_id: "test".to_string(),
+            background_task: runtime::spawn(async {}),
+            shutdown,
+        });
+        *game.state.write().await = Some(state);
+        (game, sent)
+    }
+
+    fn hidden_state(width: usize, height: usize) -> GameState {
+        GameState::new(width, height, 1, vec![vec![Cell::Hidden; width]; height])
+    }
+
+    fn update(updates: Vec<CellUpdate>) -> ServerMessage {
+        ServerMessage::Update {
+            updates,
+            won: false,
+            lost: false,
+            elapsed_secs: 0,
+            flags_placed: 0,
+            moves: 0,
+            revision: 0,
+        }
+    }
+
+    fn cell_update(x: usize, y: usize, value: Cell) -> CellUpdate {
+        CellUpdate {
+            pos: Pos { x, y },
+            value,
+            depth: None,
         }
     }
+
+    /// Applies the server's answer to a local flag toggle
+    async fn confirm(game: &MinesweeperGame, x: usize, y: usize, value: Cell) {
+        let message = update(vec![cell_update(x, y, value)]);
+        apply_server_message(&mut *game.state.write().await, message);
+    }
+
+    #[tokio::test]
+    async fn flag_order_follows_local_flags() {
+        let (game, _sent) = connected_game(hidden_state(3, 3)).await;
+        for (x, y) in [(0, 0), (2, 1), (1, 2)] {
+            game.flag_xy(x, y).await.unwrap();
+            confirm(&game, x, y, Cell::Flagged).await;
+        }
+
+        let state = game.get_state().await.unwrap();
+        assert_eq!(
+            state.flag_order(),
+            [Pos { x: 0, y: 0 }, Pos { x: 2, y: 1 }, Pos { x: 1, y: 2 }]
+        );
+        assert_eq!(state.last_flagged(), Some(Pos { x: 1, y: 2 }));
+    }
+
+    #[tokio::test]
+    async fn cells_leave_the_flag_order_once_no_longer_flagged() {
+        let (game, _sent) = connected_game(hidden_state(3, 3)).await;
+        for (x, y) in [(0, 0), (1, 0)] {
+            game.flag_xy(x, y).await.unwrap();
+            confirm(&game, x, y, Cell::Flagged).await;
+        }
+
+        // Someone else cycles the newer flag on to a question mark
+        confirm(&game, 1, 0, Cell::Marked).await;
+        let state = game.get_state().await.unwrap();
+        assert_eq!(state.flag_order(), [Pos { x: 0, y: 0 }]);
+    }
+
+    #[tokio::test]
+    async fn unflag_last_cycles_the_newest_flag_back_to_hidden() {
+        let (game, mut sent) = connected_game(hidden_state(3, 3)).await;
+        for (x, y) in [(0, 0), (2, 2)] {
+            game.flag_xy(x, y).await.unwrap();
+            confirm(&game, x, y, Cell::Flagged).await;
+        }
+        while sent.try_recv().is_ok() {}
+
+        assert_eq!(game.unflag_last().await.unwrap(), Some(Pos { x: 2, y: 2 }));
+        let mut toggles = 0;
+        while let Ok(message) = sent.try_recv() {
+            assert!(matches!(
+                message,
+                ClientMessage::Flag {
+                    pos: Pos { x: 2, y: 2 }
+                }
+            ));
+            toggles += 1;
+        }
+        assert_eq!(toggles, 2);
+
+        let state = game.get_state().await.unwrap();
+        assert_eq!(state.flag_order(), [Pos { x: 0, y: 0 }]);
+        assert_eq!(state.last_flagged(), Some(Pos { x: 0, y: 0 }));
+    }
+
+    #[tokio::test]
+    async fn unflag_last_toggles_once_without_question_marks() {
+        let mut state = None;
+        apply_server_message(
+            &mut state,
+            ServerMessage::Init {
+                width: 3,
+                height: 3,
+                bombs: 1,
+                field: vec![vec![Cell::Hidden; 3]; 3],
+                elapsed_secs: 0,
+                flags_placed: 0,
+                moves: 0,
+                your_token: None,
+                revision: 0,
+                paused: false,
+                enable_question_marks: false,
+            },
+        );
+        let state = state.unwrap();
+        assert!(!state.enable_question_marks);
+
+        let (game, mut sent) = connected_game(state).await;
+        game.flag_xy(1, 1).await.unwrap();
+        confirm(&game, 1, 1, Cell::Flagged).await;
+        while sent.try_recv().is_ok() {}
+
+        assert_eq!(game.unflag_last().await.unwrap(), Some(Pos { x: 1, y: 1 }));
+        assert!(matches!(
+            sent.try_recv(),
+            Ok(ClientMessage::Flag {
+                pos: Pos { x: 1, y: 1 }
+            })
+        ));
+        assert!(sent.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn unflag_last_without_flags_sends_nothing() {
+        let (game, mut sent) = connected_game(hidden_state(3, 3)).await;
+        assert_eq!(game.unflag_last().await.unwrap(), None);
+        assert!(sent.try_recv().is_err());
+    }
+
+    #[test]
+    fn every_event_round_trips_through_json() {
+        let events = vec![
+            GameEvent::BoardUpdated {
+                changes: vec![cell_update(1, 2, Cell::Revealed { adjacent: 3 })],
+            },
+            GameEvent::CascadeRevealed {
+                path: vec![(Pos { x: 0, y: 0 }, 0), (Pos { x: 1, y: 0 }, 1)],
+            },
+            GameEvent::GameStatusChanged {
+                won: true,
+                lost: false,
+            },
+            GameEvent::GameInitialized {
+                width: 9,
+                height: 9,
+                bombs: 10,
+            },
+            GameEvent::GameRestarted {
+                params: GameParams {
+                    seed: Some(7),
+                    ..Default::default()
+                },
+                params_changed: true,
+                new_layout: false,
+            },
+            GameEvent::GameSummary {
+                won: false,
+                elapsed_ms: 1500,
+                moves: 4,
+                reveals: 3,
+                flags: 1,
+                board_cleared_pct: 42.5,
+                efficiency: Some(EfficiencyStats {
+                    three_bv: 12,
+                    useful_clicks: 3,
+                    wasted_clicks: 1,
+                    three_bv_per_second: 8.0,
+                }),
+            },
+            GameEvent::ExpiryWarning {
+                seconds_remaining: 60,
+            },
+            GameEvent::PresenceChanged { players: 2 },
+            GameEvent::PausedChanged { paused: true },
+            GameEvent::ChatReceived {
+                from: Uuid::new_v4(),
+                text: "hi".to_string(),
+            },
+            GameEvent::Error {
+                code: "cell_revealed".to_string(),
+                message: "Cell is already revealed".to_string(),
+            },
+            GameEvent::Reconnecting { attempt: 2 },
+            GameEvent::Reconnected,
+            GameEvent::ConnectionLost,
+        ];
+
+        for event in events {
+            let json = serde_json::to_value(&event).unwrap();
+            assert!(json["event"].is_string(), "{}", json);
+            let decoded: GameEvent = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", event));
+            assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn init_then_updates_build_the_state_and_events() {
+        let mut state = None;
+        let events = apply_server_message(
+            &mut state,
+            ServerMessage::Init {
+                width: 3,
+                height: 3,
+                bombs: 1,
+                field: vec![vec![Cell::Hidden; 3]; 3],
+                elapsed_secs: 0,
+                flags_placed: 0,
+                moves: 0,
+                your_token: None,
+                revision: 0,
+                paused: false,
+                enable_question_marks: true,
+            },
+        );
+        assert!(matches!(
+            events[..],
+            [GameEvent::GameInitialized {
+                width: 3,
+                height: 3,
+                bombs: 1
+            }]
+        ));
+
+        let cascade = vec![
+            CellUpdate {
+                depth: Some(0),
+                ..cell_update(0, 0, Cell::Revealed { adjacent: 0 })
+            },
+            CellUpdate {
+                depth: Some(1),
+                ..cell_update(1, 0, Cell::Revealed { adjacent: 1 })
+            },
+        ];
+        let events = apply_server_message(
+            &mut state,
+            ServerMessage::Update {
+                updates: cascade,
+                won: false,
+                lost: false,
+                elapsed_secs: 2,
+                flags_placed: 0,
+                moves: 1,
+                revision: 1,
+            },
+        );
+        assert!(matches!(
+            &events[..],
+            [
+                GameEvent::BoardUpdated { changes },
+                GameEvent::CascadeRevealed { path },
+            ] if changes.len() == 2
+                && path == &[(Pos { x: 0, y: 0 }, 0), (Pos { x: 1, y: 0 }, 1)]
+        ));
+
+        let events = apply_server_message(
+            &mut state,
+            ServerMessage::Update {
+                updates: vec![cell_update(2, 2, Cell::Flagged)],
+                won: false,
+                lost: false,
+                elapsed_secs: 3,
+                flags_placed: 1,
+                moves: 2,
+                revision: 2,
+            },
+        );
+        assert!(matches!(&events[..], [GameEvent::BoardUpdated { .. }]));
+
+        let events = apply_server_message(
+            &mut state,
+            ServerMessage::Update {
+                updates: vec![cell_update(2, 1, Cell::Bomb)],
+                won: false,
+                lost: true,
+                elapsed_secs: 4,
+                flags_placed: 1,
+                moves: 3,
+                revision: 3,
+            },
+        );
+        assert!(matches!(
+            &events[..],
+            [
+                GameEvent::BoardUpdated { .. },
+                GameEvent::GameStatusChanged {
+                    won: false,
+                    lost: true
+                },
+            ]
+        ));
+
+        let state = state.unwrap();
+        assert_eq!(state.board[0][0], Cell::Revealed { adjacent: 0 });
+        assert_eq!(state.board[0][1], Cell::Revealed { adjacent: 1 });
+        assert_eq!(state.board[2][2], Cell::Flagged);
+        assert_eq!(state.board[1][2], Cell::Bomb);
+        assert!(state.game_over && !state.won);
+        assert_eq!(state.flags_placed, 1);
+        assert_eq!(state.elapsed_secs, 4);
+        assert_eq!(state.moves(), 3);
+        assert_eq!(state.revision(), 3);
+    }
+
+    #[test]
+    fn updates_before_init_leave_no_state() {
+        let mut state = None;
+        let events = apply_server_message(
+            &mut state,
+            update(vec![cell_update(0, 0, Cell::Revealed { adjacent: 0 })]),
+        );
+        assert!(state.is_none());
+        assert!(matches!(&events[..], [GameEvent::BoardUpdated { .. }]));
+    }
+
+    #[test]
+    fn remaining_safe_counts_down_as_cells_are_revealed() {
+        let mut state = hidden_state(3, 3);
+        state.board[1][1] = Cell::Flagged;
+        assert_eq!(state.remaining_safe(), 8);
+        assert_eq!(state.completion(), 0.0);
+
+        state.board[0][0] = Cell::Revealed { adjacent: 1 };
+        state.board[0][1] = Cell::Revealed { adjacent: 1 };
+        assert_eq!(state.remaining_safe(), 6);
+        assert_eq!(state.completion(), 0.25);
+
+        for (x, y) in [(2, 0), (0, 1), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            state.board[y][x] = Cell::Revealed { adjacent: 1 };
+        }
+        assert_eq!(state.remaining_safe(), 0);
+        assert_eq!(state.completion(), 1.0);
+    }
+
+    #[test]
+    fn compute_3bv_counts_openings_and_isolated_numbers() {
+        // 0 1 1 1 0
+        // 0 1 * 1 0
+        // 0 1 1 1 0
+        let zero = Cell::Revealed { adjacent: 0 };
+        let one = Cell::Revealed { adjacent: 1 };
+        let mut board = vec![vec![zero, one, one, one, zero]; 3];
+        board[1][2] = Cell::Bomb;
+        let mut state = GameState::new(5, 3, 1, board);
+
+        // Both zero columns are openings taking their numbered borders with them, which
+        // leaves the numbers above and below the bomb as one click each
+        assert_eq!(state.compute_3bv(), Some(4));
+
+        state.board[1][2] = Cell::Hidden;
+        assert_eq!(state.compute_3bv(), None);
+        state.won = true;
+        assert_eq!(state.compute_3bv(), Some(4));
+    }
+
+    #[test]
+    fn counters_missing_from_older_servers_default_to_zero() {
+        let json = r#"{"type": "update", "updates": [], "won": false, "lost": false}"#;
+        let message: ServerMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            message,
+            ServerMessage::Update {
+                elapsed_secs: 0,
+                flags_placed: 0,
+                moves: 0,
+                revision: 0,
+                ..
+            }
+        ));
+    }
 }
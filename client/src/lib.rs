@@ -18,7 +18,7 @@
 //!     let game = MinesweeperGame::new("http://localhost:8000")?;
 //!
 //!     // Start a new game
-//!     let params = GameParams { width: 8, height: 8, bombs: 10 };
+//!     let params = GameParams { width: 8, height: 8, bombs: 10, ..Default::default() };
 //!     game.start_game(params).await?;
 //!
 //!     // Make moves
@@ -46,7 +46,7 @@
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 //!     let client = MinesweeperClient::new("http://localhost:8000")?;
-//!     let game_id = client.create_game(GameParams { width: 8, height: 8, bombs: 10 }).await?;
+//!     let game_id = client.create_game(GameParams::default()).await?;
 //!     
 //!     let ws_url = client.websocket_url(&game_id)?;
 //!     let mut ws = MinesweeperWebSocket::connect(&ws_url).await?;
@@ -63,16 +63,57 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ### TLS Backend
+//!
+//! `https://` and `wss://` connections use `rustls` by default, which needs no system
+//! libraries. To use the platform's TLS stack (OpenSSL on Linux) instead:
+//!
+//! ```toml
+//! minesweeper-client = { version = "2", default-features = false, features = ["native-tls"] }
+//! ```
+//!
+//! If both features end up enabled, `native-tls` is used for HTTP and WebSockets alike.
+//!
+//! ### Browser (WASM)
+//!
+//! With the `wasm` feature the crate builds for `wasm32-unknown-unknown`:
+//! `MinesweeperWebSocket` is backed by `gloo-net`, background tasks run through
+//! `wasm-bindgen-futures` and HTTP requests use `reqwest`'s fetch backend. The public
+//! API is the same, except that `Recorder::save` and `Replay::load` are unavailable
+//! since there is no file system; use `Recorder::to_ndjson` and `Replay::parse` instead.
+//!
+//! ```toml
+//! minesweeper-client = { version = "2", features = ["wasm"] }
+//! ```
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls", feature = "wasm")))]
+compile_error!("enable the `rustls` or `native-tls` feature to select a TLS backend");
 
 mod client;
+mod error;
 mod game;
+mod replay;
+mod runtime;
+mod session;
+pub mod solver;
+#[cfg(not(feature = "wasm"))]
+mod websocket;
+#[cfg(feature = "wasm")]
+#[path = "websocket_wasm.rs"]
 mod websocket;
 
-pub use client::MinesweeperClient;
-pub use game::{GameEvent, GameState, MinesweeperGame};
+pub use client::{ConnectOptions, MinesweeperClient};
+pub use error::ClientError;
+pub use game::{
+    AutoplayResult, BoardSnapshot, GameEvent, GameState, MinesweeperGame, RevealManyResult,
+    apply_server_message,
+};
+pub use replay::{Recorder, Replay};
+pub use session::{MinesweeperSession, SessionEvent};
 pub use websocket::MinesweeperWebSocket;
 
 // Re-export common types for convenience
-pub use minesweeper_common::{models::*, protocol::*};
+pub use minesweeper_common::{mask::*, models::*, protocol::*};
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -0,0 +1,132 @@
+//! Recording and offline playback of game sessions.
+//!
+//! A `Recorder` captures every `ServerMessage` a `MinesweeperGame` receives and saves
+//! them as newline-delimited JSON, one `{"at_ms": ..., "message": {...}}` object per
+//! line. `Replay` loads such a file and feeds the messages through
+//! `apply_server_message`, the same logic the live client uses, so desyncs can be
+//! reproduced without a server.
+
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
+use std::{sync::Arc, time::Duration};
+
+use minesweeper_common::protocol::ServerMessage;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    GameState, MinesweeperGame, Result, apply_server_message,
+    runtime::{self, Instant, TaskHandle},
+};
+
+/// One line of a recording
+#[derive(Serialize, Deserialize)]
+struct RecordedMessage {
+    at_ms: u64,
+    message: ServerMessage,
+}
+
+/// Captures server messages with the time they arrived, relative to the start of the
+/// recording. Stops recording when dropped.
+pub struct Recorder {
+    entries: Arc<RwLock<Vec<(Duration, ServerMessage)>>>,
+    task: TaskHandle,
+}
+
+impl Recorder {
+    /// Records every message `game` receives from now on. Replaces any previous
+    /// `subscribe_to_messages` subscriber of the game.
+    pub async fn start(game: &MinesweeperGame) -> Self {
+        let mut receiver = game.subscribe_to_messages().await;
+        let entries = Arc::new(RwLock::new(Vec::new()));
+        let started = Instant::now();
+
+        let task = runtime::spawn({
+            let entries = entries.clone();
+            async move {
+                while let Some(message) = receiver.recv().await {
+                    entries.write().await.push((started.elapsed(), message));
+                }
+            }
+        });
+
+        Self { entries, task }
+    }
+
+    /// Messages recorded so far
+    pub async fn entries(&self) -> Vec<(Duration, ServerMessage)> {
+        self.entries.read().await.clone()
+    }
+
+    /// The recording as newline-delimited JSON, the format read by `Replay::parse`
+    pub async fn to_ndjson(&self) -> Result<String> {
+        let mut output = String::new();
+        for (at, message) in self.entries.read().await.iter() {
+            let line = RecordedMessage {
+                at_ms: at.as_millis() as u64,
+                message: message.clone(),
+            };
+            output.push_str(&serde_json::to_string(&line)?);
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+
+    /// Writes the recording to `path` as newline-delimited JSON
+    #[cfg(not(feature = "wasm"))]
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        tokio::fs::write(path, self.to_ndjson().await?).await?;
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A recording loaded from disk
+pub struct Replay {
+    entries: Vec<(Duration, ServerMessage)>,
+}
+
+impl Replay {
+    /// Loads a recording written by `Recorder::save`
+    #[cfg(not(feature = "wasm"))]
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let contents = tokio::fs::read_to_string(path).await?;
+        Self::parse(&contents)
+    }
+
+    /// Parses a recording in the format of `Recorder::to_ndjson`. Blank lines are skipped.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let recorded: RecordedMessage = serde_json::from_str(line)?;
+            entries.push((Duration::from_millis(recorded.at_ms), recorded.message));
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[(Duration, ServerMessage)] {
+        &self.entries
+    }
+
+    /// The client state after applying every message recorded up to and including `at`.
+    /// `None` if no `Init` was received by then.
+    pub fn state_at(&self, at: Duration) -> Option<GameState> {
+        let mut state: Option<GameState> = None;
+        for (_, message) in self.entries.iter().take_while(|(time, _)| *time <= at) {
+            apply_server_message(&mut state, message.clone());
+        }
+        state
+    }
+
+    /// The client state at the end of the recording
+    pub fn final_state(&self) -> Option<GameState> {
+        self.state_at(Duration::MAX)
+    }
+}
@@ -0,0 +1,91 @@
+//! Task spawning and timers for the async runtime in use: tokio natively, the browser's
+//! event loop with the `wasm` feature.
+
+use std::{future::Future, time::Duration};
+
+#[cfg(not(feature = "wasm"))]
+pub(crate) use std::time::Instant;
+#[cfg(feature = "wasm")]
+pub(crate) use web_time::Instant;
+
+/// Handle to a spawned background task
+#[cfg(not(feature = "wasm"))]
+pub(crate) struct TaskHandle(tokio::task::JoinHandle<()>);
+
+/// Handle to a spawned background task
+#[cfg(feature = "wasm")]
+pub(crate) struct TaskHandle {
+    abort: futures_util::future::AbortHandle,
+    done: tokio::sync::oneshot::Receiver<()>,
+}
+
+#[cfg(not(feature = "wasm"))]
+pub(crate) fn spawn<F>(future: F) -> TaskHandle
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    TaskHandle(tokio::spawn(future))
+}
+
+/// Runs `future` on the browser's event loop. Futures don't need to be `Send` here since
+/// everything runs on one thread.
+#[cfg(feature = "wasm")]
+pub(crate) fn spawn<F>(future: F) -> TaskHandle
+where
+    F: Future<Output = ()> + 'static,
+{
+    use futures_util::future::{AbortHandle, Abortable};
+
+    let (abort, registration) = AbortHandle::new_pair();
+    let (done_sender, done) = tokio::sync::oneshot::channel();
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = Abortable::new(future, registration).await;
+        let _ = done_sender.send(());
+    });
+
+    TaskHandle { abort, done }
+}
+
+impl TaskHandle {
+    /// Stops the task at its next await point
+    pub(crate) fn abort(&self) {
+        #[cfg(not(feature = "wasm"))]
+        self.0.abort();
+        #[cfg(feature = "wasm")]
+        self.abort.abort();
+    }
+
+    /// Waits until the task has finished or was aborted. Borrowing rather than consuming
+    /// the handle lets a caller that gave up waiting still abort the task.
+    pub(crate) async fn join(&mut self) {
+        #[cfg(not(feature = "wasm"))]
+        let _ = (&mut self.0).await;
+        #[cfg(feature = "wasm")]
+        let _ = (&mut self.done).await;
+    }
+}
+
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(not(feature = "wasm"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(feature = "wasm")]
+    gloo_timers::future::sleep(duration).await;
+}
+
+/// Runs `future` to completion unless `duration` elapses first
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    #[cfg(not(feature = "wasm"))]
+    return tokio::time::timeout(duration, future).await.ok();
+
+    #[cfg(feature = "wasm")]
+    {
+        use futures_util::future::{Either, select};
+
+        let future = std::pin::pin!(future);
+        let timer = std::pin::pin!(sleep(duration));
+        match select(future, timer).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! Several game connections sharing one HTTP client and one event stream.
+
+use std::{collections::HashMap, sync::Arc};
+
+use minesweeper_common::{
+    models::{GameParams, Pos},
+    protocol::ClientMessage,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{RwLock, mpsc, oneshot};
+use tracing::{debug, info, warn};
+
+use crate::{
+    ConnectOptions, GameEvent, GameState, MinesweeperClient, MinesweeperWebSocket, Result,
+    apply_server_message,
+    game::{ConnectionState, until_shutdown},
+    runtime::{self, TaskHandle},
+};
+
+type EventSender = Arc<RwLock<Option<mpsc::UnboundedSender<SessionEvent>>>>;
+
+/// A `GameEvent` tagged with the game it happened in.
+/// Serializes as the event with an extra `game_id` field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub game_id: String,
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
+
+struct SessionGame {
+    connection: ConnectionState,
+    state: Arc<RwLock<Option<GameState>>>,
+}
+
+/// Watches and plays any number of games at once, e.g. for a lobby view. Each game has
+/// its own WebSocket and local `GameState`; events from all of them arrive on a single
+/// stream. Dropped connections are not redialed.
+pub struct MinesweeperSession {
+    client: MinesweeperClient,
+    connect_options: ConnectOptions,
+    games: RwLock<HashMap<String, SessionGame>>,
+    event_sender: EventSender,
+}
+
+impl MinesweeperSession {
+    /// Create a new session for the server at `server_url`
+    pub fn new(server_url: &str) -> Result<Self> {
+        Ok(Self {
+            client: MinesweeperClient::new(server_url)?,
+            connect_options: ConnectOptions::default(),
+            games: RwLock::new(HashMap::new()),
+            event_sender: Arc::new(RwLock::new(None)),
+        })
+    }
+
+    /// Set the options used when joining games
+    pub fn with_connect_options(mut self, options: ConnectOptions) -> Self {
+        self.connect_options = options;
+        self
+    }
+
+    /// Subscribe to the events of every joined game
+    pub async fn subscribe_to_events(&self) -> mpsc::UnboundedReceiver<SessionEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.event_sender.write().await = Some(sender);
+        receiver
+    }
+
+    /// Create a game and join it, returning its ID
+    pub async fn start_game(&self, params: GameParams) -> Result<String> {
+        let game_id = self.client.create_game(params).await?;
+        self.join(game_id.clone()).await?;
+        Ok(game_id)
+    }
+
+    /// Connect to a game. Joining a game that is already joined reconnects it.
+    pub async fn join(&self, game_id: String) -> Result<()> {
+        info!("Session joining game {}", game_id);
+
+        let ws_url = self
+            .client
+            .websocket_url_with_options(&game_id, &self.connect_options)?;
+        let websocket = MinesweeperWebSocket::connect(&ws_url).await?;
+        let websocket_sender = websocket.get_sender();
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+        let state = Arc::new(RwLock::new(None));
+        let background_task = Self::start_background_listener(
+            game_id.clone(),
+            websocket,
+            state.clone(),
+            self.event_sender.clone(),
+            shutdown_receiver,
+        );
+
+        let game = SessionGame {
+            connection: ConnectionState {
+                websocket_sender,
+                game_id: game_id.clone(),
+                background_task,
+                shutdown,
+            },
+            state,
+        };
+        if let Some(previous) = self.games.write().await.insert(game_id, game) {
+            previous.connection.abort_and_wait_background_task().await;
+        }
+
+        Ok(())
+    }
+
+    /// Disconnect from a game, after sending any moves still queued for it. Returns
+    /// `false` if it was not joined.
+    pub async fn leave(&self, game_id: &str) -> bool {
+        let Some(game) = self.games.write().await.remove(game_id) else {
+            return false;
+        };
+
+        game.connection.close(None).await;
+        info!("Session left game {}", game_id);
+        true
+    }
+
+    /// Disconnect from every game
+    pub async fn leave_all(&self) {
+        let games: Vec<_> = self.games.write().await.drain().collect();
+        for (_, game) in games {
+            game.connection.close(None).await;
+        }
+    }
+
+    /// IDs of all joined games
+    pub async fn game_ids(&self) -> Vec<String> {
+        self.games.read().await.keys().cloned().collect()
+    }
+
+    /// Get a copy of a game's current state
+    pub async fn get_state(&self, game_id: &str) -> Option<GameState> {
+        let state = self.games.read().await.get(game_id)?.state.clone();
+        state.read().await.clone()
+    }
+
+    /// Send any client message to a joined game
+    pub async fn send(&self, game_id: &str, message: ClientMessage) -> Result<()> {
+        match self.games.read().await.get(game_id) {
+            Some(game) => game.connection.send_message(message),
+            None => Err(format!("Not joined to game {}", game_id).into()),
+        }
+    }
+
+    /// Reveal a cell in a joined game
+    pub async fn reveal(&self, game_id: &str, pos: Pos) -> Result<()> {
+        debug!("Revealing cell at ({}, {}) in {}", pos.x, pos.y, game_id);
+        self.send(game_id, ClientMessage::Reveal { pos }).await
+    }
+
+    /// Toggle the flag on a cell in a joined game
+    pub async fn flag(&self, game_id: &str, pos: Pos) -> Result<()> {
+        debug!("Flagging cell at ({}, {}) in {}", pos.x, pos.y, game_id);
+        self.send(game_id, ClientMessage::Flag { pos }).await
+    }
+
+    /// Applies messages to the game's state and forwards its events, tagged with
+    /// `game_id`, until the connection drops
+    fn start_background_listener(
+        game_id: String,
+        mut websocket: MinesweeperWebSocket,
+        state: Arc<RwLock<Option<GameState>>>,
+        event_sender: EventSender,
+        mut shutdown: oneshot::Receiver<()>,
+    ) -> TaskHandle {
+        runtime::spawn(async move {
+            let emit = |event: GameEvent| {
+                let event_sender = event_sender.clone();
+                let game_id = game_id.clone();
+                async move {
+                    if let Some(ref sender) = *event_sender.read().await {
+                        let _ = sender.send(SessionEvent { game_id, event });
+                    }
+                }
+            };
+
+            loop {
+                let Some(message) =
+                    until_shutdown(websocket.receive_message(), &mut shutdown).await
+                else {
+                    let _ = websocket.close().await;
+                    return;
+                };
+                let message = match message {
+                    Ok(Some(message)) => message,
+                    Ok(None) => {
+                        info!("WebSocket connection for game {} closed", game_id);
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Error receiving message for game {}: {}", game_id, e);
+                        break;
+                    }
+                };
+
+                let events = apply_server_message(&mut *state.write().await, message);
+                for event in events {
+                    emit(event).await;
+                }
+            }
+
+            emit(GameEvent::ConnectionLost).await;
+        })
+    }
+}
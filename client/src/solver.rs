@@ -0,0 +1,130 @@
+//! Deterministic single-cell solver for bots.
+//!
+//! Only the two classic rules are applied to each revealed number:
+//! - if its hidden and flagged neighbours together equal the number, every hidden
+//!   neighbour is a mine;
+//! - if its flagged neighbours already equal the number, every hidden neighbour is safe.
+//!
+//! Marked (`?`) cells are treated as hidden. Situations that need reasoning across
+//! several numbers yield no moves.
+
+use std::collections::HashSet;
+
+use minesweeper_common::models::{Cell, Pos};
+use serde::{Deserialize, Serialize};
+
+use crate::GameState;
+
+/// A move the solver has proven to be correct
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SolverMove {
+    Reveal(Pos),
+    Flag(Pos),
+}
+
+/// Returns every move that follows from the single-cell rules, without duplicates, in
+/// board order of the number that implied it. Empty when no deterministic move exists.
+pub fn next_moves(state: &GameState) -> Vec<SolverMove> {
+    let mut moves = Vec::new();
+    let mut seen = HashSet::new();
+
+    for y in 0..state.height {
+        for x in 0..state.width {
+            let pos = Pos { x, y };
+            let Some(adjacent) = state.get_cell(pos).and_then(Cell::adjacent_count) else {
+                continue;
+            };
+            let adjacent = adjacent as usize;
+            if adjacent == 0 {
+                continue;
+            }
+
+            let mut hidden = Vec::new();
+            let mut flagged = 0;
+            for neighbor in pos.neighbors(state.width, state.height) {
+                match state.get_cell(neighbor) {
+                    Some(cell) if cell.is_hidden() => hidden.push(neighbor),
+                    Some(cell) if cell.is_flagged() => flagged += 1,
+                    _ => {}
+                }
+            }
+            if hidden.is_empty() {
+                continue;
+            }
+
+            let make_move: fn(Pos) -> SolverMove = if flagged == adjacent {
+                SolverMove::Reveal
+            } else if flagged + hidden.len() == adjacent {
+                SolverMove::Flag
+            } else {
+                continue;
+            };
+
+            for pos in hidden {
+                let solver_move = make_move(pos);
+                if seen.insert(solver_move) {
+                    moves.push(solver_move);
+                }
+            }
+        }
+    }
+
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// State drawn row by row: `#` hidden, `?` marked, `F` flagged, digits revealed
+    fn state(rows: &[&str]) -> GameState {
+        let board: Vec<Vec<Cell>> = rows
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|c| match c {
+                        '#' => Cell::Hidden,
+                        '?' => Cell::Marked,
+                        'F' => Cell::Flagged,
+                        digit => Cell::Revealed {
+                            adjacent: digit.to_digit(10).unwrap() as u8,
+                        },
+                    })
+                    .collect()
+            })
+            .collect();
+        GameState::new(board[0].len(), board.len(), 1, board)
+    }
+
+    #[test]
+    fn number_matching_its_hidden_neighbours_flags_them_once() {
+        // Every number implies the same mine
+        assert_eq!(
+            next_moves(&state(&["#1", "11"])),
+            [SolverMove::Flag(Pos { x: 0, y: 0 })]
+        );
+    }
+
+    #[test]
+    fn satisfied_number_reveals_its_other_neighbours() {
+        assert_eq!(
+            next_moves(&state(&["F1#", "110"])),
+            [SolverMove::Reveal(Pos { x: 2, y: 0 })]
+        );
+    }
+
+    #[test]
+    fn marked_cells_count_as_hidden() {
+        assert_eq!(
+            next_moves(&state(&["?1", "11"])),
+            [SolverMove::Flag(Pos { x: 0, y: 0 })]
+        );
+    }
+
+    #[test]
+    fn forced_guess_yields_no_moves() {
+        // One mine behind two hidden cells that both numbers see
+        assert!(next_moves(&state(&["##", "11"])).is_empty());
+        assert!(next_moves(&state(&["###", "###"])).is_empty());
+    }
+}
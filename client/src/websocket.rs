@@ -1,29 +1,60 @@
+use std::time::Duration;
+
 use futures_util::{SinkExt, StreamExt, stream::SplitStream};
 use minesweeper_common::protocol::{ClientMessage, ServerMessage};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use tokio::task::JoinHandle;
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
 use tracing::{debug, info, warn};
 
-use crate::Result;
+use crate::{
+    Result,
+    runtime::{self, TaskHandle},
+};
 
 type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 type WsReader = SplitStream<WsStream>;
 
+/// Timeout used by [`MinesweeperWebSocket::connect`]
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// WebSocket client for real-time minesweeper gameplay
 pub struct MinesweeperWebSocket {
     sender: mpsc::UnboundedSender<ClientMessage>,
     reader: WsReader,
-    writer_task: JoinHandle<()>,
+    writer_task: TaskHandle,
 }
 
 impl MinesweeperWebSocket {
-    /// Connect to a minesweeper game via WebSocket
+    /// Connect to a minesweeper game via WebSocket, giving up after 10 seconds
     pub async fn connect(url: &str) -> Result<Self> {
+        Self::connect_with_timeout(url, DEFAULT_CONNECT_TIMEOUT).await
+    }
+
+    /// Connect to a minesweeper game via WebSocket, failing if the handshake does not
+    /// complete within `timeout`
+    pub async fn connect_with_timeout(url: &str, timeout: Duration) -> Result<Self> {
+        Self::open(url, timeout, false).await
+    }
+
+    /// Connect with MessagePack binary frames instead of JSON text frames in both
+    /// directions. Adds `binary=true` to `url` so the server encodes its messages the
+    /// same way; servers built without their `binary` feature keep sending text frames,
+    /// which are still understood.
+    #[cfg(feature = "binary")]
+    pub async fn connect_binary(url: &str) -> Result<Self> {
+        let mut url = url::Url::parse(url)?;
+        url.query_pairs_mut().append_pair("binary", "true");
+        Self::open(url.as_str(), DEFAULT_CONNECT_TIMEOUT, true).await
+    }
+
+    #[cfg_attr(not(feature = "binary"), allow(unused_variables))]
+    async fn open(url: &str, timeout: Duration, binary: bool) -> Result<Self> {
         info!("Connecting to WebSocket: {}", url);
 
-        let (ws_stream, _) = connect_async(url).await?;
+        let (ws_stream, _) = runtime::timeout(timeout, connect_async(url))
+            .await
+            .ok_or_else(|| format!("connection timed out after {:?}", timeout))??;
         info!("WebSocket connected successfully");
 
         let (writer, reader) = ws_stream.split();
@@ -32,19 +63,19 @@ impl MinesweeperWebSocket {
         let (sender, mut receiver) = mpsc::unbounded_channel::<ClientMessage>();
 
         // Spawn writer task that handles all outgoing messages
-        let writer_task = tokio::spawn(async move {
+        let writer_task = runtime::spawn(async move {
             let mut writer = writer;
             while let Some(message) = receiver.recv().await {
-                let json = match serde_json::to_string(&message) {
-                    Ok(json) => json,
+                let frame = match encode(&message, binary) {
+                    Ok(frame) => frame,
                     Err(e) => {
                         warn!("Failed to serialize message: {}", e);
                         continue;
                     }
                 };
 
-                debug!("Sending message: {}", json);
-                if let Err(e) = writer.send(Message::Text(json.into())).await {
+                debug!("Sending message: {:?}", message);
+                if let Err(e) = writer.send(frame).await {
                     warn!("Failed to send WebSocket message: {}", e);
                     break;
                 }
@@ -77,35 +108,98 @@ impl MinesweeperWebSocket {
     /// Receive the next server message
     /// Returns None if the connection is closed
     pub async fn receive_message(&mut self) -> Result<Option<ServerMessage>> {
-        if let Some(msg) = self.reader.next().await {
+        while let Some(msg) = self.reader.next().await {
             match msg? {
                 Message::Text(text) => {
                     debug!("Received message: {}", text);
                     let server_message: ServerMessage = serde_json::from_str(&text)?;
-                    Ok(Some(server_message))
+                    return Ok(Some(server_message));
+                }
+                #[cfg(feature = "binary")]
+                Message::Binary(bytes) => {
+                    let server_message: ServerMessage =
+                        minesweeper_common::protocol::from_bytes(&bytes)?;
+                    debug!("Received binary message: {:?}", server_message);
+                    return Ok(Some(server_message));
                 }
                 Message::Close(_) => {
                     info!("WebSocket connection closed");
-                    Ok(None)
-                }
-                _ => {
-                    // Ignore ping/pong and binary messages, try again
-                    Box::pin(self.receive_message()).await
+                    return Ok(None);
                 }
+                // Ignore ping/pong (and binary messages without the `binary` feature) and
+                // wait for the next frame
+                _ => continue,
             }
-        } else {
-            Ok(None)
         }
+
+        Ok(None)
     }
 
-    /// Close the WebSocket connection
-    pub async fn close(self) -> Result<()> {
+    /// Close the WebSocket connection once every queued message has been sent
+    pub async fn close(mut self) -> Result<()> {
         // Drop the sender to signal the writer task to close
         drop(self.sender);
 
         // Wait for the writer task to complete
-        let _ = self.writer_task.await;
+        self.writer_task.join().await;
+
+        // Wait for the server to answer the close frame or hang up. Dropping the socket
+        // while the server still has unread frames would reset the connection and
+        // discard them.
+        while let Some(Ok(_)) = self.reader.next().await {}
 
         Ok(())
     }
 }
+
+/// Encodes a message as a JSON text frame, or as a MessagePack binary frame for
+/// connections opened with `connect_binary`
+#[cfg_attr(not(feature = "binary"), allow(unused_variables))]
+fn encode(message: &ClientMessage, binary: bool) -> Result<Message> {
+    #[cfg(feature = "binary")]
+    if binary {
+        let bytes = minesweeper_common::protocol::to_bytes(message)?;
+        return Ok(Message::Binary(bytes.into()));
+    }
+    Ok(Message::Text(serde_json::to_string(message)?.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn control_frames_before_a_text_frame_are_skipped() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            for i in 0..1000u32 {
+                ws.send(Message::Ping(i.to_be_bytes().to_vec().into()))
+                    .await
+                    .unwrap();
+            }
+            ws.send(Message::Pong(Vec::new().into())).await.unwrap();
+            let presence = serde_json::to_string(&ServerMessage::Presence { players: 2 }).unwrap();
+            ws.send(Message::Text(presence.into())).await.unwrap();
+            ws.close(None).await.unwrap();
+            // Answer the client's close frame
+            while ws.next().await.is_some() {}
+        });
+
+        let mut ws = MinesweeperWebSocket::connect(&url).await.unwrap();
+        let message = ws.receive_message().await.unwrap();
+        assert!(matches!(
+            message,
+            Some(ServerMessage::Presence { players: 2 })
+        ));
+        assert!(ws.receive_message().await.unwrap().is_none());
+
+        ws.close().await.unwrap();
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+//! Browser implementation of `MinesweeperWebSocket`, used with the `wasm` feature. It
+//! mirrors the native API on top of `gloo-net`.
+
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt, stream::SplitStream};
+use gloo_net::websocket::{Message, State, futures::WebSocket};
+use minesweeper_common::protocol::{ClientMessage, ServerMessage};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::{
+    Result,
+    runtime::{self, TaskHandle},
+};
+
+type WsReader = SplitStream<WebSocket>;
+
+/// Timeout used by [`MinesweeperWebSocket::connect`]
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the connection state is checked while the handshake is in progress
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// WebSocket client for real-time minesweeper gameplay
+pub struct MinesweeperWebSocket {
+    sender: mpsc::UnboundedSender<ClientMessage>,
+    reader: WsReader,
+    writer_task: TaskHandle,
+}
+
+impl MinesweeperWebSocket {
+    /// Connect to a minesweeper game via WebSocket, giving up after 10 seconds
+    pub async fn connect(url: &str) -> Result<Self> {
+        Self::connect_with_timeout(url, DEFAULT_CONNECT_TIMEOUT).await
+    }
+
+    /// Connect to a minesweeper game via WebSocket, failing if the handshake does not
+    /// complete within `timeout`
+    pub async fn connect_with_timeout(url: &str, timeout: Duration) -> Result<Self> {
+        info!("Connecting to WebSocket: {}", url);
+
+        // Browser errors hold a `JsValue`, which is not `Send`, so only their text is kept
+        let ws_stream = WebSocket::open(url).map_err(|e| e.to_string())?;
+        let opened = runtime::timeout(timeout, async {
+            while matches!(ws_stream.state(), State::Connecting) {
+                runtime::sleep(CONNECT_POLL_INTERVAL).await;
+            }
+            matches!(ws_stream.state(), State::Open)
+        })
+        .await
+        .ok_or_else(|| format!("connection timed out after {:?}", timeout))?;
+        if !opened {
+            return Err("WebSocket closed during the handshake".into());
+        }
+        info!("WebSocket connected successfully");
+
+        let (writer, reader) = ws_stream.split();
+
+        // Create MPSC channel for sending messages
+        let (sender, mut receiver) = mpsc::unbounded_channel::<ClientMessage>();
+
+        // Spawn writer task that handles all outgoing messages
+        let writer_task = runtime::spawn(async move {
+            let mut writer = writer;
+            while let Some(message) = receiver.recv().await {
+                let json = match serde_json::to_string(&message) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        warn!("Failed to serialize message: {}", e);
+                        continue;
+                    }
+                };
+
+                debug!("Sending message: {}", json);
+                if let Err(e) = writer.send(Message::Text(json)).await {
+                    warn!("Failed to send WebSocket message: {}", e);
+                    break;
+                }
+            }
+
+            // Close the writer when done
+            let _ = writer.close().await;
+        });
+
+        Ok(Self {
+            sender,
+            reader,
+            writer_task,
+        })
+    }
+
+    /// Get a cloneable sender for sending messages
+    pub fn get_sender(&self) -> mpsc::UnboundedSender<ClientMessage> {
+        self.sender.clone()
+    }
+
+    /// Send a client message to the server
+    pub async fn send_message(&self, message: ClientMessage) -> Result<()> {
+        self.sender
+            .send(message)
+            .map_err(|_| "WebSocket sender channel closed")?;
+        Ok(())
+    }
+
+    /// Receive the next server message
+    /// Returns None if the connection is closed
+    pub async fn receive_message(&mut self) -> Result<Option<ServerMessage>> {
+        while let Some(msg) = self.reader.next().await {
+            match msg.map_err(|e| e.to_string())? {
+                Message::Text(text) => {
+                    debug!("Received message: {}", text);
+                    let server_message: ServerMessage = serde_json::from_str(&text)?;
+                    return Ok(Some(server_message));
+                }
+                // Ignore binary messages and wait for the next frame
+                Message::Bytes(_) => continue,
+            }
+        }
+
+        info!("WebSocket connection closed");
+        Ok(None)
+    }
+
+    /// Close the WebSocket connection once every queued message has been sent
+    pub async fn close(mut self) -> Result<()> {
+        // Drop the sender to signal the writer task to close
+        drop(self.sender);
+
+        // Wait for the writer task to complete
+        self.writer_task.join().await;
+
+        // Wait for the server to answer the close frame or hang up. Dropping the socket
+        // while the server still has unread frames would reset the connection and
+        // discard them.
+        while let Some(Ok(_)) = self.reader.next().await {}
+
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+
+use base64::{DecodeError, Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+
+use crate::{mask::MaskState, models::Pos};
+
+/// Current layout version, stored in the first byte of every code
+const VERSION: u8 = 1;
+/// Version byte followed by width and height as little-endian `u32`s
+const HEADER_LEN: usize = 9;
+
+/// A complete board, bomb layout and visibility, for sharing puzzles as short text.
+///
+/// The code is the header (version, width, height) followed by a bitset of bombs, one
+/// bit per cell, and then the cell states packed two bits per cell as in
+/// [`crate::mask::RevealedMask`]. Both sections are row-major, least significant bits
+/// first, and padded to whole bytes. The bytes are URL-safe base64 without padding, so a
+/// code can be used in a link as is. Adjacency counts are not stored; use
+/// [`BoardCode::adjacent_bombs`] to recompute them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardCode {
+    pub width: usize,
+    pub height: usize,
+    /// Whether each cell holds a bomb, in row-major order
+    pub bombs: Vec<bool>,
+    /// Visibility of each cell, in row-major order
+    pub states: Vec<MaskState>,
+}
+
+/// Why a string could not be read as a [`BoardCode`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardCodeError {
+    /// Not valid URL-safe base64
+    Base64(DecodeError),
+    /// Written by an unknown version of the format
+    UnsupportedVersion(u8),
+    /// Width or height is zero
+    EmptyBoard,
+    /// Fewer bytes than the board size requires
+    Truncated,
+}
+
+impl fmt::Display for BoardCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base64(error) => write!(f, "invalid board code: {}", error),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported board code version {}", version)
+            }
+            Self::EmptyBoard => write!(f, "board code has no cells"),
+            Self::Truncated => write!(f, "board code is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for BoardCodeError {}
+
+impl BoardCode {
+    /// Encode the board as a URL-safe string
+    pub fn to_code(&self) -> String {
+        let cells = self.width * self.height;
+        let bomb_bytes = cells.div_ceil(8);
+        let mut bytes = vec![0u8; HEADER_LEN + bomb_bytes + cells.div_ceil(4)];
+
+        bytes[0] = VERSION;
+        bytes[1..5].copy_from_slice(&(self.width as u32).to_le_bytes());
+        bytes[5..9].copy_from_slice(&(self.height as u32).to_le_bytes());
+
+        let (bomb_section, state_section) = bytes[HEADER_LEN..].split_at_mut(bomb_bytes);
+        for (index, bomb) in self.bombs.iter().enumerate().take(cells) {
+            bomb_section[index / 8] |= (*bomb as u8) << (index % 8);
+        }
+        for (index, state) in self.states.iter().enumerate().take(cells) {
+            state_section[index / 4] |= (*state as u8) << ((index % 4) * 2);
+        }
+
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Decode a string produced by [`BoardCode::to_code`]
+    pub fn from_code(code: &str) -> Result<Self, BoardCodeError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(code.trim())
+            .map_err(BoardCodeError::Base64)?;
+
+        let header = bytes.get(..HEADER_LEN).ok_or(BoardCodeError::Truncated)?;
+        if header[0] != VERSION {
+            return Err(BoardCodeError::UnsupportedVersion(header[0]));
+        }
+        let width = u32::from_le_bytes(header[1..5].try_into().unwrap()) as usize;
+        let height = u32::from_le_bytes(header[5..9].try_into().unwrap()) as usize;
+        if width == 0 || height == 0 {
+            return Err(BoardCodeError::EmptyBoard);
+        }
+
+        let cells = width.checked_mul(height).ok_or(BoardCodeError::Truncated)?;
+        let bomb_bytes = cells.div_ceil(8);
+        if bytes.len() < HEADER_LEN + bomb_bytes + cells.div_ceil(4) {
+            return Err(BoardCodeError::Truncated);
+        }
+
+        let (bomb_section, state_section) = bytes[HEADER_LEN..].split_at(bomb_bytes);
+        let bombs = (0..cells)
+            .map(|index| bomb_section[index / 8] & (1 << (index % 8)) != 0)
+            .collect();
+        let states = (0..cells)
+            .map(|index| MaskState::from_bits(state_section[index / 4] >> ((index % 4) * 2)))
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            bombs,
+            states,
+        })
+    }
+
+    pub fn bomb_count(&self) -> usize {
+        self.bombs.iter().filter(|&&bomb| bomb).count()
+    }
+
+    /// Number of bombs surrounding `pos`
+    pub fn adjacent_bombs(&self, pos: Pos) -> u8 {
+        pos.neighbors(self.width, self.height)
+            .filter(|neighbor| self.bombs[neighbor.index(self.width)])
+            .count() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(width: usize, height: usize) -> BoardCode {
+        let cells = width * height;
+        BoardCode {
+            width,
+            height,
+            bombs: (0..cells).map(|index| index % 7 == 3).collect(),
+            states: (0..cells)
+                .map(|index| match index % 5 {
+                    0 => MaskState::Revealed,
+                    1 => MaskState::Flagged,
+                    2 => MaskState::Marked,
+                    _ => MaskState::Hidden,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn round_trips_boards_of_any_shape() {
+        for (width, height) in [(1, 1), (9, 9), (16, 16), (30, 16), (1, 37), (13, 3)] {
+            let board = board(width, height);
+            let code = board.to_code();
+            assert_eq!(
+                BoardCode::from_code(&code),
+                Ok(board),
+                "{}x{}",
+                width,
+                height
+            );
+        }
+    }
+
+    #[test]
+    fn code_is_url_safe() {
+        let code = board(30, 16).to_code();
+        assert!(
+            code.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        );
+    }
+
+    #[test]
+    fn adjacency_is_recomputed_from_bombs() {
+        let mut board = board(3, 3);
+        board.bombs = vec![true, false, true, false, false, false, false, false, false];
+        let decoded = BoardCode::from_code(&board.to_code()).unwrap();
+        assert_eq!(decoded.bomb_count(), 2);
+        assert_eq!(decoded.adjacent_bombs(Pos { x: 1, y: 1 }), 2);
+        assert_eq!(decoded.adjacent_bombs(Pos { x: 0, y: 1 }), 1);
+        assert_eq!(decoded.adjacent_bombs(Pos { x: 1, y: 2 }), 0);
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        let bytes = URL_SAFE_NO_PAD.decode(board(9, 9).to_code()).unwrap();
+        assert!(matches!(
+            BoardCode::from_code("not base64!"),
+            Err(BoardCodeError::Base64(_))
+        ));
+        assert_eq!(
+            BoardCode::from_code(&URL_SAFE_NO_PAD.encode(&bytes[..bytes.len() - 1])),
+            Err(BoardCodeError::Truncated)
+        );
+        assert_eq!(
+            BoardCode::from_code(&URL_SAFE_NO_PAD.encode([2, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0])),
+            Err(BoardCodeError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            BoardCode::from_code(&URL_SAFE_NO_PAD.encode([1, 0, 0, 0, 0, 1, 0, 0, 0])),
+            Err(BoardCodeError::EmptyBoard)
+        );
+    }
+}
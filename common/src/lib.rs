@@ -1,2 +1,4 @@
+pub mod board_code;
+pub mod mask;
 pub mod models;
 pub mod protocol;
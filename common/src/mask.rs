@@ -0,0 +1,175 @@
+use std::fmt;
+
+use base64::{DecodeError, Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Cell, Pos};
+
+/// Visibility of a single cell in a [`RevealedMask`], stored in two bits.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaskState {
+    Hidden = 0,
+    Revealed = 1,
+    Flagged = 2,
+    Marked = 3,
+}
+
+impl From<&Cell> for MaskState {
+    fn from(value: &Cell) -> Self {
+        match value {
+            Cell::Hidden => Self::Hidden,
+            Cell::Marked => Self::Marked,
+            Cell::Flagged => Self::Flagged,
+            Cell::Revealed { .. } | Cell::Bomb => Self::Revealed,
+        }
+    }
+}
+
+impl MaskState {
+    pub(crate) fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0 => Self::Hidden,
+            1 => Self::Revealed,
+            2 => Self::Flagged,
+            _ => Self::Marked,
+        }
+    }
+}
+
+/// Why a [`RevealedMask`] could not be decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskError {
+    /// Not valid base64
+    Base64(DecodeError),
+    /// Fewer bytes than `width * height` cells require
+    Truncated,
+}
+
+impl fmt::Display for MaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base64(error) => write!(f, "invalid mask: {}", error),
+            Self::Truncated => write!(f, "mask is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for MaskError {}
+
+/// Compact visibility snapshot of a board.
+///
+/// Cells are packed in row-major order, two bits each ([`MaskState`] discriminants),
+/// four cells per byte starting at the least significant bits. The bytes are base64
+/// encoded in `mask`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RevealedMask {
+    pub width: usize,
+    pub height: usize,
+    pub mask: String,
+}
+
+impl RevealedMask {
+    /// Pack cell states given in row-major order
+    pub fn encode(
+        width: usize,
+        height: usize,
+        states: impl IntoIterator<Item = MaskState>,
+    ) -> Self {
+        let mut bytes = vec![0u8; (width * height).div_ceil(4)];
+        for (index, state) in states.into_iter().enumerate().take(width * height) {
+            bytes[index / 4] |= (state as u8) << ((index % 4) * 2);
+        }
+
+        Self {
+            width,
+            height,
+            mask: STANDARD.encode(bytes),
+        }
+    }
+
+    /// Unpack the mask into rows of cell states, matching the layout of the board
+    pub fn decode(&self) -> Result<Vec<Vec<MaskState>>, MaskError> {
+        let bytes = STANDARD.decode(&self.mask).map_err(MaskError::Base64)?;
+        let cells = self.width * self.height;
+        if bytes.len() < cells.div_ceil(4) {
+            return Err(MaskError::Truncated);
+        }
+
+        let states: Vec<MaskState> = (0..cells)
+            .map(|index| MaskState::from_bits(bytes[index / 4] >> ((index % 4) * 2)))
+            .collect();
+
+        Ok(states
+            .chunks(self.width.max(1))
+            .map(|row| row.to_vec())
+            .collect())
+    }
+
+    /// Decode the state of a single cell
+    pub fn get(&self, pos: Pos) -> Option<MaskState> {
+        if pos.x >= self.width || pos.y >= self.height {
+            return None;
+        }
+
+        let index = pos.index(self.width);
+        let bytes = STANDARD.decode(&self.mask).ok()?;
+        let byte = bytes.get(index / 4)?;
+        Some(MaskState::from_bits(byte >> ((index % 4) * 2)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [MaskState; 4] = [
+        MaskState::Hidden,
+        MaskState::Revealed,
+        MaskState::Flagged,
+        MaskState::Marked,
+    ];
+
+    fn states(cells: usize) -> Vec<MaskState> {
+        (0..cells).map(|index| STATES[index % 4]).collect()
+    }
+
+    #[test]
+    fn round_trips_every_state() {
+        let mask = RevealedMask::encode(4, 2, states(8));
+        let rows = mask.decode().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows.concat(), states(8));
+        for (index, state) in states(8).into_iter().enumerate() {
+            assert_eq!(mask.get(Pos::from_index(index, 4)), Some(state));
+        }
+    }
+
+    #[test]
+    fn odd_cell_counts_fill_a_partial_last_byte() {
+        for (width, height) in [(1, 1), (3, 3), (5, 3), (7, 1)] {
+            let mask = RevealedMask::encode(width, height, states(width * height));
+            assert_eq!(
+                STANDARD.decode(&mask.mask).unwrap().len(),
+                (width * height).div_ceil(4)
+            );
+            assert_eq!(
+                mask.decode().unwrap().concat(),
+                states(width * height),
+                "{}x{}",
+                width,
+                height
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_short_or_invalid_masks() {
+        let mut mask = RevealedMask::encode(3, 3, states(9));
+        mask.width = 9;
+        assert_eq!(mask.decode(), Err(MaskError::Truncated));
+
+        mask.mask = "not base64!".to_string();
+        assert!(matches!(mask.decode(), Err(MaskError::Base64(_))));
+        assert_eq!(mask.get(Pos { x: 0, y: 0 }), None);
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+use crate::{board_code::BoardCode, mask::MaskState};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 #[serde(tag = "state")]
 pub enum Cell {
     #[serde(rename = "hidden")]
@@ -15,18 +17,113 @@ pub enum Cell {
     Bomb,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+impl Cell {
+    pub fn is_revealed(&self) -> bool {
+        matches!(self, Cell::Revealed { .. })
+    }
+
+    /// Not opened yet and not flagged, including `?`-marked cells
+    pub fn is_hidden(&self) -> bool {
+        matches!(self, Cell::Hidden | Cell::Marked)
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        matches!(self, Cell::Flagged)
+    }
+
+    pub fn is_bomb(&self) -> bool {
+        matches!(self, Cell::Bomb)
+    }
+
+    /// Number of adjacent bombs for a revealed cell, `None` otherwise
+    pub fn adjacent_count(&self) -> Option<u8> {
+        match self {
+            Cell::Revealed { adjacent } => Some(*adjacent),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Pos {
     pub x: usize,
     pub y: usize,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+impl Pos {
+    /// Row-major index of this position on a board `width` cells wide
+    pub fn index(&self, width: usize) -> usize {
+        self.x + self.y * width
+    }
+
+    /// Inverse of [`Pos::index`]
+    pub fn from_index(index: usize, width: usize) -> Pos {
+        Pos {
+            x: index % width,
+            y: index / width,
+        }
+    }
+
+    /// The up to eight surrounding positions that lie on a `width` x `height` board
+    pub fn neighbors(self, width: usize, height: usize) -> impl Iterator<Item = Pos> {
+        let Pos { x, y } = self;
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|&(dx, dy)| dx != 0 || dy != 0)
+            .filter_map(move |(dx, dy)| {
+                let x = x.checked_add_signed(dx)?;
+                let y = y.checked_add_signed(dy)?;
+                (x < width && y < height).then_some(Pos { x, y })
+            })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(default)]
 pub struct GameParams {
     pub width: usize,
     pub height: usize,
     pub bombs: usize,
+    /// Minimum number of cells the first reveal should open. The server regenerates the
+    /// board a bounded number of times to satisfy this; `0` disables the check.
+    pub min_opening_size: usize,
+    /// Seed for bomb placement. Games with the same width, height, bombs and seed get an
+    /// identical board; omit it for a random board.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Include 3BV-based efficiency stats in the game summary. Off by default since the
+    /// 3BV computation walks the whole board.
+    pub efficiency_stats: bool,
+    /// Include the `?` marked state in the flag cycle (Hidden → Flagged → Marked →
+    /// Hidden). When disabled, flagging toggles between Hidden and Flagged only.
+    pub enable_question_marks: bool,
+    /// Flag every remaining bomb when the game is won, so the finished board shows all
+    /// mines flagged.
+    pub auto_flag_on_win: bool,
+    /// List the game in the lobby so `POST /join` can hand it to players looking for any
+    /// open game. Games are private by default and only reachable by their ID.
+    pub public: bool,
+    /// Most players connected at once; further players are turned away with a
+    /// `game_full` error. Spectators don't count. `0` means unlimited.
+    pub max_players: usize,
+    /// Chord only when the flags around a number match it exactly (classic). When
+    /// disabled, chording also works with more flags than the number.
+    pub chord_requires_exact_flags: bool,
+    /// No-guess mode: the server checks after every reveal whether the single-cell rules
+    /// still prove some move. For now this is only detected and logged.
+    pub no_guess: bool,
+    /// Whether flags can still be placed and removed while the game is paused. Reveals
+    /// and chords are always blocked.
+    pub flags_while_paused: bool,
+    /// Seconds of play, counted from the first move, after which the game is lost.
+    /// Enforced by the server once the first cell is revealed; pauses don't count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_limit_secs: Option<u64>,
+    /// Predefined board from [`BoardCode::to_code`] to play instead of a random one, e.g.
+    /// for a daily puzzle. Its size and bomb count replace `width`, `height` and `bombs`,
+    /// and its flags and revealed cells are restored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
 }
 
 impl Default for GameParams {
@@ -35,11 +132,374 @@ impl Default for GameParams {
             width: 9,
             height: 9,
             bombs: 10,
+            min_opening_size: 0,
+            seed: None,
+            efficiency_stats: false,
+            enable_question_marks: true,
+            auto_flag_on_win: true,
+            public: false,
+            max_players: 0,
+            chord_requires_exact_flags: true,
+            no_guess: false,
+            flags_while_paused: true,
+            time_limit_secs: None,
+            code: None,
         }
     }
 }
 
+/// Optional server-side bounds on custom boards, checked by
+/// [`GameParams::validate_within`]. `None` leaves that aspect unrestricted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoardLimits {
+    pub max_width: Option<usize>,
+    pub max_height: Option<usize>,
+    /// Largest fraction of cells that may be bombs, e.g. `0.9`
+    pub max_bomb_density: Option<f64>,
+}
+
+impl GameParams {
+    /// Checks that the board has at least one cell, at least one bomb and at least one
+    /// safe cell. The error describes the first constraint that failed.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width == 0 {
+            return Err("width must be at least 1".to_string());
+        }
+        if self.height == 0 {
+            return Err("height must be at least 1".to_string());
+        }
+        if self.bombs == 0 {
+            return Err("bombs must be at least 1".to_string());
+        }
+        let Some(cells) = self.width.checked_mul(self.height) else {
+            return Err("width * height overflows".to_string());
+        };
+        if self.bombs >= cells {
+            return Err(format!(
+                "bombs must be less than width * height ({}) to leave a safe cell",
+                cells
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decodes `code`, if set, and copies the board's size and bomb count into `width`,
+    /// `height` and `bombs` so the usual checks apply to it. Codes that reveal a bomb or
+    /// leave no safe cell to reveal are rejected.
+    pub fn resolve_code(&mut self) -> Result<Option<BoardCode>, String> {
+        let Some(code) = &self.code else {
+            return Ok(None);
+        };
+        let board = BoardCode::from_code(code).map_err(|error| error.to_string())?;
+
+        let revealed_bomb = board
+            .bombs
+            .iter()
+            .zip(&board.states)
+            .any(|(&bomb, &state)| bomb && state == MaskState::Revealed);
+        if revealed_bomb {
+            return Err("board code reveals a bomb".to_string());
+        }
+        let hidden_safe = board
+            .bombs
+            .iter()
+            .zip(&board.states)
+            .any(|(&bomb, &state)| !bomb && state != MaskState::Revealed);
+        if !hidden_safe {
+            return Err("board code has no safe cell left to reveal".to_string());
+        }
+
+        self.width = board.width;
+        self.height = board.height;
+        self.bombs = board.bomb_count();
+        Ok(Some(board))
+    }
+
+    /// Like [`GameParams::validate`], but also enforces `limits`. The error names the
+    /// limit that was violated.
+    pub fn validate_within(&self, limits: &BoardLimits) -> Result<(), String> {
+        self.validate()?;
+
+        if let Some(max_width) = limits.max_width
+            && self.width > max_width
+        {
+            return Err(format!(
+                "width {} exceeds the maximum board width of {}",
+                self.width, max_width
+            ));
+        }
+        if let Some(max_height) = limits.max_height
+            && self.height > max_height
+        {
+            return Err(format!(
+                "height {} exceeds the maximum board height of {}",
+                self.height, max_height
+            ));
+        }
+        if let Some(max_density) = limits.max_bomb_density {
+            let density = self.bombs as f64 / (self.width * self.height) as f64;
+            if density > max_density {
+                return Err(format!(
+                    "bomb density {:.1}% exceeds the maximum of {:.1}%",
+                    density * 100.0,
+                    max_density * 100.0
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CreateResponse {
     pub id: String,
+    /// Parameters the game was actually created with after server-side clamping. The
+    /// seed is only included when requested with `?return_seed=true`.
+    pub params: GameParams,
+    /// Seed used to generate the board, returned when requested with `?return_seed=true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// Game handed out by `POST /join`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JoinResponse {
+    pub id: String,
+    /// Whether a new public game was created because none had room
+    pub created: bool,
+}
+
+/// Outcome of a game as returned by `GET /games/<id>/status`, for integrations that
+/// poll instead of keeping a WebSocket open
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameStatus {
+    pub finished: bool,
+    /// Only meaningful once `finished` is set
+    pub won: bool,
+    pub connections: usize,
+}
+
+/// Full layout of a finished game as returned by `GET /games/<id>/reveal`, for reviewing
+/// where the bombs were
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BoardSolution {
+    pub width: usize,
+    pub height: usize,
+    /// Every bomb position in row-major order
+    pub bombs: Vec<Pos>,
+    /// Number of bombs around each cell in row-major order, including around bomb cells
+    pub adjacent: Vec<u8>,
+}
+
+/// Public metadata of a running game as listed by `GET /games`. Never includes bomb
+/// positions or cell contents.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameSummary {
+    pub id: String,
+    pub width: usize,
+    pub height: usize,
+    pub bombs: usize,
+    pub connections: usize,
+    /// Connections allowed to play
+    pub players: usize,
+    /// Read-only connections joined with `?spectate=true`
+    pub spectators: usize,
+    pub finished: bool,
+    pub idle_secs: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(width: usize, height: usize, bombs: usize) -> GameParams {
+        GameParams {
+            width,
+            height,
+            bombs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cell_predicates_match_each_variant() {
+        // (cell, revealed, hidden, flagged, bomb, adjacent count)
+        let cases = [
+            (Cell::Hidden, false, true, false, false, None),
+            (Cell::Marked, false, true, false, false, None),
+            (Cell::Flagged, false, false, true, false, None),
+            (
+                Cell::Revealed { adjacent: 0 },
+                true,
+                false,
+                false,
+                false,
+                Some(0),
+            ),
+            (
+                Cell::Revealed { adjacent: 3 },
+                true,
+                false,
+                false,
+                false,
+                Some(3),
+            ),
+            (Cell::Bomb, false, false, false, true, None),
+        ];
+        for (cell, revealed, hidden, flagged, bomb, adjacent) in cases {
+            assert_eq!(cell.is_revealed(), revealed, "{:?}", cell);
+            assert_eq!(cell.is_hidden(), hidden, "{:?}", cell);
+            assert_eq!(cell.is_flagged(), flagged, "{:?}", cell);
+            assert_eq!(cell.is_bomb(), bomb, "{:?}", cell);
+            assert_eq!(cell.adjacent_count(), adjacent, "{:?}", cell);
+        }
+    }
+
+    fn neighbors(x: usize, y: usize, width: usize, height: usize) -> Vec<Pos> {
+        Pos { x, y }.neighbors(width, height).collect()
+    }
+
+    #[test]
+    fn corners_have_three_neighbors() {
+        for (x, y) in [(0, 0), (4, 0), (0, 3), (4, 3)] {
+            assert_eq!(neighbors(x, y, 5, 4).len(), 3, "({}, {})", x, y);
+        }
+        assert_eq!(
+            neighbors(0, 0, 5, 4),
+            [Pos { x: 1, y: 0 }, Pos { x: 0, y: 1 }, Pos { x: 1, y: 1 }]
+        );
+    }
+
+    #[test]
+    fn edges_have_five_neighbors_and_the_interior_eight() {
+        for (x, y) in [(2, 0), (0, 2), (4, 1), (3, 3)] {
+            assert_eq!(neighbors(x, y, 5, 4).len(), 5, "({}, {})", x, y);
+        }
+        let around = neighbors(2, 2, 5, 4);
+        assert_eq!(around.len(), 8);
+        assert!(!around.contains(&Pos { x: 2, y: 2 }));
+        assert!(
+            around
+                .iter()
+                .all(|pos| pos.x.abs_diff(2) <= 1 && pos.y.abs_diff(2) <= 1)
+        );
+    }
+
+    #[test]
+    fn neighbors_stay_on_the_board_at_any_size() {
+        assert!(neighbors(0, 0, 1, 1).is_empty());
+        assert_eq!(neighbors(0, 0, 1, 3), [Pos { x: 0, y: 1 }]);
+        // Positions at the edge of `usize` neither underflow nor overflow
+        let max = usize::MAX;
+        assert_eq!(
+            neighbors(max - 1, max - 1, max, max),
+            [
+                Pos {
+                    x: max - 2,
+                    y: max - 2
+                },
+                Pos {
+                    x: max - 1,
+                    y: max - 2
+                },
+                Pos {
+                    x: max - 2,
+                    y: max - 1
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_accepts_boards_with_a_safe_cell() {
+        assert!(params(1, 2, 1).validate().is_ok());
+        assert!(params(9, 9, 80).validate().is_ok());
+        assert!(GameParams::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_names_the_failed_constraint() {
+        let cases = [
+            (params(0, 9, 10), "width must be at least 1"),
+            (params(9, 0, 10), "height must be at least 1"),
+            (params(9, 9, 0), "bombs must be at least 1"),
+            (params(usize::MAX, 2, 1), "width * height overflows"),
+            (
+                params(9, 9, 81),
+                "bombs must be less than width * height (81)",
+            ),
+            (
+                params(1, 1, 1),
+                "bombs must be less than width * height (1)",
+            ),
+        ];
+        for (params, expected) in cases {
+            let error = params.validate().unwrap_err();
+            assert!(error.contains(expected), "{:?}: {}", params, error);
+        }
+    }
+
+    #[test]
+    fn validate_within_accepts_anything_without_limits() {
+        let limits = BoardLimits::default();
+        assert!(params(1, 10_000, 9_999).validate_within(&limits).is_ok());
+    }
+
+    #[test]
+    fn validate_within_names_the_violated_dimension() {
+        let limits = BoardLimits {
+            max_width: Some(100),
+            max_height: Some(50),
+            ..Default::default()
+        };
+        assert!(params(100, 50, 10).validate_within(&limits).is_ok());
+
+        let error = params(101, 10, 10).validate_within(&limits).unwrap_err();
+        assert!(error.contains("maximum board width of 100"), "{}", error);
+        let error = params(1, 10_000, 10).validate_within(&limits).unwrap_err();
+        assert!(error.contains("maximum board height of 50"), "{}", error);
+    }
+
+    #[test]
+    fn validate_within_rejects_dense_boards() {
+        let limits = BoardLimits {
+            max_bomb_density: Some(0.9),
+            ..Default::default()
+        };
+        assert!(params(10, 10, 90).validate_within(&limits).is_ok());
+
+        let error = params(10, 10, 91).validate_within(&limits).unwrap_err();
+        assert!(error.contains("bomb density"), "{}", error);
+    }
+
+    #[test]
+    fn resolve_code_takes_the_board_size_from_the_code() {
+        let board = BoardCode {
+            width: 4,
+            height: 2,
+            bombs: vec![true, false, false, false, false, false, false, true],
+            states: vec![MaskState::Hidden; 8],
+        };
+        let mut params = GameParams {
+            code: Some(board.to_code()),
+            ..Default::default()
+        };
+        assert_eq!(params.resolve_code(), Ok(Some(board)));
+        assert_eq!((params.width, params.height, params.bombs), (4, 2, 2));
+    }
+
+    #[test]
+    fn resolve_code_rejects_revealed_bombs() {
+        let board = BoardCode {
+            width: 2,
+            height: 1,
+            bombs: vec![true, false],
+            states: vec![MaskState::Revealed, MaskState::Hidden],
+        };
+        let mut params = GameParams {
+            code: Some(board.to_code()),
+            ..Default::default()
+        };
+        assert!(params.resolve_code().is_err());
+    }
 }
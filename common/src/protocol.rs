@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::models::{Cell, GameParams, Pos};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Longest chat message, in characters, the server relays
+pub const MAX_CHAT_LENGTH: usize = 500;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "action")]
 pub enum ClientMessage {
     #[serde(rename = "reveal")]
@@ -11,15 +15,67 @@ pub enum ClientMessage {
     Flag { pos: Pos },
     #[serde(rename = "restart")]
     Restart { params: GameParams },
+    #[serde(rename = "chord")]
+    Chord { pos: Pos },
+    /// Only receive updates for cells inside this region (inclusive)
+    #[serde(rename = "viewport")]
+    Viewport { top_left: Pos, bottom_right: Pos },
+    /// Revert the last reveal, flag, chord or hint; everyone receives a fresh `Init`
+    #[serde(rename = "undo")]
+    Undo,
+    /// Reveal one hidden cell that is guaranteed to be safe
+    #[serde(rename = "hint")]
+    Hint,
+    /// Ask for the current board, answered with an `Init` to this connection only
+    #[serde(rename = "get_state")]
+    GetState,
+    /// Send a chat message to everyone connected to the game, including the sender
+    #[serde(rename = "chat")]
+    Chat { text: String },
+    /// Stop the timer and block reveals and chords for everyone until someone resumes
+    #[serde(rename = "pause")]
+    Pause,
+    #[serde(rename = "resume")]
+    Resume,
+}
+
+impl ClientMessage {
+    /// Whether the message leaves the board untouched, and so is allowed from spectators
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Self::Viewport { .. } | Self::GetState | Self::Chat { .. }
+        )
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct CellUpdate {
     pub pos: Pos,
     pub value: Cell,
+    /// Breadth-first distance from the revealed cell for cascade updates. Only sent to
+    /// connections that opted in with `cascade_depth=true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<usize>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Efficiency of a finished game, sent when `GameParams::efficiency_stats` is set.
+/// Useful clicks are reveals and chords that opened at least one cell; every other
+/// action, including flags, counts as wasted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct EfficiencyStats {
+    pub three_bv: usize,
+    pub useful_clicks: usize,
+    pub wasted_clicks: usize,
+    pub three_bv_per_second: f64,
+}
+
+/// Servers that predate `Init::enable_question_marks` always use the three-state cycle
+fn question_marks_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "type")]
 pub enum ServerMessage {
     #[serde(rename = "init")]
@@ -28,11 +84,233 @@ pub enum ServerMessage {
         height: usize,
         bombs: usize,
         field: Vec<Vec<Cell>>,
+        /// Seconds since the first move, `0` for a game that has not started yet
+        #[serde(default)]
+        elapsed_secs: u64,
+        #[serde(default)]
+        flags_placed: usize,
+        /// Reveals, chords, hints and flag changes applied so far
+        #[serde(default)]
+        moves: u64,
+        /// Player token of the receiving connection. Only sent in the `Init` addressed to
+        /// a single connection; pass it back as `/ws?token=` to resume as the same player.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        your_token: Option<Uuid>,
+        /// Board revision this state reflects; see `Update::revision`
+        #[serde(default)]
+        revision: u64,
+        #[serde(default)]
+        paused: bool,
+        /// Whether flagging cycles through the `?` marked state, see
+        /// `GameParams::enable_question_marks`
+        #[serde(default = "question_marks_default")]
+        enable_question_marks: bool,
     },
+    /// Cells that changed. Also sent instead of an `Init` to a player reconnecting with
+    /// `/ws?token=<token>&rev=<revision>` while the server still has every change since
+    /// that revision; `updates` then holds all of them in order.
     #[serde(rename = "update")]
     Update {
         updates: Vec<CellUpdate>,
         won: bool,
         lost: bool,
+        /// Seconds since the first move, frozen once the game is over
+        #[serde(default)]
+        elapsed_secs: u64,
+        /// Number of flagged cells on the whole board, regardless of viewport
+        #[serde(default)]
+        flags_placed: usize,
+        /// Reveals, chords, hints and flag changes applied so far. Ignored actions, such
+        /// as flagging a revealed cell, are not counted.
+        #[serde(default)]
+        moves: u64,
+        /// Counter bumped by every change to the board. Pass the latest one back as
+        /// `/ws?rev=` when reconnecting to receive only what was missed.
+        #[serde(default)]
+        revision: u64,
+    },
+    #[serde(rename = "game_summary")]
+    GameSummary {
+        won: bool,
+        elapsed_ms: u64,
+        moves: u64,
+        reveals: usize,
+        flags: usize,
+        board_cleared_pct: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        efficiency: Option<EfficiencyStats>,
     },
+    /// Sent before the `Init` of a restarted game. `params` are the effective parameters
+    /// including the seed; `new_layout` is false only when the same seed was reused.
+    #[serde(rename = "restarted")]
+    Restarted {
+        params: GameParams,
+        params_changed: bool,
+        new_layout: bool,
+    },
+    #[serde(rename = "expiry_warning")]
+    ExpiryWarning { seconds_remaining: u64 },
+    /// Number of connections to the game, spectators included. Broadcast whenever someone
+    /// joins or leaves.
+    #[serde(rename = "presence")]
+    Presence { players: usize },
+    /// Chat message relayed to every connection of the game. `from` identifies the
+    /// sending connection.
+    #[serde(rename = "chat")]
+    Chat { from: Uuid, text: String },
+    /// Broadcast when someone pauses or resumes the game
+    #[serde(rename = "paused")]
+    Paused { paused: bool },
+    /// Sent to a single connection when one of its messages was rejected. `code` is one of
+    /// `invalid_message`, `out_of_bounds`, `cell_revealed`, `cell_flagged`, `game_finished`,
+    /// `no_hint`, `nothing_to_undo`, `invalid_params`, `board_too_large`, `chat_too_long`,
+    /// `too_fast`, `game_full`, `paused` or `closed`.
+    #[serde(rename = "error")]
+    Error { code: String, message: String },
+}
+
+impl ServerMessage {
+    /// The `type` tag the message is sent with, e.g. `"update"`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Init { .. } => "init",
+            Self::Update { .. } => "update",
+            Self::GameSummary { .. } => "game_summary",
+            Self::Restarted { .. } => "restarted",
+            Self::ExpiryWarning { .. } => "expiry_warning",
+            Self::Presence { .. } => "presence",
+            Self::Chat { .. } => "chat",
+            Self::Paused { .. } => "paused",
+            Self::Error { .. } => "error",
+        }
+    }
+}
+
+/// Encodes a protocol message as MessagePack for a binary WebSocket frame. Structs are
+/// written as maps with their field names, so the tagged enums and defaulted fields
+/// decode exactly like their JSON form.
+#[cfg(feature = "binary")]
+pub fn to_bytes<T: Serialize>(message: &T) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    let mut bytes = Vec::new();
+    let mut serializer = rmp_serde::Serializer::new(&mut bytes)
+        .with_struct_map()
+        .with_human_readable();
+    message.serialize(&mut serializer)?;
+    Ok(bytes)
+}
+
+/// Decodes a protocol message written by [`to_bytes`]
+#[cfg(feature = "binary")]
+pub fn from_bytes<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, rmp_serde::decode::Error> {
+    let mut deserializer = rmp_serde::Deserializer::from_read_ref(bytes).with_human_readable();
+    T::deserialize(&mut deserializer)
+}
+
+#[cfg(all(test, feature = "binary"))]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Serialize + for<'a> Deserialize<'a> + std::fmt::Debug>(message: T) {
+        let bytes = to_bytes(&message).unwrap();
+        let decoded: T = from_bytes(&bytes).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", message));
+    }
+
+    #[test]
+    fn client_messages_survive_binary_encoding() {
+        let pos = Pos { x: 3, y: 7 };
+        for message in [
+            ClientMessage::Reveal { pos },
+            ClientMessage::Flag { pos },
+            ClientMessage::Chord { pos },
+            ClientMessage::Restart {
+                params: GameParams {
+                    seed: Some(42),
+                    code: Some("AQID".to_string()),
+                    ..Default::default()
+                },
+            },
+            ClientMessage::Viewport {
+                top_left: Pos { x: 0, y: 0 },
+                bottom_right: pos,
+            },
+            ClientMessage::Undo,
+            ClientMessage::Hint,
+            ClientMessage::GetState,
+            ClientMessage::Chat {
+                text: "gg".to_string(),
+            },
+            ClientMessage::Pause,
+            ClientMessage::Resume,
+        ] {
+            round_trip(message);
+        }
+    }
+
+    #[test]
+    fn server_messages_survive_binary_encoding() {
+        let update = CellUpdate {
+            pos: Pos { x: 1, y: 2 },
+            value: Cell::Revealed { adjacent: 3 },
+            depth: Some(4),
+        };
+        for message in [
+            ServerMessage::Init {
+                width: 2,
+                height: 1,
+                bombs: 1,
+                field: vec![vec![Cell::Hidden, Cell::Flagged]],
+                elapsed_secs: 5,
+                flags_placed: 1,
+                moves: 2,
+                your_token: Some(Uuid::new_v4()),
+                revision: 3,
+                paused: true,
+                enable_question_marks: false,
+            },
+            ServerMessage::Update {
+                updates: vec![update],
+                won: false,
+                lost: true,
+                elapsed_secs: 9,
+                flags_placed: 0,
+                moves: 4,
+                revision: 5,
+            },
+            ServerMessage::GameSummary {
+                won: true,
+                elapsed_ms: 1500,
+                moves: 10,
+                reveals: 8,
+                flags: 2,
+                board_cleared_pct: 100.0,
+                efficiency: Some(EfficiencyStats {
+                    three_bv: 7,
+                    useful_clicks: 6,
+                    wasted_clicks: 4,
+                    three_bv_per_second: 4.5,
+                }),
+            },
+            ServerMessage::Restarted {
+                params: GameParams::default(),
+                params_changed: false,
+                new_layout: true,
+            },
+            ServerMessage::ExpiryWarning {
+                seconds_remaining: 60,
+            },
+            ServerMessage::Presence { players: 3 },
+            ServerMessage::Chat {
+                from: Uuid::new_v4(),
+                text: "hi".to_string(),
+            },
+            ServerMessage::Paused { paused: true },
+            ServerMessage::Error {
+                code: "no_hint".to_string(),
+                message: "No safe hidden cell left".to_string(),
+            },
+        ] {
+            round_trip(message);
+        }
+    }
 }
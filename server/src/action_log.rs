@@ -0,0 +1,171 @@
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use minesweeper_common::protocol::ClientMessage;
+use serde::Serialize;
+use tokio::{
+    fs::OpenOptions,
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+#[derive(Serialize, Debug)]
+pub struct ActionLogEntry {
+    pub timestamp_ms: u64,
+    pub game_id: String,
+    pub stream_id: Uuid,
+    #[serde(flatten)]
+    pub action: ClientMessage,
+}
+
+/// Append-only JSON lines audit log of player actions. Entries are handed to a
+/// background writer task so game logic never waits on disk I/O.
+#[derive(Clone, Default)]
+pub struct ActionLogger {
+    sender: Option<mpsc::UnboundedSender<ActionLogEntry>>,
+}
+
+impl ActionLogger {
+    pub fn log(&self, game_id: &str, stream_id: &Uuid, action: &ClientMessage) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+
+        let _ = sender.send(ActionLogEntry {
+            timestamp_ms,
+            game_id: game_id.to_string(),
+            stream_id: *stream_id,
+            action: action.clone(),
+        });
+    }
+}
+
+/// Starts the writer task if `ACTION_LOG_PATH` is set, otherwise returns a disabled logger.
+/// Must be called from within the Tokio runtime.
+pub fn create_action_logger() -> ActionLogger {
+    let Ok(path) = env::var("ACTION_LOG_PATH") else {
+        return ActionLogger::default();
+    };
+
+    let (sender, receiver) = mpsc::unbounded_channel::<ActionLogEntry>();
+
+    tokio::spawn(async move {
+        let file = match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open action log {}: {}", path, e);
+                return;
+            }
+        };
+        info!("Writing action log to {}", path);
+        write_entries(file, receiver).await;
+    });
+
+    ActionLogger {
+        sender: Some(sender),
+    }
+}
+
+/// Logger whose entries go to `writer` instead of `ACTION_LOG_PATH`.
+/// Must be called from within the Tokio runtime.
+pub fn action_logger_with_writer(writer: impl AsyncWrite + Unpin + Send + 'static) -> ActionLogger {
+    let (sender, receiver) = mpsc::unbounded_channel::<ActionLogEntry>();
+    tokio::spawn(write_entries(writer, receiver));
+
+    ActionLogger {
+        sender: Some(sender),
+    }
+}
+
+/// Writes each entry as a JSON line until every logger is dropped
+async fn write_entries(
+    mut writer: impl AsyncWrite + Unpin,
+    mut receiver: mpsc::UnboundedReceiver<ActionLogEntry>,
+) {
+    while let Some(entry) = receiver.recv().await {
+        let mut line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize action log entry: {}", e);
+                continue;
+            }
+        };
+        line.push('\n');
+
+        if let Err(e) = writer.write_all(line.as_bytes()).await {
+            warn!("Failed to write action log entry: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use minesweeper_common::models::Pos;
+    use tokio::io::{AsyncBufReadExt, BufReader, duplex};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn actions_are_written_as_json_lines() {
+        let (writer, reader) = duplex(4096);
+        let logger = action_logger_with_writer(writer);
+        let stream_id = Uuid::new_v4();
+        logger.log(
+            "game",
+            &stream_id,
+            &ClientMessage::Reveal {
+                pos: Pos { x: 3, y: 4 },
+            },
+        );
+
+        let mut lines = BufReader::new(reader).lines();
+        let line = lines.next_line().await.unwrap().unwrap();
+        let entry: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(entry["game_id"], "game");
+        assert_eq!(entry["stream_id"], stream_id.to_string());
+        assert_eq!(entry["action"], "reveal");
+        assert_eq!(entry["pos"]["x"], 3);
+        assert_eq!(entry["pos"]["y"], 4);
+        assert!(entry["timestamp_ms"].as_u64().unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn slow_writer_does_not_block_logging() {
+        // Nobody reads the other end yet, so the writer stalls once 64 bytes are buffered
+        let (writer, reader) = duplex(64);
+        let logger = action_logger_with_writer(writer);
+        let stream_id = Uuid::new_v4();
+
+        let started = Instant::now();
+        for _ in 0..1000 {
+            logger.log("game", &stream_id, &ClientMessage::GetState);
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // Everything arrives once the reader catches up
+        drop(logger);
+        let mut lines = BufReader::new(reader).lines();
+        let mut count = 0;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            assert!(line.contains("get_state"), "{}", line);
+            count += 1;
+        }
+        assert_eq!(count, 1000);
+    }
+}
@@ -0,0 +1,193 @@
+use std::{path::PathBuf, sync::Arc, time::Instant};
+
+use dashmap::DashMap;
+use rocket::{
+    Build, Orbit, Rocket,
+    fairing::{Fairing, Info, Kind},
+    routes,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    action_log::create_action_logger,
+    cleanup::start_cleanup_task,
+    connections::{create_connection_limiter, create_ip_connection_limiter},
+    cors::create_cors_from_env,
+    logic::{GameLimit, Games, MaxCascadeRadius},
+    metrics::create_metrics,
+    persistence::{load_games, persist_path, save_games},
+    presets::{AllowedBoardSizes, MaxBoardCells, board_limits_from_env},
+    rate_limit::{MessageRateLimit, RateLimitConfig, create_rate_limiter},
+    routes::{
+        GameIdConfig, ServerStart, create_game, delete_game, get_game_status, get_rate_limit,
+        get_revealed_mask, get_solution, health, join_public_game, list_games, metrics,
+        reset_rate_limit_for_ip, websocket_handler,
+    },
+};
+
+struct CleanupFairing;
+
+#[rocket::async_trait]
+impl Fairing for CleanupFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Cleanup Task",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        if let Some(games) = rocket.state::<Games>() {
+            info!("Starting cleanup task for game management");
+            let games_for_cleanup = games.clone();
+            tokio::spawn(async move {
+                start_cleanup_task(games_for_cleanup).await;
+            });
+        } else {
+            warn!("Failed to get games state for cleanup task");
+        }
+        Ok(rocket)
+    }
+}
+
+struct ActionLogFairing;
+
+#[rocket::async_trait]
+impl Fairing for ActionLogFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Action Log",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        Ok(rocket.manage(create_action_logger()))
+    }
+}
+
+/// Reads `MessageRateLimit` from the environment unless one was already given to the
+/// builder, which `manage` would otherwise reject as a duplicate.
+struct MessageRateLimitFairing;
+
+#[rocket::async_trait]
+impl Fairing for MessageRateLimitFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "WebSocket Message Limit",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        if rocket.state::<MessageRateLimit>().is_some() {
+            return Ok(rocket);
+        }
+        Ok(rocket.manage(MessageRateLimit::from_env()))
+    }
+}
+
+/// Reads `AllowedBoardSizes` from the environment unless one was already given to the
+/// builder, like `MessageRateLimitFairing`.
+struct AllowedBoardSizesFairing;
+
+#[rocket::async_trait]
+impl Fairing for AllowedBoardSizesFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Allowed Board Sizes",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        if rocket.state::<AllowedBoardSizes>().is_some() {
+            return Ok(rocket);
+        }
+        Ok(rocket.manage(AllowedBoardSizes::from_env()))
+    }
+}
+
+/// Saves all games to `PERSIST_PATH` when the server shuts down.
+struct PersistenceFairing(PathBuf);
+
+#[rocket::async_trait]
+impl Fairing for PersistenceFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Game Persistence",
+            kind: Kind::Shutdown,
+        }
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        let Some(games) = rocket.state::<Games>() else {
+            warn!("Failed to get games state for persistence");
+            return;
+        };
+        if let Err(e) = save_games(&self.0, games).await {
+            error!("Failed to save games to {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Builds the server with all state, fairings and routes, configured from the
+/// environment. Used by `main` and by tests that launch the server in-process.
+pub fn build_rocket() -> Rocket<Build> {
+    let games: Games = Arc::new(DashMap::new());
+    let rate_limiter = create_rate_limiter();
+    let connection_limiter = create_connection_limiter();
+    let metrics = create_metrics();
+    let max_cascade_radius = MaxCascadeRadius::from_env();
+
+    info!("📊 Initialized game storage and rate limiter");
+
+    let mut rocket = rocket::build();
+    if let Some(path) = persist_path() {
+        load_games(&path, &games, &metrics, max_cascade_radius);
+        rocket = rocket.attach(PersistenceFairing(path));
+    }
+
+    let rocket = rocket
+        .attach(create_cors_from_env())
+        .attach(CleanupFairing)
+        .attach(ActionLogFairing)
+        .attach(MessageRateLimitFairing)
+        .attach(AllowedBoardSizesFairing)
+        .manage(games)
+        .manage(GameLimit::from_env())
+        .manage(GameIdConfig::from_env())
+        .manage(board_limits_from_env())
+        .manage(MaxBoardCells::from_env())
+        .manage(max_cascade_radius)
+        .manage(rate_limiter)
+        .manage(RateLimitConfig::from_env())
+        .manage(connection_limiter)
+        .manage(create_ip_connection_limiter())
+        .manage(ServerStart(Instant::now()))
+        .manage(metrics)
+        .mount(
+            "/",
+            routes![
+                create_game,
+                join_public_game,
+                websocket_handler,
+                get_revealed_mask,
+                get_game_status,
+                get_solution,
+                list_games,
+                delete_game,
+                health,
+                metrics,
+                get_rate_limit,
+                reset_rate_limit_for_ip
+            ],
+        );
+
+    info!("🌐 Server configured with CORS, cleanup task, and routes");
+    info!(
+        "📡 Endpoints: POST /create, POST /join, GET /ws, GET /games/<id>/mask, GET /games/<id>/status, GET /games/<id>/reveal, GET /games, DELETE /games/<id>, GET /health, GET /metrics, GET /admin/rate-limits/<ip>, POST /admin/rate-limits/reset"
+    );
+
+    rocket
+}
@@ -0,0 +1,128 @@
+use std::env;
+
+use rocket::{
+    Request,
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+use tracing::warn;
+
+/// Request guard for admin endpoints. Requires `Authorization: Bearer <ADMIN_API_TOKEN>`;
+/// admin endpoints respond with 404 when no token is configured.
+pub struct AdminToken;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = match env::var("ADMIN_API_TOKEN") {
+            Ok(token) if !token.is_empty() => token,
+            _ => return Outcome::Error((Status::NotFound, ())),
+        };
+
+        let provided = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match provided {
+            Some(token) if token == expected => Outcome::Success(AdminToken),
+            _ => {
+                warn!(
+                    "Rejected admin request to {} from {:?}",
+                    request.uri(),
+                    request.client_ip()
+                );
+                Outcome::Error((Status::Unauthorized, ()))
+            }
+        }
+    }
+}
+
+/// Request guard for the game management API. Responds with 404 unless
+/// `ENABLE_ADMIN_API=true`, so the endpoints are invisible by default.
+pub struct AdminApiEnabled;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminApiEnabled {
+    type Error = ();
+
+    async fn from_request(_request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match env::var("ENABLE_ADMIN_API") {
+            Ok(value) if value.eq_ignore_ascii_case("true") => Outcome::Success(AdminApiEnabled),
+            _ => Outcome::Error((Status::NotFound, ())),
+        }
+    }
+}
+
+/// Request guard for game creation. Requires `Authorization: Bearer <CREATE_API_TOKEN>`
+/// when that variable is set; without it anyone can create games.
+pub struct ApiToken;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = env::var("CREATE_API_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+        let provided = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match check_api_token(expected.as_deref(), provided) {
+            Ok(()) => Outcome::Success(ApiToken),
+            Err(status) => {
+                warn!(
+                    "Rejected game creation from {:?}: missing or wrong API token",
+                    request.client_ip()
+                );
+                Outcome::Error((status, ()))
+            }
+        }
+    }
+}
+
+/// Checks a bearer token against the configured one; anything passes when none is set.
+fn check_api_token(expected: Option<&str>, provided: Option<&str>) -> Result<(), Status> {
+    match (expected, provided) {
+        (None, _) => Ok(()),
+        (Some(expected), Some(provided)) if provided == expected => Ok(()),
+        _ => Err(Status::Unauthorized),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_token_is_optional_when_unset() {
+        assert_eq!(check_api_token(None, None), Ok(()));
+        assert_eq!(check_api_token(None, Some("anything")), Ok(()));
+    }
+
+    #[test]
+    fn missing_api_token_is_rejected() {
+        assert_eq!(
+            check_api_token(Some("secret"), None),
+            Err(Status::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn wrong_api_token_is_rejected() {
+        assert_eq!(
+            check_api_token(Some("secret"), Some("guess")),
+            Err(Status::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn correct_api_token_is_accepted() {
+        assert_eq!(check_api_token(Some("secret"), Some("secret")), Ok(()));
+    }
+}
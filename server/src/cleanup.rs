@@ -3,52 +3,83 @@ use std::{env, time::Duration};
 use tokio::time;
 use tracing::{debug, info};
 
-use crate::logic::Games;
+use crate::logic::{CleanupAction, Games};
 
-pub async fn start_cleanup_task(games: Games) {
-    let cleanup_interval_secs: u64 = env::var("CLEANUP_INTERVAL_SECONDS")
-        .unwrap_or_else(|_| "60".to_string())
+pub(crate) fn env_secs(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .unwrap_or_else(|_| default.to_string())
         .parse()
-        .unwrap_or(60);
+        .unwrap_or(default)
+}
 
-    let inactive_timeout_secs: u64 = env::var("INACTIVE_GAME_TIMEOUT_SECONDS")
-        .unwrap_or_else(|_| "600".to_string())
-        .parse()
-        .unwrap_or(600);
+pub async fn start_cleanup_task(games: Games) {
+    let cleanup_interval_secs = env_secs("CLEANUP_INTERVAL_SECONDS", 60);
+    let inactive_timeout_secs = env_secs("INACTIVE_GAME_TIMEOUT_SECONDS", 600);
+    let active_timeout_secs = env_secs("ACTIVE_GAME_TIMEOUT_SECONDS", 3600);
+    let warning_secs = env_secs("EXPIRY_WARNING_SECONDS", 60);
 
     let mut interval = time::interval(Duration::from_secs(cleanup_interval_secs));
 
     info!(
-        "Started game cleanup task: checking every {}s, inactive timeout: {}s",
-        cleanup_interval_secs, inactive_timeout_secs
+        "Started game cleanup task: checking every {}s, inactive timeout: {}s, active timeout: {}s, expiry warning: {}s",
+        cleanup_interval_secs, inactive_timeout_secs, active_timeout_secs, warning_secs
     );
 
     loop {
         interval.tick().await;
-        cleanup_games(&games, inactive_timeout_secs).await;
+        cleanup_games(
+            &games,
+            inactive_timeout_secs,
+            active_timeout_secs,
+            warning_secs,
+        )
+        .await;
     }
 }
 
-async fn cleanup_games(games: &Games, inactive_timeout_secs: u64) {
+/// One pass of the cleanup task: warns the connections of games about to expire and
+/// removes expired games, closing whatever connections they still have.
+pub async fn cleanup_games(
+    games: &Games,
+    inactive_timeout_secs: u64,
+    active_timeout_secs: u64,
+    warning_secs: u64,
+) {
+    let mut games_to_warn = Vec::new();
     let mut games_to_remove = Vec::new();
 
-    // First pass: identify games to remove
+    // First pass: identify games to warn or remove
     for entry in games.iter() {
         let game_id = entry.key();
         let game = entry.value();
 
         // Try to lock the game, skip if we can't (probably in use)
-        if let Ok(game_guard) = game.try_lock()
-            && game_guard.should_cleanup(inactive_timeout_secs)
-        {
-            games_to_remove.push(game_id.clone());
+        if let Ok(game_guard) = game.try_lock() {
+            match game_guard.cleanup_action(
+                inactive_timeout_secs,
+                active_timeout_secs,
+                warning_secs,
+            ) {
+                CleanupAction::Keep => {}
+                CleanupAction::Warn { seconds_remaining } => {
+                    games_to_warn.push((game.clone(), seconds_remaining));
+                }
+                CleanupAction::Remove => games_to_remove.push(game_id.clone()),
+            }
         }
     }
 
-    // Second pass: remove identified games
+    // Second pass: warn idle connections that their game is about to expire
+    for (game, seconds_remaining) in games_to_warn {
+        game.lock().await.warn_expiry(seconds_remaining).await;
+    }
+
+    // Third pass: remove identified games and close lingering connections
     let removed_count = games_to_remove.len();
     for game_id in games_to_remove {
-        games.remove(&game_id);
+        if let Some((_, game)) = games.remove(&game_id) {
+            game.lock().await.close_streams().await;
+        }
         debug!("Cleaned up game: {}", game_id);
     }
 
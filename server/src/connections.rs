@@ -0,0 +1,245 @@
+use std::{
+    env,
+    net::IpAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use dashmap::{DashMap, mapref::entry::Entry};
+use tracing::{debug, info, warn};
+
+use crate::cleanup::env_secs;
+
+/// Server-wide cap on concurrent WebSocket connections (`MAX_TOTAL_CONNECTIONS`).
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionLimiter {
+    active: Arc<AtomicUsize>,
+    max: Option<usize>,
+}
+
+/// Holds one connection slot and releases it when dropped, so the count is
+/// decremented on every exit path of the WebSocket handler.
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let remaining = self.active.fetch_sub(1, Ordering::AcqRel) - 1;
+        debug!(
+            "Connection slot released, active connections: {}",
+            remaining
+        );
+    }
+}
+
+impl ConnectionLimiter {
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            active: Arc::new(AtomicUsize::new(0)),
+            max,
+        }
+    }
+
+    /// Reserves a connection slot, or returns `None` if the server is at capacity.
+    pub fn try_acquire(&self) -> Option<ConnectionGuard> {
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                match self.max {
+                    Some(max) if active >= max => None,
+                    _ => Some(active + 1),
+                }
+            })
+            .ok()?;
+
+        Some(ConnectionGuard {
+            active: self.active.clone(),
+        })
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+}
+
+pub fn create_connection_limiter() -> ConnectionLimiter {
+    let max = env::var("MAX_TOTAL_CONNECTIONS")
+        .ok()
+        .and_then(|value| value.parse().ok());
+
+    if let Some(max) = max {
+        info!("Limiting server to {} concurrent connections", max);
+    }
+
+    ConnectionLimiter::new(max)
+}
+
+/// Cap on concurrent WebSocket connections from a single IP (`MAX_WS_PER_IP`).
+#[derive(Debug, Clone, Default)]
+pub struct IpConnectionLimiter {
+    active: Arc<DashMap<IpAddr, usize>>,
+    max: Option<usize>,
+}
+
+/// Holds one of an IP's connection slots and releases it when dropped. The IP's entry is
+/// removed once its last connection closes, so the map only holds connected clients.
+#[derive(Debug)]
+pub struct IpConnectionGuard {
+    active: Arc<DashMap<IpAddr, usize>>,
+    ip: IpAddr,
+}
+
+impl Drop for IpConnectionGuard {
+    fn drop(&mut self) {
+        if let Entry::Occupied(mut entry) = self.active.entry(self.ip) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+}
+
+impl IpConnectionLimiter {
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            active: Arc::new(DashMap::new()),
+            max,
+        }
+    }
+
+    /// Reserves a connection slot for `ip`, or returns `None` if it already has the
+    /// maximum number of connections open.
+    pub fn try_acquire(&self, ip: IpAddr) -> Option<IpConnectionGuard> {
+        let mut active = self.active.entry(ip).or_insert(0);
+        if self.max.is_some_and(|max| *active >= max) {
+            return None;
+        }
+        *active += 1;
+        drop(active);
+
+        Some(IpConnectionGuard {
+            active: self.active.clone(),
+            ip,
+        })
+    }
+
+    pub fn active_connections(&self, ip: &IpAddr) -> usize {
+        self.active.get(ip).map_or(0, |active| *active)
+    }
+}
+
+pub fn create_ip_connection_limiter() -> IpConnectionLimiter {
+    let max = env::var("MAX_WS_PER_IP")
+        .ok()
+        .and_then(|value| value.parse().ok());
+
+    if let Some(max) = max {
+        info!("Limiting each IP to {} concurrent connections", max);
+    }
+
+    IpConnectionLimiter::new(max)
+}
+
+/// How often a WebSocket connection is pinged (`WS_PING_INTERVAL_SECS`, default 30,
+/// at least 1).
+pub fn ping_interval() -> Duration {
+    at_least_one_sec(
+        "WS_PING_INTERVAL_SECS",
+        env_secs("WS_PING_INTERVAL_SECS", 30),
+    )
+}
+
+/// How long to wait for a pong before treating the connection as dead
+/// (`WS_PONG_TIMEOUT_SECS`, default 10, at least 1). A zero timeout would drop every
+/// connection before its pong could arrive.
+pub fn pong_timeout() -> Duration {
+    at_least_one_sec("WS_PONG_TIMEOUT_SECS", env_secs("WS_PONG_TIMEOUT_SECS", 10))
+}
+
+fn at_least_one_sec(name: &str, secs: u64) -> Duration {
+    if secs == 0 {
+        warn!("{} must be at least 1, using 1", name);
+    }
+    Duration::from_secs(secs.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    const OTHER_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 8));
+
+    #[test]
+    fn keepalive_durations_are_at_least_a_second() {
+        assert_eq!(
+            at_least_one_sec("WS_PONG_TIMEOUT_SECS", 0),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            at_least_one_sec("WS_PONG_TIMEOUT_SECS", 10),
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn connections_past_the_server_cap_are_rejected() {
+        let limiter = ConnectionLimiter::new(Some(2));
+        let first = limiter.try_acquire().expect("slot below the limit");
+        let second = limiter.try_acquire().expect("slot below the limit");
+        assert!(limiter.try_acquire().is_none());
+        assert_eq!(limiter.active_connections(), 2);
+
+        drop(first);
+        assert_eq!(limiter.active_connections(), 1);
+        let third = limiter
+            .try_acquire()
+            .expect("the dropped guard freed a slot");
+        assert!(limiter.try_acquire().is_none());
+
+        drop((second, third));
+        assert_eq!(limiter.active_connections(), 0);
+    }
+
+    #[test]
+    fn repeated_connects_from_one_ip_are_capped() {
+        let limiter = IpConnectionLimiter::new(Some(3));
+        let guards: Vec<_> = (0..3)
+            .map(|_| limiter.try_acquire(IP).expect("slot below the limit"))
+            .collect();
+
+        for _ in 0..10 {
+            assert!(limiter.try_acquire(IP).is_none());
+        }
+        assert_eq!(limiter.active_connections(&IP), 3);
+        assert!(limiter.try_acquire(OTHER_IP).is_some());
+
+        drop(guards);
+        assert_eq!(limiter.active_connections(&IP), 0);
+        assert!(limiter.try_acquire(IP).is_some());
+    }
+
+    #[test]
+    fn closed_connections_free_their_slot() {
+        let limiter = IpConnectionLimiter::new(Some(1));
+        for _ in 0..100 {
+            let guard = limiter.try_acquire(IP);
+            assert!(guard.is_some());
+        }
+        assert!(limiter.active.is_empty());
+    }
+
+    #[test]
+    fn unlimited_without_a_maximum() {
+        let limiter = IpConnectionLimiter::new(None);
+        let guards: Vec<_> = (0..1000).filter_map(|_| limiter.try_acquire(IP)).collect();
+        assert_eq!(guards.len(), 1000);
+    }
+}
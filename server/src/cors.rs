@@ -1,41 +1,198 @@
 use rocket::http::Method;
-use rocket_cors::{AllowedOrigins, CorsOptions};
-use std::env;
+use rocket_cors::{AllowedHeaders, AllowedOrigins, CorsOptions};
+use std::{env, time::Duration};
 
-pub fn create_cors() -> rocket_cors::Cors {
-    let allowed_origins_env =
-        env::var("CORS_ALLOWED_ORIGINS").unwrap_or_else(|_| "http://localhost:5173".to_string());
+/// CORS settings for the CORS fairing. [`CorsConfig::from_env`] gives the server's own
+/// configuration; applications mounting the routes in their own Rocket instance can
+/// build one directly and pass it to [`create_cors`].
+///
+/// - `CORS_ALLOWED_ORIGINS`: comma-separated origins (default `http://localhost:5173`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsConfig {
+    /// Exact origins allowed to make requests, e.g. `https://example.com`
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response; `None` leaves it to the browser
+    pub max_age: Option<Duration>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["http://localhost:5173".to_string()],
+            allowed_methods: vec![
+                Method::Get,
+                Method::Post,
+                Method::Put,
+                Method::Delete,
+                Method::Patch,
+                Method::Options,
+            ],
+            allowed_headers: [
+                "Authorization",
+                "Accept",
+                "Content-Type",
+                "X-Requested-With",
+            ]
+            .map(String::from)
+            .to_vec(),
+            allow_credentials: true,
+            max_age: None,
+        }
+    }
+}
 
-    let allowed_origins: Vec<String> = allowed_origins_env
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
+impl CorsConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        let allowed_origins = match env::var("CORS_ALLOWED_ORIGINS") {
+            Err(_) => default.allowed_origins,
+            Ok(value) => value
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        };
+
+        Self {
+            allowed_origins,
+            ..default
+        }
+    }
+}
 
-    let allowed_origins = AllowedOrigins::some_exact(&allowed_origins);
+/// Builds the CORS fairing. Fails if an origin can't be parsed.
+pub fn create_cors(config: CorsConfig) -> Result<rocket_cors::Cors, rocket_cors::Error> {
+    let allowed_headers: Vec<&str> = config.allowed_headers.iter().map(String::as_str).collect();
 
     CorsOptions {
-        allowed_origins,
-        allowed_methods: vec![
-            Method::Get,
-            Method::Post,
-            Method::Put,
-            Method::Delete,
-            Method::Patch,
-            Method::Options,
-        ]
-        .into_iter()
-        .map(|m| m.into())
-        .collect(),
-        allowed_headers: rocket_cors::AllowedHeaders::some(&[
-            "Authorization",
-            "Accept",
-            "Content-Type",
-            "X-Requested-With",
-        ]),
-        allow_credentials: true,
+        allowed_origins: AllowedOrigins::some_exact(&config.allowed_origins),
+        allowed_methods: config
+            .allowed_methods
+            .into_iter()
+            .map(|m| m.into())
+            .collect(),
+        allowed_headers: AllowedHeaders::some(&allowed_headers),
+        allow_credentials: config.allow_credentials,
+        max_age: config.max_age.map(|max_age| max_age.as_secs() as usize),
         ..Default::default()
     }
     .to_cors()
-    .expect("Failed to create CORS configuration")
+}
+
+/// The CORS fairing configured from the environment, see [`CorsConfig::from_env`]
+pub fn create_cors_from_env() -> rocket_cors::Cors {
+    create_cors(CorsConfig::from_env()).expect("Failed to create CORS configuration")
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        get,
+        http::{Header, Status},
+        local::asynchronous::Client,
+        routes,
+    };
+
+    use super::*;
+
+    #[get("/ping")]
+    fn ping() -> &'static str {
+        "pong"
+    }
+
+    async fn client(config: CorsConfig) -> Client {
+        let rocket = rocket::build()
+            .attach(create_cors(config).unwrap())
+            .mount("/", routes![ping]);
+        Client::untracked(rocket).await.unwrap()
+    }
+
+    fn custom_config() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: vec!["https://play.example.com".to_string()],
+            allowed_methods: vec![Method::Get],
+            allowed_headers: vec!["X-Game-Token".to_string()],
+            allow_credentials: false,
+            max_age: Some(Duration::from_secs(600)),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn custom_config_allows_its_origin() {
+        let client = client(custom_config()).await;
+        let response = client
+            .get("/ping")
+            .header(Header::new("Origin", "https://play.example.com"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+        let headers = response.headers();
+        assert_eq!(
+            headers.get_one("Access-Control-Allow-Origin"),
+            Some("https://play.example.com")
+        );
+        assert_eq!(headers.get_one("Access-Control-Allow-Credentials"), None);
+    }
+
+    #[rocket::async_test]
+    async fn custom_config_rejects_other_origins() {
+        let client = client(custom_config()).await;
+        let response = client
+            .get("/ping")
+            .header(Header::new("Origin", "http://localhost:5173"))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Forbidden);
+        assert_eq!(
+            response.headers().get_one("Access-Control-Allow-Origin"),
+            None
+        );
+    }
+
+    #[rocket::async_test]
+    async fn preflight_uses_custom_headers_and_max_age() {
+        let client = client(custom_config()).await;
+        let response = client
+            .options("/ping")
+            .header(Header::new("Origin", "https://play.example.com"))
+            .header(Header::new("Access-Control-Request-Method", "GET"))
+            .header(Header::new(
+                "Access-Control-Request-Headers",
+                "X-Game-Token",
+            ))
+            .dispatch()
+            .await;
+
+        let headers = response.headers();
+        assert_eq!(headers.get_one("Access-Control-Max-Age"), Some("600"));
+        assert_eq!(
+            headers
+                .get_one("Access-Control-Allow-Headers")
+                .map(str::to_lowercase),
+            Some("x-game-token".to_string())
+        );
+
+        let response = client
+            .options("/ping")
+            .header(Header::new("Origin", "https://play.example.com"))
+            .header(Header::new("Access-Control-Request-Method", "POST"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+
+    #[test]
+    fn invalid_origin_is_an_error() {
+        let config = CorsConfig {
+            allowed_origins: vec!["not a url".to_string()],
+            ..Default::default()
+        };
+        assert!(create_cors(config).is_err());
+    }
 }
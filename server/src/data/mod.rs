@@ -1,4 +1,15 @@
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RevealedState {
     Hidden,
     Marked,
@@ -13,12 +24,64 @@ pub struct Cell {
     pub revealed: RevealedState,
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    pub started_at: Option<Instant>,
+    pub finished_at: Option<Instant>,
+    /// When the current pause began, `None` while not paused
+    pub paused_at: Option<Instant>,
+    /// Pauses after the first move, which the timer leaves out
+    pub paused_for: Duration,
+    pub moves: u64,
+    pub useful_clicks: usize,
+    pub reveals: usize,
+    pub flags: usize,
+    pub hints: usize,
+}
+
+/// Player-visible state of a `Field` before an action, used to undo it. The bomb layout
+/// is not included since actions never change it after the first reveal.
+#[derive(Debug)]
+pub struct FieldSnapshot {
+    pub revealed_states: Vec<RevealedState>,
+    pub revealed: usize,
+    pub finished: bool,
+    pub won: bool,
+    /// Counters and timestamps, restored so an undone action no longer counts
+    pub stats: Stats,
+}
+
 #[derive(Debug)]
 pub struct Field {
     pub width: usize,
     pub height: usize,
     pub bombs: usize,
+    pub min_opening_size: usize,
+    pub efficiency_stats: bool,
+    pub enable_question_marks: bool,
+    pub auto_flag_on_win: bool,
+    pub public: bool,
+    pub max_players: usize,
+    pub chord_requires_exact_flags: bool,
+    pub no_guess: bool,
+    pub flags_while_paused: bool,
+    pub time_limit_secs: Option<u64>,
+    /// Board code the game was loaded from, kept so a restart replays the same puzzle
+    pub code: Option<String>,
+    pub max_cascade_radius: Option<usize>,
+    pub seed: u64,
+    pub rng: ChaCha8Rng,
     pub revealed: usize,
     pub finished: bool,
+    /// Set together with `finished` when the last safe cell was revealed
+    pub won: bool,
+    /// Reveals and chords are blocked and the timer is stopped
+    pub paused: bool,
+    /// Bumped by every change players can see, so reconnecting clients can tell what
+    /// they missed
+    pub revision: u64,
     pub cells: Vec<Cell>,
+    pub stats: Stats,
+    /// Time source for the game timer and expiry
+    pub clock: Arc<dyn Clock>,
 }
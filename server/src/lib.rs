@@ -1,6 +1,15 @@
+pub mod action_log;
+pub mod app;
+pub mod auth;
 pub mod cleanup;
+pub mod clock;
+pub mod connections;
 pub mod cors;
 pub mod data;
+pub mod logging;
 pub mod logic;
+pub mod metrics;
+pub mod persistence;
+pub mod presets;
 pub mod rate_limit;
 pub mod routes;
@@ -0,0 +1,180 @@
+use std::{env, fmt};
+
+use serde_json::{Map, Value};
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+    warn,
+};
+use tracing_subscriber::{
+    EnvFilter,
+    fmt::{
+        FmtContext, FormatEvent, FormatFields,
+        format::Writer,
+        time::{FormatTime, SystemTime},
+    },
+    registry::LookupSpan,
+};
+
+/// Installs the global subscriber.
+///
+/// - `LOG_FORMAT`: `text` (default) or `json`, one object per line with `timestamp`,
+///   `level`, `target`, `spans` and the event's `fields`
+/// - `RUST_LOG`, falling back to `LOG_LEVEL`: filter directives (default `info`)
+pub fn init_logging() {
+    let (filter, invalid) = match env::var("RUST_LOG").or_else(|_| env::var("LOG_LEVEL")) {
+        Ok(directives) => match EnvFilter::try_new(&directives) {
+            Ok(filter) => (filter, None),
+            Err(_) => (EnvFilter::new("info"), Some(directives)),
+        },
+        Err(_) => (EnvFilter::new("info"), None),
+    };
+
+    let json = env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    if json {
+        builder.event_format(JsonFormat).init();
+    } else {
+        builder.init();
+    }
+
+    if let Some(directives) = invalid {
+        warn!("Ignoring invalid log filter '{}', using info", directives);
+    }
+}
+
+/// Formats each event as a single JSON object. Implemented here because the `json`
+/// feature of `tracing-subscriber` pulls in another serializer we don't otherwise need.
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+
+        let mut fields = JsonFields(Map::new());
+        event.record(&mut fields);
+
+        let spans: Vec<Value> = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| Value::from(span.name()))
+            .collect();
+
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert("timestamp".into(), timestamp.into());
+        line.insert("level".into(), metadata.level().as_str().into());
+        line.insert("target".into(), metadata.target().into());
+        if !spans.is_empty() {
+            line.insert("spans".into(), spans.into());
+        }
+        line.insert("fields".into(), fields.0.into());
+
+        let json = serde_json::to_string(&line).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{}", json)
+    }
+}
+
+/// Collects event fields, keeping numbers and booleans typed
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{info, info_span};
+
+    use super::*;
+
+    /// Collects everything the subscriber writes so the test can read it back
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_format_writes_one_object_per_event() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(JsonFormat)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            info_span!("game").in_scope(|| {
+                info!(moves = 3_u64, won = true, "Game finished");
+            });
+            warn!("Outside any span");
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+
+        let first = &lines[0];
+        assert_eq!(first["level"], "INFO");
+        assert_eq!(first["target"], module_path!());
+        assert_eq!(first["spans"], serde_json::json!(["game"]));
+        assert_eq!(first["fields"]["message"], "Game finished");
+        assert_eq!(first["fields"]["moves"], 3);
+        assert_eq!(first["fields"]["won"], true);
+        assert!(first["timestamp"].is_string());
+
+        assert_eq!(lines[1]["level"], "WARN");
+        assert!(lines[1].get("spans").is_none());
+    }
+}
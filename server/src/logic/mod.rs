@@ -1,81 +1,309 @@
-use std::{cmp::min, collections::HashMap, sync::Arc, time::Instant};
+use std::{
+    cell::OnceCell,
+    cmp::min,
+    collections::{HashMap, HashSet, VecDeque},
+    env,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
 
 use dashmap::DashMap;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use rocket::futures::{SinkExt, future::join_all, stream::SplitSink};
 use rocket_ws::{Message, stream::DuplexStream};
-use tokio::sync::Mutex;
-use tracing::{debug, info, instrument, warn};
+use serde::Serialize;
+use tokio::{sync::Mutex, task::JoinHandle, time};
+use tracing::{debug, error, info, instrument, warn};
 use uuid::Uuid;
 
 use minesweeper_common::{
-    models::{GameParams, Pos},
-    protocol::{CellUpdate, ServerMessage},
+    board_code::BoardCode,
+    mask::{MaskState, RevealedMask},
+    models::{BoardLimits, BoardSolution, GameParams, GameStatus, GameSummary, Pos},
+    protocol::{CellUpdate, ClientMessage, EfficiencyStats, MAX_CHAT_LENGTH, ServerMessage},
 };
 
-use crate::data::{Cell, Field, RevealedState};
+use crate::{
+    clock::{Clock, system_clock},
+    data::{Cell, Field, FieldSnapshot, RevealedState, Stats},
+    metrics::Metrics,
+    presets::{AllowedBoardSizes, MaxBoardCells},
+};
 
 pub type Games = Arc<DashMap<String, Arc<Mutex<Game>>>>;
 
-pub struct Game {
-    field: Field,
-    streams: HashMap<Uuid, SplitSink<DuplexStream, Message>>,
-    last_activity: Instant,
+/// Cap on the number of games held at once (`MAX_ACTIVE_GAMES`, default unlimited).
+/// Slots free up as the cleanup task removes idle games.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GameLimit(pub Option<usize>);
+
+impl GameLimit {
+    pub fn from_env() -> Self {
+        let max = env::var("MAX_ACTIVE_GAMES")
+            .ok()
+            .and_then(|value| value.parse().ok());
+
+        if let Some(max) = max {
+            info!("Limiting server to {} active games", max);
+        }
+
+        Self(max)
+    }
+
+    /// Whether another game may be created while `active` games exist.
+    pub fn allows(&self, active: usize) -> bool {
+        self.0.is_none_or(|max| active < max)
+    }
 }
 
-fn validate_params(params: &mut GameParams) {
-    params.bombs = min(params.bombs, params.width * params.height)
+/// Maximum number of boards generated on the first reveal while trying to satisfy
+/// `min_opening_size`. If none qualifies, the board with the largest opening is kept.
+const MAX_OPENING_ATTEMPTS: usize = 100;
+
+/// Number of actions that can be undone per game.
+const MAX_UNDO_HISTORY: usize = 50;
+
+/// Players a public game can have before `POST /join` stops handing it out.
+pub const LOBBY_MAX_PLAYERS: usize = 8;
+
+/// Encoding of the frames sent to a connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// JSON in text frames
+    #[default]
+    Text,
+    /// MessagePack in binary frames, see `minesweeper_common::protocol::to_bytes`
+    #[cfg(feature = "binary")]
+    Binary,
 }
 
-fn generate_bombs(params: &GameParams) -> Vec<bool> {
-    let mut bombs = Vec::new();
-    let mut rng = rand::rng();
+/// Per-connection options chosen by the client when connecting.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionOptions {
+    /// Include breadth-first cascade depths in reveal updates.
+    pub cascade_depth: bool,
+    /// Read-only connection: receives broadcasts but may not change the board.
+    pub spectator: bool,
+    /// Player token from an earlier connection to resume as that player.
+    pub token: Option<Uuid>,
+    /// Last board revision the resumed player saw, to be sent only the changes since.
+    pub revision: Option<u64>,
+    /// Encoding of the frames sent to this connection.
+    pub format: FrameFormat,
+}
 
-    let mut bombs_left = params.bombs;
-    let length = params.width * params.height;
-    for cells_left in (1..=length).rev() {
-        let value = rng.random_ratio(bombs_left as u32, cells_left as u32);
-        bombs.push(value);
-        if value {
-            bombs_left -= 1;
+/// Rectangular region of the board a connection is rendering, inclusive on both corners.
+#[derive(Debug, Clone, Copy)]
+struct Viewport {
+    top_left: Pos,
+    bottom_right: Pos,
+}
+
+impl Viewport {
+    fn contains(&self, pos: &Pos) -> bool {
+        (self.top_left.x..=self.bottom_right.x).contains(&pos.x)
+            && (self.top_left.y..=self.bottom_right.y).contains(&pos.y)
+    }
+
+    /// Restricts an `Update` to cells inside the viewport. Returns `None` if nothing in it
+    /// is relevant to this connection.
+    fn scope(&self, message: &ServerMessage) -> Option<ServerMessage> {
+        let ServerMessage::Update {
+            updates,
+            won,
+            lost,
+            elapsed_secs,
+            flags_placed,
+            moves,
+            revision,
+        } = message
+        else {
+            return None;
+        };
+
+        let updates: Vec<CellUpdate> = updates
+            .iter()
+            .filter(|update| self.contains(&update.pos))
+            .copied()
+            .collect();
+
+        if updates.is_empty() && !won && !lost {
+            return None;
         }
+
+        Some(ServerMessage::Update {
+            updates,
+            won: *won,
+            lost: *lost,
+            elapsed_secs: *elapsed_secs,
+            flags_placed: *flags_placed,
+            moves: *moves,
+            revision: *revision,
+        })
     }
+}
 
-    bombs
+/// Cell changes of the most recent revisions, used to catch up reconnecting players
+/// without resending the whole board. Holds at most as many cell updates as the board
+/// has cells, beyond which a full `Init` is no larger.
+#[derive(Debug, Default)]
+struct UpdateLog {
+    /// Revision each batch of updates produced, oldest first
+    entries: VecDeque<(u64, Vec<CellUpdate>)>,
+    cells: usize,
 }
 
-fn count_adjacent_bombs(bombs: &[bool], index: usize, params: &GameParams) -> u8 {
-    let x = index % params.width;
-    let y = index / params.width;
-    let mut count = 0;
+impl UpdateLog {
+    fn push(&mut self, revision: u64, updates: &[CellUpdate], capacity: usize) {
+        self.entries.push_back((revision, updates.to_vec()));
+        self.cells += updates.len();
+        while self.cells > capacity {
+            let Some((_, dropped)) = self.entries.pop_front() else {
+                break;
+            };
+            self.cells -= dropped.len();
+        }
+    }
 
-    for dy in -1..=1 {
-        for dx in -1..=1 {
-            if dx == 0 && dy == 0 {
-                continue;
-            }
+    /// Every update after `revision` up to `current`, in order. `None` if some of them
+    /// are no longer logged or `revision` is unknown.
+    fn since(&self, revision: u64, current: u64) -> Option<Vec<CellUpdate>> {
+        if revision == current {
+            return Some(Vec::new());
+        }
+        if revision > current {
+            return None;
+        }
+
+        let (first, _) = self.entries.front()?;
+        if *first > revision + 1 {
+            return None;
+        }
+        Some(
+            self.entries
+                .iter()
+                .filter(|(logged, _)| *logged > revision)
+                .flat_map(|(_, updates)| updates.iter().copied())
+                .map(|update| CellUpdate {
+                    depth: None,
+                    ..update
+                })
+                .collect(),
+        )
+    }
 
-            let new_x = x as i32 + dx;
-            let new_y = y as i32 + dy;
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.cells = 0;
+    }
+}
 
-            if new_x >= 0
-                && new_x < params.width as i32
-                && new_y >= 0
-                && new_y < params.height as i32
-            {
-                let adj_index = (new_x as usize) + (new_y as usize) * params.width;
-                if adj_index < bombs.len() && bombs[adj_index] {
-                    count += 1;
-                }
+struct Connection {
+    sink: SplitSink<DuplexStream, Message>,
+    /// Stable identity of the player, kept across reconnects
+    token: Uuid,
+    options: ConnectionOptions,
+    viewport: Option<Viewport>,
+}
+
+pub struct Game {
+    field: Field,
+    streams: HashMap<Uuid, Connection>,
+    /// Player tokens handed out so far
+    players: HashSet<Uuid>,
+    last_activity: Instant,
+    expiry_warned: bool,
+    history: VecDeque<FieldSnapshot>,
+    updates: UpdateLog,
+    metrics: Arc<Metrics>,
+    /// The game as stored in `Games`, for tasks that must not keep it alive
+    this: Weak<Mutex<Game>>,
+    /// Task ending the game once `time_limit_secs` is up
+    time_limit: Option<JoinHandle<()>>,
+}
+
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.cancel_time_limit();
+    }
+}
+
+/// What the cleanup task should do with a game on its current pass.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CleanupAction {
+    Keep,
+    Warn { seconds_remaining: u64 },
+    Remove,
+}
+
+/// Farthest BFS distance a reveal cascade spreads from the clicked cell
+/// (`MAX_CASCADE_RADIUS`, default unlimited).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaxCascadeRadius(pub Option<usize>);
+
+impl MaxCascadeRadius {
+    /// Reads `MAX_CASCADE_RADIUS` once at startup, warning about an invalid value.
+    pub fn from_env() -> Self {
+        let Ok(value) = env::var("MAX_CASCADE_RADIUS") else {
+            return Self(None);
+        };
+        match value.trim().parse::<usize>() {
+            Ok(radius) => {
+                info!("Limiting reveal cascades to a radius of {}", radius);
+                Self(Some(radius))
+            }
+            Err(_) => {
+                warn!("Ignoring invalid MAX_CASCADE_RADIUS '{}'", value);
+                Self(None)
             }
         }
     }
+}
+
+/// Clamps parameters that slipped past `GameParams::validate`. At least one cell is
+/// always left safe, otherwise the board could neither be won nor lost sanely.
+fn validate_params(params: &mut GameParams) {
+    let cells = params.width * params.height;
+    params.bombs = min(params.bombs, cells.saturating_sub(1));
+    params.min_opening_size = min(params.min_opening_size, cells - params.bombs);
+}
+
+/// Picks a random seed for games created without one. Seeds are kept below 2^53 so they
+/// survive a round trip through JavaScript numbers.
+fn random_seed() -> u64 {
+    rand::rng().random_range(0..1 << 53)
+}
+
+/// Places exactly `params.bombs` bombs using the game's seeded RNG. ChaCha8 is used rather
+/// than `StdRng` because its output is stable across `rand` releases, so a seed keeps
+/// producing the same board.
+///
+/// Bomb cells are picked with a partial Fisher–Yates shuffle over all cell indices, which
+/// stays exact for boards of any size since nothing is narrowed to `u32`.
+fn generate_bombs(params: &GameParams, rng: &mut ChaCha8Rng) -> Vec<bool> {
+    let length = params.width * params.height;
+    let mut indices: Vec<usize> = (0..length).collect();
+    let mut bombs = vec![false; length];
+
+    for i in 0..params.bombs.min(length) {
+        let j = rng.random_range(i..length);
+        indices.swap(i, j);
+        bombs[indices[i]] = true;
+    }
+
+    bombs
+}
 
-    count
+fn count_adjacent_bombs(bombs: &[bool], index: usize, params: &GameParams) -> u8 {
+    Pos::from_index(index, params.width)
+        .neighbors(params.width, params.height)
+        .filter(|neighbor| bombs[neighbor.index(params.width)])
+        .count() as u8
 }
 
-fn generate_cells(params: &GameParams) -> Vec<Cell> {
-    let bombs = generate_bombs(params);
+fn generate_cells(params: &GameParams, rng: &mut ChaCha8Rng) -> Vec<Cell> {
+    let bombs = generate_bombs(params, rng);
     let cells = bombs.iter().enumerate().map(|(i, bomb)| Cell {
         bomb: *bomb,
         adjacent: count_adjacent_bombs(&bombs, i, params),
@@ -85,6 +313,31 @@ fn generate_cells(params: &GameParams) -> Vec<Cell> {
     cells.collect()
 }
 
+/// Builds the cells of a predefined board, recomputing adjacency from its bombs
+fn cells_from_code(board: &BoardCode) -> Vec<Cell> {
+    (0..board.width * board.height)
+        .map(|i| Cell {
+            bomb: board.bombs[i],
+            adjacent: board.adjacent_bombs(Pos::from_index(i, board.width)),
+            revealed: match board.states[i] {
+                MaskState::Hidden => RevealedState::Hidden,
+                MaskState::Revealed => RevealedState::Revealed,
+                MaskState::Flagged => RevealedState::Flagged,
+                MaskState::Marked => RevealedState::Marked,
+            },
+        })
+        .collect()
+}
+
+fn mask_state(revealed: RevealedState) -> MaskState {
+    match revealed {
+        RevealedState::Hidden => MaskState::Hidden,
+        RevealedState::Marked => MaskState::Marked,
+        RevealedState::Flagged => MaskState::Flagged,
+        RevealedState::Revealed => MaskState::Revealed,
+    }
+}
+
 impl From<&Cell> for minesweeper_common::models::Cell {
     fn from(value: &Cell) -> Self {
         match value.revealed {
@@ -99,18 +352,167 @@ impl From<&Cell> for minesweeper_common::models::Cell {
     }
 }
 
-async fn send(stream: &mut SplitSink<DuplexStream, Message>, message: &ServerMessage) {
-    if let Ok(text) = serde_json::to_string(message) {
-        let _ = stream.send(Message::Text(text)).await;
+/// Serializes a message into a frame of the given format. Broadcasts render once per
+/// format and clone the frame for each recipient instead of serializing per stream. A
+/// message that can't be serialized is logged and counted in `metrics`, then dropped.
+fn render<M: Serialize>(
+    message: &M,
+    kind: &str,
+    format: FrameFormat,
+    metrics: &Metrics,
+) -> Option<Message> {
+    let frame = match format {
+        FrameFormat::Text => serde_json::to_string(message)
+            .map(Message::Text)
+            .map_err(|e| e.to_string()),
+        #[cfg(feature = "binary")]
+        FrameFormat::Binary => minesweeper_common::protocol::to_bytes(message)
+            .map(Message::Binary)
+            .map_err(|e| e.to_string()),
+    };
+
+    match frame {
+        Ok(frame) => Some(frame),
+        Err(e) => {
+            error!("Failed to serialize {} message: {}", kind, e);
+            metrics.record_serialization_failure();
+            None
+        }
+    }
+}
+
+fn render_server_message(
+    message: &ServerMessage,
+    format: FrameFormat,
+    metrics: &Metrics,
+) -> Option<Message> {
+    render(message, message.kind(), format, metrics)
+}
+
+/// One message rendered lazily for each frame format, so a broadcast serializes it at
+/// most once per format in use.
+struct Frames<'a> {
+    message: &'a ServerMessage,
+    metrics: &'a Metrics,
+    text: OnceCell<Option<Message>>,
+    #[cfg(feature = "binary")]
+    binary: OnceCell<Option<Message>>,
+}
+
+impl<'a> Frames<'a> {
+    fn new(message: &'a ServerMessage, metrics: &'a Metrics) -> Self {
+        Self {
+            message,
+            metrics,
+            text: OnceCell::new(),
+            #[cfg(feature = "binary")]
+            binary: OnceCell::new(),
+        }
+    }
+
+    fn get(&self, format: FrameFormat) -> Option<Message> {
+        let frame = match format {
+            FrameFormat::Text => &self.text,
+            #[cfg(feature = "binary")]
+            FrameFormat::Binary => &self.binary,
+        };
+        frame
+            .get_or_init(|| render_server_message(self.message, format, self.metrics))
+            .clone()
+    }
+}
+
+async fn send_frame(stream: &mut SplitSink<DuplexStream, Message>, frame: Message) {
+    let _ = stream.send(frame).await;
+}
+
+async fn send(
+    metrics: &Metrics,
+    stream: &mut SplitSink<DuplexStream, Message>,
+    format: FrameFormat,
+    message: &ServerMessage,
+) {
+    if let Some(frame) = render_server_message(message, format, metrics) {
+        send_frame(stream, frame).await;
     }
 }
+
 async fn broadcast(
-    streams: &mut HashMap<Uuid, SplitSink<DuplexStream, Message>>,
+    metrics: &Metrics,
+    streams: &mut HashMap<Uuid, Connection>,
     message: &ServerMessage,
 ) {
+    let frames = Frames::new(message, metrics);
+
+    let futures: Vec<_> = streams
+        .values_mut()
+        .filter_map(|connection| {
+            let frame = frames.get(connection.options.format)?;
+            Some(send_frame(&mut connection.sink, frame))
+        })
+        .collect();
+
+    join_all(futures).await;
+}
+
+/// Broadcasts an `Update`, stripping cascade depths for connections that did not opt in
+/// and scoping it to each connection's viewport.
+async fn broadcast_update(
+    metrics: &Metrics,
+    streams: &mut HashMap<Uuid, Connection>,
+    detailed: ServerMessage,
+) {
+    let plain = match &detailed {
+        ServerMessage::Update {
+            updates,
+            won,
+            lost,
+            elapsed_secs,
+            flags_placed,
+            moves,
+            revision,
+        } => ServerMessage::Update {
+            updates: updates
+                .iter()
+                .map(|update| CellUpdate {
+                    depth: None,
+                    ..*update
+                })
+                .collect(),
+            won: *won,
+            lost: *lost,
+            elapsed_secs: *elapsed_secs,
+            flags_placed: *flags_placed,
+            moves: *moves,
+            revision: *revision,
+        },
+        other => other.clone(),
+    };
+    let detailed_frames = Frames::new(&detailed, metrics);
+    let plain_frames = Frames::new(&plain, metrics);
+
     let futures: Vec<_> = streams
-        .iter_mut()
-        .map(|(_, stream)| send(stream, message))
+        .values_mut()
+        .map(|connection| {
+            let (message, frames) = if connection.options.cascade_depth {
+                (&detailed, &detailed_frames)
+            } else {
+                (&plain, &plain_frames)
+            };
+            let format = connection.options.format;
+            // Viewport-scoped updates differ per connection and are rendered individually
+            let frame = match connection.viewport {
+                None => frames.get(format),
+                Some(viewport) => viewport
+                    .scope(message)
+                    .and_then(|scoped| render_server_message(&scoped, format, metrics)),
+            };
+            async move {
+                if let Some(frame) = frame {
+                    send_frame(&mut connection.sink, frame).await;
+                }
+            }
+        })
         .collect();
 
     join_all(futures).await;
@@ -118,18 +520,53 @@ async fn broadcast(
 
 impl Field {
     fn new(mut params: GameParams) -> Self {
+        let board = params.resolve_code().unwrap_or_else(|error| {
+            warn!("Ignoring invalid board code: {}", error);
+            params.code = None;
+            None
+        });
         validate_params(&mut params);
+        let seed = params.seed.unwrap_or_else(random_seed);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let cells = match &board {
+            Some(board) => cells_from_code(board),
+            None => generate_cells(&params, &mut rng),
+        };
+        let revealed = cells
+            .iter()
+            .filter(|cell| cell.revealed == RevealedState::Revealed)
+            .count();
         Self {
             width: params.width,
             height: params.height,
             bombs: params.bombs,
-            revealed: 0,
+            min_opening_size: params.min_opening_size,
+            efficiency_stats: params.efficiency_stats,
+            enable_question_marks: params.enable_question_marks,
+            auto_flag_on_win: params.auto_flag_on_win,
+            public: params.public,
+            max_players: params.max_players,
+            chord_requires_exact_flags: params.chord_requires_exact_flags,
+            no_guess: params.no_guess,
+            flags_while_paused: params.flags_while_paused,
+            time_limit_secs: params.time_limit_secs,
+            code: params.code,
+            // Set by `Game::new` from the server-wide `MaxCascadeRadius`
+            max_cascade_radius: None,
+            seed,
+            rng,
+            revealed,
             finished: false,
-            cells: generate_cells(&params),
+            won: false,
+            paused: false,
+            revision: 0,
+            cells,
+            stats: Stats::default(),
+            clock: system_clock(),
         }
     }
 
-    fn init_message(&self) -> ServerMessage {
+    fn init_message(&self, your_token: Option<Uuid>) -> ServerMessage {
         ServerMessage::Init {
             width: self.width,
             height: self.height,
@@ -142,6 +579,58 @@ impl Field {
                 .chunks(self.width)
                 .map(|chunk| chunk.to_vec())
                 .collect(),
+            elapsed_secs: self.elapsed().as_secs(),
+            flags_placed: self.flags_placed(),
+            moves: self.stats.moves,
+            your_token,
+            revision: self.revision,
+            paused: self.paused,
+            enable_question_marks: self.enable_question_marks,
+        }
+    }
+
+    fn snapshot(&self) -> FieldSnapshot {
+        FieldSnapshot {
+            revealed_states: self.cells.iter().map(|cell| cell.revealed).collect(),
+            revealed: self.revealed,
+            finished: self.finished,
+            won: self.won,
+            stats: self.stats.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: FieldSnapshot) {
+        for (cell, revealed) in self.cells.iter_mut().zip(snapshot.revealed_states) {
+            cell.revealed = revealed;
+        }
+        self.revealed = snapshot.revealed;
+        self.finished = snapshot.finished;
+        self.won = snapshot.won;
+        // The timer isn't rewound, only a finished game's stop time and the counters
+        self.stats = Stats {
+            started_at: self.stats.started_at,
+            paused_at: self.stats.paused_at,
+            paused_for: self.stats.paused_for,
+            ..snapshot.stats
+        };
+    }
+
+    fn flags_placed(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|cell| cell.revealed == RevealedState::Flagged)
+            .count()
+    }
+
+    fn update_message(&self, updates: Vec<CellUpdate>, won: bool, lost: bool) -> ServerMessage {
+        ServerMessage::Update {
+            updates,
+            won,
+            lost,
+            elapsed_secs: self.elapsed().as_secs(),
+            flags_placed: self.flags_placed(),
+            moves: self.stats.moves,
+            revision: self.revision,
         }
     }
 
@@ -149,32 +638,183 @@ impl Field {
         self.width * self.height == self.bombs + self.revealed
     }
 
+    fn record_move(&mut self) {
+        let now = self.clock.now();
+        self.stats.started_at.get_or_insert(now);
+        self.stats.moves += 1;
+    }
+
+    fn finish(&mut self, won: bool) {
+        self.finished = true;
+        self.won = won;
+        self.stats.finished_at = Some(self.clock.now());
+    }
+
+    /// Time since the first move, stopped while paused and when the game finished.
+    /// Restarting creates a new `Field`, which resets it.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.elapsed_until(self.stats.finished_at.or(self.stats.paused_at))
+    }
+
+    /// Whether `time_limit_secs` has run out, counting time since the game finished as
+    /// well since undoing the final move would resume the timer.
+    fn time_limit_expired(&self) -> bool {
+        self.time_limit_secs.is_some_and(|limit| {
+            self.elapsed_until(self.stats.paused_at) >= Duration::from_secs(limit)
+        })
+    }
+
+    fn elapsed_until(&self, stopped_at: Option<Instant>) -> Duration {
+        match self.stats.started_at {
+            Some(started_at) => {
+                let until = stopped_at.unwrap_or_else(|| self.clock.now());
+                // A flag placed during a pause starts the timer after the pause began
+                until
+                    .max(started_at)
+                    .duration_since(started_at)
+                    .saturating_sub(self.stats.paused_for)
+            }
+            None => Duration::ZERO,
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        let now = self.clock.now();
+        self.paused = paused;
+        if paused {
+            self.stats.paused_at = Some(now);
+        } else if let Some(paused_at) = self.stats.paused_at.take()
+            && let Some(started_at) = self.stats.started_at
+        {
+            self.stats.paused_for += now.duration_since(paused_at.max(started_at));
+        }
+    }
+
+    fn summary_message(&self, won: bool) -> ServerMessage {
+        let elapsed_ms = self.elapsed().as_millis() as u64;
+
+        let safe_cells = self.width * self.height - self.bombs;
+        let board_cleared_pct = if safe_cells == 0 {
+            100.0
+        } else {
+            self.revealed as f64 * 100.0 / safe_cells as f64
+        };
+
+        let efficiency = self.efficiency_stats.then(|| {
+            let three_bv = self.three_bv();
+            EfficiencyStats {
+                three_bv,
+                useful_clicks: self.stats.useful_clicks,
+                wasted_clicks: self.stats.moves as usize - self.stats.useful_clicks,
+                three_bv_per_second: if elapsed_ms == 0 {
+                    0.0
+                } else {
+                    three_bv as f64 * 1000.0 / elapsed_ms as f64
+                },
+            }
+        });
+
+        ServerMessage::GameSummary {
+            won,
+            elapsed_ms,
+            moves: self.stats.moves,
+            reveals: self.stats.reveals,
+            flags: self.stats.flags,
+            board_cleared_pct,
+            efficiency,
+        }
+    }
+
+    /// Minimum number of clicks needed to clear the board: one per opening (connected
+    /// zero cells plus their numbered border) and one per numbered cell outside openings.
+    fn three_bv(&self) -> usize {
+        let mut visited = vec![false; self.cells.len()];
+        let mut clicks = 0;
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            if visited[index] || cell.bomb || cell.adjacent != 0 {
+                continue;
+            }
+
+            clicks += 1;
+            visited[index] = true;
+            let mut stack = vec![index];
+            while let Some(index) = stack.pop() {
+                let pos = Pos::from_index(index, self.width);
+                for neighbor in self.neighbors(pos) {
+                    let neighbor_index = neighbor.index(self.width);
+                    let neighbor_cell = &self.cells[neighbor_index];
+                    if visited[neighbor_index] || neighbor_cell.bomb {
+                        continue;
+                    }
+                    visited[neighbor_index] = true;
+                    if neighbor_cell.adjacent == 0 {
+                        stack.push(neighbor_index);
+                    }
+                }
+            }
+        }
+
+        clicks
+            + self
+                .cells
+                .iter()
+                .zip(&visited)
+                .filter(|(cell, visited)| !cell.bomb && !**visited)
+                .count()
+    }
+
     fn reveal_bombs(&mut self, updates: &mut Vec<CellUpdate>) {
         for y in 0..self.height {
             for x in 0..self.width {
                 let pos = Pos { x, y };
 
-                if let Some(cell) = self.cells.get_mut(pos.x + pos.y * self.width)
+                if let Some(cell) = self.cells.get_mut(pos.index(self.width))
                     && cell.bomb
                 {
                     cell.revealed = RevealedState::Revealed;
                     updates.push(CellUpdate {
                         pos,
                         value: (&*cell).into(),
+                        depth: None,
                     });
                 }
             }
         }
     }
 
-    fn reveal_recursive(&mut self, pos: Pos, updates: &mut Vec<CellUpdate>) {
-        if !self.validate_pos(&pos) {
-            return;
+    /// Flags every bomb that is not flagged yet, used to complete the board on a win.
+    fn flag_bombs(&mut self, updates: &mut Vec<CellUpdate>) {
+        for (index, cell) in self.cells.iter_mut().enumerate() {
+            if cell.bomb && cell.revealed != RevealedState::Flagged {
+                cell.revealed = RevealedState::Flagged;
+                updates.push(CellUpdate {
+                    pos: Pos::from_index(index, self.width),
+                    value: (&*cell).into(),
+                    depth: None,
+                });
+            }
         }
+    }
+
+    /// Flood-reveals from `start` using an explicit work queue, tagging each update with
+    /// its distance from `start` so clients can animate the expansion. Numbered cells are
+    /// revealed but not expanded, and every newly revealed cell yields one update.
+    ///
+    /// Already revealed cells are skipped and never re-emitted. Since actions are applied
+    /// one at a time under the game's lock, overlapping reveals from different players
+    /// produce disjoint updates: each cell appears in exactly one `Update`.
+    fn reveal_flood(&mut self, start: Pos, updates: &mut Vec<CellUpdate>) {
+        let mut queue = VecDeque::from([(start, 0)]);
+
+        while let Some((pos, depth)) = queue.pop_front() {
+            if !self.validate_pos(&pos) {
+                continue;
+            }
 
-        if let Some(cell) = self.cells.get_mut(pos.x + pos.y * self.width) {
+            let cell = &mut self.cells[pos.index(self.width)];
             if cell.revealed == RevealedState::Revealed {
-                return;
+                continue;
             }
 
             cell.revealed = RevealedState::Revealed;
@@ -182,77 +822,373 @@ impl Field {
             updates.push(CellUpdate {
                 pos,
                 value: (&*cell).into(),
+                depth: Some(depth),
             });
 
-            if cell.adjacent != 0 {
-                return;
+            if cell.adjacent != 0 || !self.can_expand(depth) {
+                continue;
             }
 
-            for dy in -1..=1 {
-                for dx in -1..=1 {
-                    if dx == 0 && dy == 0 {
-                        continue;
-                    }
+            queue.extend(
+                self.neighbors(pos)
+                    .into_iter()
+                    .map(|neighbor| (neighbor, depth + 1)),
+            );
+        }
+    }
 
-                    self.reveal_recursive(
-                        Pos {
-                            x: (pos.x as i32 + dx) as usize,
-                            y: (pos.y as i32 + dy) as usize,
-                        },
-                        updates,
-                    );
-                }
+    /// Whether a cascade may spread past a cell at `depth`. With `MAX_CASCADE_RADIUS` set,
+    /// cells at that distance are revealed but their neighbours stay hidden.
+    fn can_expand(&self, depth: usize) -> bool {
+        self.max_cascade_radius
+            .is_none_or(|max_radius| depth < max_radius)
+    }
+
+    fn params(&self) -> GameParams {
+        GameParams {
+            width: self.width,
+            height: self.height,
+            bombs: self.bombs,
+            min_opening_size: self.min_opening_size,
+            seed: Some(self.seed),
+            efficiency_stats: self.efficiency_stats,
+            enable_question_marks: self.enable_question_marks,
+            auto_flag_on_win: self.auto_flag_on_win,
+            public: self.public,
+            max_players: self.max_players,
+            chord_requires_exact_flags: self.chord_requires_exact_flags,
+            no_guess: self.no_guess,
+            flags_while_paused: self.flags_while_paused,
+            time_limit_secs: self.time_limit_secs,
+            code: self.code.clone(),
+        }
+    }
+
+    /// Bomb layout and visibility of the board, for sharing it as a puzzle
+    fn board_code(&self) -> BoardCode {
+        BoardCode {
+            width: self.width,
+            height: self.height,
+            bombs: self.cells.iter().map(|cell| cell.bomb).collect(),
+            states: self
+                .cells
+                .iter()
+                .map(|cell| mask_state(cell.revealed))
+                .collect(),
+        }
+    }
+
+    /// Bomb positions and adjacency counts of every cell, regardless of what has been
+    /// revealed
+    pub fn to_solution(&self) -> BoardSolution {
+        BoardSolution {
+            width: self.width,
+            height: self.height,
+            bombs: (0..self.cells.len())
+                .filter(|&index| self.cells[index].bomb)
+                .map(|index| Pos::from_index(index, self.width))
+                .collect(),
+            adjacent: self.cells.iter().map(|cell| cell.adjacent).collect(),
+        }
+    }
+
+    /// Counts the cells a reveal at `pos` would open on the current board.
+    fn opening_size(&self, pos: Pos) -> usize {
+        let mut visited = vec![false; self.cells.len()];
+        let mut queue = VecDeque::from([(pos, 0)]);
+        let mut size = 0;
+
+        while let Some((pos, depth)) = queue.pop_front() {
+            let index = pos.index(self.width);
+            if visited[index] || self.cells[index].bomb {
+                continue;
+            }
+            visited[index] = true;
+            size += 1;
+
+            if self.cells[index].adjacent != 0 || !self.can_expand(depth) {
+                continue;
+            }
+
+            queue.extend(
+                self.neighbors(pos)
+                    .into_iter()
+                    .map(|neighbor| (neighbor, depth + 1)),
+            );
+        }
+
+        size
+    }
+
+    /// Regenerates a random board until the first reveal at `pos` opens at least
+    /// `min_opening_size` cells, keeping the cell states players already set. Boards
+    /// loaded from a code are played as given.
+    fn ensure_opening(&mut self, pos: Pos) {
+        if self.min_opening_size == 0 || self.code.is_some() {
+            return;
+        }
+
+        let params = self.params();
+        let mut best = self.opening_size(pos);
+        let mut attempts = 0;
+
+        while best < self.min_opening_size && attempts < MAX_OPENING_ATTEMPTS {
+            attempts += 1;
+            let mut cells = generate_cells(&params, &mut self.rng);
+            // Flags and marks placed before the first reveal stay where they were
+            for (cell, previous) in cells.iter_mut().zip(&self.cells) {
+                cell.revealed = previous.revealed;
+            }
+            let previous = std::mem::replace(&mut self.cells, cells);
+            let size = self.opening_size(pos);
+            if size > best {
+                best = size;
+            } else {
+                self.cells = previous;
             }
         }
+
+        if best < self.min_opening_size {
+            warn!(
+                "Could not generate an opening of {} cells after {} attempts, using {}",
+                self.min_opening_size, attempts, best
+            );
+        } else if attempts > 0 {
+            debug!(
+                "Regenerated board {} times for an opening of {} cells",
+                attempts, best
+            );
+        }
+    }
+
+    fn cell(&self, pos: &Pos) -> &Cell {
+        &self.cells[pos.index(self.width)]
+    }
+
+    /// In-bounds positions surrounding `pos`.
+    fn neighbors(&self, pos: Pos) -> Vec<Pos> {
+        pos.neighbors(self.width, self.height).collect()
     }
 
     fn validate_pos(&self, pos: &Pos) -> bool {
         pos.x < self.width && pos.y < self.height
     }
+
+    /// Whether the single-cell rules prove some hidden cell safe or a mine from what the
+    /// players can see, like the client's `solver::next_moves`: a number whose flags
+    /// already match it, or whose hidden and flagged neighbours together match it. Flags
+    /// are taken at face value and `?` marks count as hidden. Before any number is
+    /// revealed there is no forced move.
+    fn has_forced_move(&self) -> bool {
+        self.cells.iter().enumerate().any(|(index, cell)| {
+            if cell.revealed != RevealedState::Revealed || cell.adjacent == 0 {
+                return false;
+            }
+
+            let (mut hidden, mut flagged) = (0, 0);
+            for neighbor in self.neighbors(Pos::from_index(index, self.width)) {
+                match self.cell(&neighbor).revealed {
+                    RevealedState::Hidden | RevealedState::Marked => hidden += 1,
+                    RevealedState::Flagged => flagged += 1,
+                    RevealedState::Revealed => {}
+                }
+            }
+            let adjacent = cell.adjacent as usize;
+            hidden > 0 && (flagged == adjacent || flagged + hidden == adjacent)
+        })
+    }
+
+    /// Whether `flagged` flags around a number showing `adjacent` allow a chord, per
+    /// `chord_requires_exact_flags`.
+    fn chord_allowed(&self, flagged: usize, adjacent: u8) -> bool {
+        if self.chord_requires_exact_flags {
+            flagged == adjacent as usize
+        } else {
+            flagged >= adjacent as usize
+        }
+    }
 }
 
 impl Game {
-    #[instrument(level = "trace")]
-    pub fn new(params: GameParams) -> Self {
+    #[instrument(level = "trace", skip(metrics))]
+    pub fn new(
+        params: GameParams,
+        metrics: Arc<Metrics>,
+        max_cascade_radius: MaxCascadeRadius,
+    ) -> Self {
         info!(
             "Creating new game: {}x{} with {} bombs",
             params.width, params.height, params.bombs
         );
+        let mut field = Field::new(params);
+        field.max_cascade_radius = max_cascade_radius.0;
+        Self::from_field(field, metrics)
+    }
+
+    /// A new game whose timer and expiry follow `clock` instead of the system clock
+    pub fn with_clock(params: GameParams, metrics: Arc<Metrics>, clock: Arc<dyn Clock>) -> Self {
+        let mut field = Field::new(params);
+        field.clock = clock;
+        Self::from_field(field, metrics)
+    }
+
+    /// Wraps an existing board, e.g. one reloaded from disk, with no connections or
+    /// undo history.
+    pub(crate) fn from_field(field: Field, metrics: Arc<Metrics>) -> Self {
         Self {
-            field: Field::new(params),
+            last_activity: field.clock.now(),
+            field,
             streams: HashMap::new(),
-            last_activity: Instant::now(),
+            players: HashSet::new(),
+            expiry_warned: false,
+            history: VecDeque::new(),
+            updates: UpdateLog::default(),
+            metrics,
+            this: Weak::new(),
+            time_limit: None,
         }
     }
 
+    /// Wraps the game for `Games`. Timers only run for games shared this way.
+    pub fn into_shared(mut self) -> Arc<Mutex<Game>> {
+        Arc::new_cyclic(|this| {
+            self.this = this.clone();
+            Mutex::new(self)
+        })
+    }
+
+    pub(crate) fn field(&self) -> &Field {
+        &self.field
+    }
+
     #[instrument(level = "trace", skip(self))]
     pub async fn restart(&mut self, params: GameParams) {
         info!(
             "Restarting game with new parameters: {}x{} with {} bombs",
             params.width, params.height, params.bombs
         );
+        let old_params = self.field.params();
+        let revision = self.field.revision + 1;
+        let clock = self.field.clock.clone();
+        let max_cascade_radius = self.field.max_cascade_radius;
+        self.cancel_time_limit();
         self.field = Field::new(params);
-        self.last_activity = Instant::now();
-        broadcast(&mut self.streams, &self.field.init_message()).await;
+        self.field.revision = revision;
+        self.field.clock = clock;
+        self.field.max_cascade_radius = max_cascade_radius;
+        self.history.clear();
+        self.updates.clear();
+        self.touch();
+
+        let new_params = self.field.params();
+        let params_changed = GameParams {
+            seed: old_params.seed,
+            ..new_params.clone()
+        } != old_params;
+        let new_layout = params_changed || new_params.seed != old_params.seed;
+        broadcast(
+            &self.metrics,
+            &mut self.streams,
+            &ServerMessage::Restarted {
+                params: new_params,
+                params_changed,
+                new_layout,
+            },
+        )
+        .await;
+        broadcast(
+            &self.metrics,
+            &mut self.streams,
+            &self.field.init_message(None),
+        )
+        .await;
         info!(
             "Game restarted and broadcasted to {} connections",
             self.streams.len()
         );
     }
 
+    /// Adds a connection and sends it the board. Players beyond `max_players` get a
+    /// `game_full` error instead and their stream is closed, in which case `None` is
+    /// returned. Spectators are always let in.
     #[instrument(level = "trace", skip(self, stream))]
-    pub async fn add_stream(&mut self, mut stream: SplitSink<DuplexStream, Message>) -> Uuid {
+    pub async fn add_stream(
+        &mut self,
+        mut stream: SplitSink<DuplexStream, Message>,
+        options: ConnectionOptions,
+    ) -> Option<Uuid> {
+        let max_players = self.field.max_players;
+        if !options.spectator && max_players > 0 && self.players() >= max_players {
+            info!(
+                "Rejecting player: game is full with {} players",
+                max_players
+            );
+            let error = ServerMessage::Error {
+                code: "game_full".to_string(),
+                message: format!("The game is full ({} players)", max_players),
+            };
+            send(&self.metrics, &mut stream, options.format, &error).await;
+            let _ = stream.close().await;
+            return None;
+        }
+
         let id = Uuid::new_v4();
-        debug!("Adding stream {} to game", id);
-        send(&mut stream, &self.field.init_message()).await;
-        self.streams.insert(id, stream);
-        self.last_activity = Instant::now();
+        debug!("Adding stream {} to game with {:?}", id, options);
+        // Only tokens issued by this game are honoured, so players can't pick their own
+        let (token, missed) = match options.token {
+            Some(token) if self.players.contains(&token) => {
+                info!("Stream {} resumes player {}", id, token);
+                let missed = options
+                    .revision
+                    .and_then(|revision| self.updates.since(revision, self.field.revision));
+                (token, missed)
+            }
+            _ => {
+                let token = Uuid::new_v4();
+                self.players.insert(token);
+                (token, None)
+            }
+        };
+        match missed {
+            Some(updates) => {
+                debug!("Stream {} catches up with {} updates", id, updates.len());
+                let won = self.field.won;
+                let lost = self.field.finished && !won;
+                send(
+                    &self.metrics,
+                    &mut stream,
+                    options.format,
+                    &self.field.update_message(updates, won, lost),
+                )
+                .await;
+            }
+            None => {
+                send(
+                    &self.metrics,
+                    &mut stream,
+                    options.format,
+                    &self.field.init_message(Some(token)),
+                )
+                .await
+            }
+        }
+        self.streams.insert(
+            id,
+            Connection {
+                sink: stream,
+                token,
+                options,
+                viewport: None,
+            },
+        );
+        self.touch();
         info!(
             "Stream {} added, total connections: {}",
             id,
             self.streams.len()
         );
-        id
+        self.broadcast_presence().await;
+        Some(id)
     }
 
     #[instrument(level = "trace", skip(self))]
@@ -263,45 +1199,623 @@ impl Game {
                 id,
                 self.streams.len()
             );
+            self.broadcast_presence().await;
         } else {
             warn!("Attempted to remove non-existent stream: {}", id);
         }
-        self.last_activity = Instant::now()
+        self.touch();
+    }
+
+    /// Tells every connection how many are connected. Called after the stream map
+    /// changed, so a removed stream is no longer sent to.
+    async fn broadcast_presence(&mut self) {
+        let presence = ServerMessage::Presence {
+            players: self.streams.len(),
+        };
+        broadcast(&self.metrics, &mut self.streams, &presence).await;
+    }
+
+    /// Effective parameters of the current board, including its seed
+    pub fn params(&self) -> GameParams {
+        self.field.params()
+    }
+
+    /// Seed the current board was generated from
+    pub fn seed(&self) -> u64 {
+        self.field.seed
+    }
+
+    pub fn revealed_mask(&self) -> RevealedMask {
+        let states = self
+            .field
+            .cells
+            .iter()
+            .map(|cell| mask_state(cell.revealed));
+        RevealedMask::encode(self.field.width, self.field.height, states)
+    }
+
+    /// The current board as a [`BoardCode`] string, which `GameParams::code` can load
+    /// into a new game
+    pub fn board_code(&self) -> String {
+        self.field.board_code().to_code()
+    }
+
+    /// The solved board, only once the game is finished so it can't be used to cheat
+    pub fn solution(&self) -> Option<BoardSolution> {
+        self.field.finished.then(|| self.field.to_solution())
+    }
+
+    /// Metadata for the admin game listing, without any board contents
+    pub fn summary(&self, id: String) -> GameSummary {
+        GameSummary {
+            id,
+            width: self.field.width,
+            height: self.field.height,
+            bombs: self.field.bombs,
+            connections: self.streams.len(),
+            players: self.players(),
+            spectators: self.spectators(),
+            finished: self.field.finished,
+            idle_secs: self.idle().as_secs(),
+        }
+    }
+
+    /// Whether `POST /join` may hand this game out: it is public, still running and has
+    /// fewer than `LOBBY_MAX_PLAYERS` players, or fewer than its own `max_players`
+    pub fn accepts_lobby_players(&self) -> bool {
+        let max_players = match self.field.max_players {
+            0 => LOBBY_MAX_PLAYERS,
+            max_players => max_players.min(LOBBY_MAX_PLAYERS),
+        };
+        self.field.public && !self.field.finished && self.players() < max_players
+    }
+
+    /// Number of connections allowed to play, i.e. all but the spectators
+    pub fn players(&self) -> usize {
+        self.streams.len() - self.spectators()
+    }
+
+    /// Number of connections that joined with `?spectate=true`
+    pub fn spectators(&self) -> usize {
+        self.streams
+            .values()
+            .filter(|connection| connection.options.spectator)
+            .count()
+    }
+
+    /// Whether the game is over and how it ended, for `GET /games/<id>/status`
+    pub fn status(&self) -> GameStatus {
+        GameStatus {
+            finished: self.field.finished,
+            won: self.field.won,
+            connections: self.streams.len(),
+        }
     }
 
     pub fn has_active_connections(&self) -> bool {
         !self.streams.is_empty()
     }
 
-    pub fn should_cleanup(&self, inactive_timeout_secs: u64) -> bool {
-        if self.has_active_connections() {
-            return false;
+    fn touch(&mut self) {
+        self.last_activity = self.field.clock.now();
+        self.expiry_warned = false;
+    }
+
+    /// Time since the last action, connection change or chat message
+    fn idle(&self) -> Duration {
+        self.field.clock.now().duration_since(self.last_activity)
+    }
+
+    /// Decides whether the game should be kept, warned about its upcoming expiry, or removed.
+    /// Games with connections are warned `warning_secs` before their timeout and only
+    /// removed on a later pass, so the warning always precedes removal.
+    pub fn cleanup_action(
+        &self,
+        inactive_timeout_secs: u64,
+        active_timeout_secs: u64,
+        warning_secs: u64,
+    ) -> CleanupAction {
+        let elapsed = self.idle().as_secs();
+
+        if !self.has_active_connections() {
+            return if elapsed > inactive_timeout_secs {
+                CleanupAction::Remove
+            } else {
+                CleanupAction::Keep
+            };
         }
 
-        let now = Instant::now();
-        let elapsed = now.duration_since(self.last_activity).as_secs();
+        if elapsed > active_timeout_secs && self.expiry_warned {
+            CleanupAction::Remove
+        } else if elapsed + warning_secs > active_timeout_secs && !self.expiry_warned {
+            CleanupAction::Warn {
+                seconds_remaining: active_timeout_secs.saturating_sub(elapsed),
+            }
+        } else {
+            CleanupAction::Keep
+        }
+    }
 
-        elapsed > inactive_timeout_secs
+    pub async fn warn_expiry(&mut self, seconds_remaining: u64) {
+        self.expiry_warned = true;
+        broadcast(
+            &self.metrics,
+            &mut self.streams,
+            &ServerMessage::ExpiryWarning { seconds_remaining },
+        )
+        .await;
+        info!(
+            "Warned {} connections of expiry in {}s",
+            self.streams.len(),
+            seconds_remaining
+        );
     }
 
-    #[instrument(level = "trace", skip(self), fields(x = pos.x, y = pos.y))]
-    pub async fn flag(&mut self, pos: Pos) {
-        if !self.field.validate_pos(&pos) {
-            warn!("Invalid flag position: ({}, {})", pos.x, pos.y);
+    /// Tells every connection the game was shut down by an operator, then closes them.
+    pub async fn close_for_shutdown(&mut self) {
+        let closed = ServerMessage::Error {
+            code: "closed".to_string(),
+            message: "The game was closed by the server".to_string(),
+        };
+        broadcast(&self.metrics, &mut self.streams, &closed).await;
+        self.close_streams().await;
+    }
+
+    pub async fn close_streams(&mut self) {
+        let futures: Vec<_> = self
+            .streams
+            .values_mut()
+            .map(|connection| connection.sink.close())
+            .collect();
+        join_all(futures).await;
+        self.streams.clear();
+    }
+
+    /// Limits the updates a connection receives to the given region and sends it the
+    /// current state of every cell inside it, since updates outside its previous region
+    /// were not delivered.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn set_viewport(&mut self, stream_id: &Uuid, top_left: Pos, bottom_right: Pos) {
+        let Some(connection) = self.streams.get_mut(stream_id) else {
+            return;
+        };
+
+        let viewport = Viewport {
+            top_left,
+            bottom_right,
+        };
+        connection.viewport = Some(viewport);
+        debug!("Stream {} viewport set to {:?}", stream_id, viewport);
+
+        let updates = (top_left.y..=bottom_right.y.min(self.field.height.saturating_sub(1)))
+            .flat_map(|y| {
+                (top_left.x..=bottom_right.x.min(self.field.width.saturating_sub(1)))
+                    .map(move |x| Pos { x, y })
+            })
+            .map(|pos| CellUpdate {
+                pos,
+                value: self.field.cell(&pos).into(),
+                depth: None,
+            })
+            .collect();
+        let won = self.field.won;
+        let lost = self.field.finished && !won;
+
+        send(
+            &self.metrics,
+            &mut connection.sink,
+            connection.options.format,
+            &self.field.update_message(updates, won, lost),
+        )
+        .await;
+    }
+
+    /// Records the board as it was before an action so it can be undone, dropping the
+    /// oldest entry once `MAX_UNDO_HISTORY` is reached.
+    fn push_history(&mut self, snapshot: FieldSnapshot) {
+        if self.history.len() == MAX_UNDO_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot);
+    }
+
+    /// Starts a new revision for `updates` before they are broadcast, keeping them for
+    /// players who reconnect later. Nothing changes if there are no updates.
+    fn log_updates(&mut self, updates: &[CellUpdate]) {
+        if updates.is_empty() {
+            return;
+        }
+        self.field.revision += 1;
+        let capacity = self.field.width * self.field.height;
+        self.updates.push(self.field.revision, updates, capacity);
+    }
+
+    /// Reverts the most recent reveal, flag, chord or hint, including one that ended the
+    /// game, and resyncs every connection with an `Init`.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn undo(&mut self, stream_id: &Uuid) {
+        if self.field.time_limit_expired() {
+            debug!("Not undoing: the time limit has run out");
+            self.send_error(
+                stream_id,
+                "game_finished",
+                "The time limit has run out".to_string(),
+            )
+            .await;
+            return;
+        }
+        let Some(snapshot) = self.history.pop_back() else {
+            debug!("Nothing to undo");
+            self.send_error(
+                stream_id,
+                "nothing_to_undo",
+                "No action to undo".to_string(),
+            )
+            .await;
+            return;
+        };
+
+        self.touch();
+        self.field.restore(snapshot);
+        // Undoing the final move reopens the game, which `finish` stopped timing
+        if !self.field.finished && self.field.stats.started_at.is_some() {
+            self.start_time_limit();
+        }
+        // Logged updates can't be replayed on top of the restored board
+        self.field.revision += 1;
+        self.updates.clear();
+        info!("Undid last action, {} steps left", self.history.len());
+        broadcast(
+            &self.metrics,
+            &mut self.streams,
+            &self.field.init_message(None),
+        )
+        .await;
+    }
+
+    /// Sends the current board to a single connection as an `Init`, followed by the final
+    /// status if the game is already over.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn send_state_to(&mut self, id: &Uuid) {
+        let finished = self.field.finished;
+        let won = self.field.won;
+
+        if let Some(connection) = self.streams.get_mut(id) {
+            debug!("Sending current state to stream {}", id);
+            let init = self.field.init_message(Some(connection.token));
+            send(
+                &self.metrics,
+                &mut connection.sink,
+                connection.options.format,
+                &init,
+            )
+            .await;
+            if finished {
+                let status = self.field.update_message(Vec::new(), won, !won);
+                send(
+                    &self.metrics,
+                    &mut connection.sink,
+                    connection.options.format,
+                    &status,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Relays a chat message to every connection, the sender included. Chat does not
+    /// count as a move, but keeps the game from expiring.
+    #[instrument(level = "trace", skip(self, text))]
+    pub async fn chat(&mut self, stream_id: &Uuid, text: String) {
+        if text.chars().count() > MAX_CHAT_LENGTH {
+            debug!("Rejecting chat message from stream {}: too long", stream_id);
+            self.send_error(
+                stream_id,
+                "chat_too_long",
+                format!(
+                    "Chat messages are limited to {} characters",
+                    MAX_CHAT_LENGTH
+                ),
+            )
+            .await;
+            return;
+        }
+
+        self.touch();
+        let message = ServerMessage::Chat {
+            from: *stream_id,
+            text,
+        };
+        broadcast(&self.metrics, &mut self.streams, &message).await;
+    }
+
+    /// Pauses or resumes the game for everyone and broadcasts the new state. Changing
+    /// nothing, or pausing a finished game, is ignored.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn set_paused(&mut self, stream_id: &Uuid, paused: bool) {
+        if self.field.finished {
+            debug!("Ignoring pause change on finished game");
+            self.send_error(
+                stream_id,
+                "game_finished",
+                "The game is already over".to_string(),
+            )
+            .await;
+            return;
+        }
+        if self.field.paused == paused {
+            debug!(
+                "Game is already {}",
+                if paused { "paused" } else { "running" }
+            );
+            return;
+        }
+
+        self.touch();
+        self.field.set_paused(paused);
+        // Catching up from logged updates would leave a reconnecting client unaware of
+        // the pause, so it gets a full `Init` instead
+        self.field.revision += 1;
+        self.updates.clear();
+        info!("Game {}", if paused { "paused" } else { "resumed" });
+        broadcast(
+            &self.metrics,
+            &mut self.streams,
+            &ServerMessage::Paused { paused },
+        )
+        .await;
+    }
+
+    /// Starts enforcing `time_limit_secs` unless already running. The task sleeps until
+    /// the limit could be reached and checks the game timer again, so pauses push the
+    /// deadline back. It only holds a weak reference, so cleanup isn't held up either.
+    fn start_time_limit(&mut self) {
+        let Some(limit) = self.field.time_limit_secs.map(Duration::from_secs) else {
+            return;
+        };
+        if self.time_limit.is_some() {
+            return;
+        }
+
+        let game = self.this.clone();
+        let mut remaining = limit.saturating_sub(self.field.elapsed());
+        debug!("Game ends in {:?} unless finished first", remaining);
+        self.time_limit = Some(tokio::spawn(async move {
+            loop {
+                time::sleep(remaining).await;
+                let Some(game) = game.upgrade() else {
+                    return;
+                };
+                let mut game = game.lock().await;
+                remaining = limit.saturating_sub(game.field.elapsed());
+                if remaining.is_zero() {
+                    // Dropping the own handle detaches the task, which is finishing anyway
+                    game.time_limit = None;
+                    game.end_on_time_limit().await;
+                    return;
+                }
+            }
+        }));
+    }
+
+    fn cancel_time_limit(&mut self) {
+        if let Some(task) = self.time_limit.take() {
+            task.abort();
+        }
+    }
+
+    /// Ends the game as lost because `time_limit_secs` ran out. This can't be undone.
+    async fn end_on_time_limit(&mut self) {
+        if self.field.finished {
+            return;
+        }
+        info!("Time limit reached, game lost");
+        self.history.clear();
+        self.end_with_loss(Vec::new()).await;
+    }
+
+    /// Rejects an action that is blocked while the game is paused. Returns whether the
+    /// game was paused.
+    async fn reject_if_paused(&mut self, stream_id: &Uuid) -> bool {
+        if !self.field.paused {
+            return false;
+        }
+        debug!("Ignoring action from stream {}: game is paused", stream_id);
+        self.send_error(stream_id, "paused", "The game is paused".to_string())
+            .await;
+        true
+    }
+
+    /// Applies a message from `stream_id`. Restarts are checked against `board_limits`,
+    /// `allowed_sizes` and `max_cells` first, with invalid parameters reported to the
+    /// sender.
+    /// Spectators are not filtered out here; callers reject their actions beforehand.
+    pub async fn handle_message(
+        &mut self,
+        stream_id: &Uuid,
+        message: ClientMessage,
+        board_limits: &BoardLimits,
+        allowed_sizes: &AllowedBoardSizes,
+        max_cells: MaxBoardCells,
+    ) {
+        match message {
+            ClientMessage::Reveal { pos } => {
+                debug!("Player revealing cell at ({}, {})", pos.x, pos.y);
+                self.reveal(stream_id, pos).await;
+            }
+            ClientMessage::Flag { pos } => {
+                debug!("Player flagging cell at ({}, {})", pos.x, pos.y);
+                self.flag(stream_id, pos).await;
+            }
+            ClientMessage::Chord { pos } => {
+                debug!("Player chording cell at ({}, {})", pos.x, pos.y);
+                self.chord(stream_id, pos).await;
+            }
+            ClientMessage::Viewport {
+                top_left,
+                bottom_right,
+            } => {
+                self.set_viewport(stream_id, top_left, bottom_right).await;
+            }
+            ClientMessage::Undo => {
+                debug!("Player undoing last action");
+                self.undo(stream_id).await;
+            }
+            ClientMessage::Hint => {
+                debug!("Player requesting hint");
+                self.hint(stream_id).await;
+            }
+            ClientMessage::GetState => self.send_state_to(stream_id).await,
+            ClientMessage::Chat { text } => self.chat(stream_id, text).await,
+            ClientMessage::Pause => {
+                debug!("Player pausing the game");
+                self.set_paused(stream_id, true).await;
+            }
+            ClientMessage::Resume => {
+                debug!("Player resuming the game");
+                self.set_paused(stream_id, false).await;
+            }
+            ClientMessage::Restart { params } => {
+                self.handle_restart(stream_id, params, board_limits, allowed_sizes, max_cells)
+                    .await;
+            }
+        }
+    }
+
+    async fn handle_restart(
+        &mut self,
+        stream_id: &Uuid,
+        mut params: GameParams,
+        board_limits: &BoardLimits,
+        allowed_sizes: &AllowedBoardSizes,
+        max_cells: MaxBoardCells,
+    ) {
+        if let Err(error) = params.resolve_code() {
+            warn!("Ignoring restart with invalid board code: {}", error);
+            self.send_error(stream_id, "invalid_params", error).await;
+            return;
+        }
+        if !max_cells.allows(&params) {
+            warn!(
+                "Ignoring restart with oversized board {}x{}",
+                params.width, params.height
+            );
+            self.send_error(
+                stream_id,
+                "board_too_large",
+                "Board exceeds the server's cell limit".to_string(),
+            )
+            .await;
+            return;
+        }
+        if let Err(error) = params.validate_within(board_limits) {
+            warn!("Ignoring restart with invalid parameters: {}", error);
+            self.send_error(stream_id, "invalid_params", error).await;
+            return;
+        }
+        if !allowed_sizes.allows(&params) {
+            warn!(
+                "Ignoring restart with non-preset board {}x{} with {} bombs",
+                params.width, params.height, params.bombs
+            );
+            let message = format!(
+                "board {}x{}x{} is not an allowed preset",
+                params.width, params.height, params.bombs
+            );
+            self.send_error(stream_id, "invalid_params", message).await;
             return;
         }
+        self.restart(params).await;
+    }
+
+    /// Sends a keepalive ping to a single connection.
+    pub async fn ping(&mut self, stream_id: &Uuid) {
+        if let Some(connection) = self.streams.get_mut(stream_id) {
+            send_frame(&mut connection.sink, Message::Ping(Vec::new())).await;
+        }
+    }
+
+    /// Sends an `Error` to a single connection, leaving the other players untouched.
+    pub async fn send_error(&mut self, stream_id: &Uuid, code: &str, message: String) {
+        if let Some(connection) = self.streams.get_mut(stream_id) {
+            let error = ServerMessage::Error {
+                code: code.to_string(),
+                message,
+            };
+            send(
+                &self.metrics,
+                &mut connection.sink,
+                connection.options.format,
+                &error,
+            )
+            .await;
+        }
+    }
+
+    async fn send_out_of_bounds(&mut self, stream_id: &Uuid, pos: Pos) {
+        let message = format!(
+            "Position ({}, {}) is outside the {}x{} board",
+            pos.x, pos.y, self.field.width, self.field.height
+        );
+        self.send_error(stream_id, "out_of_bounds", message).await;
+    }
+
+    /// Sends the final status and summary of a finished game to a single connection, so
+    /// a client that missed the terminal broadcast can still catch up.
+    async fn send_terminal_state(&mut self, stream_id: &Uuid) {
+        let won = self.field.won;
+        let status = self.field.update_message(Vec::new(), won, !won);
+        let summary = self.field.summary_message(won);
+
+        if let Some(connection) = self.streams.get_mut(stream_id) {
+            send(
+                &self.metrics,
+                &mut connection.sink,
+                connection.options.format,
+                &status,
+            )
+            .await;
+            send(
+                &self.metrics,
+                &mut connection.sink,
+                connection.options.format,
+                &summary,
+            )
+            .await;
+        }
+    }
 
+    #[instrument(level = "trace", skip(self), fields(x = pos.x, y = pos.y))]
+    pub async fn flag(&mut self, stream_id: &Uuid, pos: Pos) {
         if self.field.finished {
             debug!(
                 "Ignoring flag action on finished game at ({}, {})",
                 pos.x, pos.y
             );
+            self.send_error(
+                stream_id,
+                "game_finished",
+                "The game is already over".to_string(),
+            )
+            .await;
+            self.send_terminal_state(stream_id).await;
+            return;
+        }
+
+        if !self.field.flags_while_paused && self.reject_if_paused(stream_id).await {
+            return;
+        }
+
+        if !self.field.validate_pos(&pos) {
+            warn!("Invalid flag position: ({}, {})", pos.x, pos.y);
+            self.send_out_of_bounds(stream_id, pos).await;
             return;
         }
 
-        self.last_activity = Instant::now();
+        self.touch();
 
-        if let Some(cell) = self.field.cells.get_mut(pos.x + pos.y * self.field.width) {
+        let snapshot = self.field.snapshot();
+        if let Some(cell) = self.field.cells.get_mut(pos.index(self.field.width)) {
             let old_state = cell.revealed;
             match cell.revealed {
                 RevealedState::Hidden => {
@@ -312,74 +1826,160 @@ impl Game {
                     cell.revealed = RevealedState::Hidden;
                     debug!("Cell ({}, {}) unmarked", pos.x, pos.y);
                 }
-                RevealedState::Flagged => {
+                RevealedState::Flagged if self.field.enable_question_marks => {
                     cell.revealed = RevealedState::Marked;
                     debug!("Cell ({}, {}) marked", pos.x, pos.y);
                 }
+                RevealedState::Flagged => {
+                    cell.revealed = RevealedState::Hidden;
+                    debug!("Cell ({}, {}) unflagged", pos.x, pos.y);
+                }
                 RevealedState::Revealed => {
                     debug!(
                         "Ignoring flag action on revealed cell ({}, {})",
                         pos.x, pos.y
                     );
+                    let message = format!("Cell ({}, {}) is already revealed", pos.x, pos.y);
+                    self.send_error(stream_id, "cell_revealed", message).await;
                     return;
                 }
             };
 
             if old_state != cell.revealed {
-                broadcast(
-                    &mut self.streams,
-                    &ServerMessage::Update {
-                        updates: vec![CellUpdate {
-                            pos,
-                            value: (&*cell).into(),
-                        }],
-                        won: false,
-                        lost: false,
-                    },
-                )
-                .await;
+                let update = CellUpdate {
+                    pos,
+                    value: (&*cell).into(),
+                    depth: None,
+                };
+                self.push_history(snapshot);
+                self.field.record_move();
+                self.field.stats.flags += 1;
+                self.metrics.record_flag();
+                self.log_updates(std::slice::from_ref(&update));
+                let message = self.field.update_message(vec![update], false, false);
+                broadcast_update(&self.metrics, &mut self.streams, message).await;
             }
         };
     }
 
-    #[instrument(level = "trace", skip(self), fields(x = pos.x, y = pos.y))]
-    pub async fn reveal(&mut self, pos: Pos) {
-        if !self.field.validate_pos(&pos) {
-            warn!("Invalid reveal position: ({}, {})", pos.x, pos.y);
-            return;
+    /// Reveals all bombs, ends the game as lost and broadcasts `updates` together with
+    /// the revealed bombs.
+    async fn end_with_loss(&mut self, mut updates: Vec<CellUpdate>) {
+        self.field.reveal_bombs(&mut updates);
+        self.field.finish(false);
+        self.cancel_time_limit();
+        self.metrics.record_loss();
+        info!("Game ended with loss, {} cells updated", updates.len());
+        self.log_updates(&updates);
+        let message = self.field.update_message(updates, false, true);
+        broadcast_update(&self.metrics, &mut self.streams, message).await;
+        broadcast(
+            &self.metrics,
+            &mut self.streams,
+            &self.field.summary_message(false),
+        )
+        .await;
+    }
+
+    /// Broadcasts the result of a safe reveal and ends the game if it was won. A win
+    /// also flags the remaining bombs when `auto_flag_on_win` is set.
+    async fn finish_reveal(&mut self, mut updates: Vec<CellUpdate>) {
+        if !updates.is_empty() {
+            self.field.stats.useful_clicks += 1;
+        }
+
+        let won = self.field.has_won();
+        if won {
+            if self.field.auto_flag_on_win {
+                self.field.flag_bombs(&mut updates);
+            }
+            self.field.finish(true);
+            self.cancel_time_limit();
+            self.metrics.record_win();
+            info!("Game won! All safe cells revealed.");
+        } else {
+            debug!("Revealed {} cells, game continues", updates.len());
+            self.start_time_limit();
+            if self.field.no_guess && !self.field.has_forced_move() {
+                debug!("No-guess game has no forced move left, the next move is a guess");
+            }
+        }
+
+        self.log_updates(&updates);
+        let message = self.field.update_message(updates, won, false);
+        broadcast_update(&self.metrics, &mut self.streams, message).await;
+
+        if won {
+            broadcast(
+                &self.metrics,
+                &mut self.streams,
+                &self.field.summary_message(true),
+            )
+            .await;
         }
+    }
 
+    #[instrument(level = "trace", skip(self), fields(x = pos.x, y = pos.y))]
+    pub async fn reveal(&mut self, stream_id: &Uuid, pos: Pos) {
         if self.field.finished {
             debug!(
                 "Ignoring reveal action on finished game at ({}, {})",
                 pos.x, pos.y
             );
+            self.send_error(
+                stream_id,
+                "game_finished",
+                "The game is already over".to_string(),
+            )
+            .await;
+            self.send_terminal_state(stream_id).await;
+            return;
+        }
+
+        if self.reject_if_paused(stream_id).await {
+            return;
+        }
+
+        if !self.field.validate_pos(&pos) {
+            warn!("Invalid reveal position: ({}, {})", pos.x, pos.y);
+            self.send_out_of_bounds(stream_id, pos).await;
             return;
         }
 
-        self.last_activity = Instant::now();
+        self.touch();
 
-        if let Some(cell) = self.field.cells.get_mut(pos.x + pos.y * self.field.width) {
-            if cell.revealed == RevealedState::Flagged {
+        let index = pos.index(self.field.width);
+        match self.field.cells[index].revealed {
+            RevealedState::Flagged => {
                 debug!("Ignoring reveal on flagged cell ({}, {})", pos.x, pos.y);
+                let message = format!(
+                    "Cell ({}, {}) is flagged, unflag it before revealing",
+                    pos.x, pos.y
+                );
+                self.send_error(stream_id, "cell_flagged", message).await;
                 return;
             }
+            // Nothing would change, so don't count a move or broadcast an empty update
+            RevealedState::Revealed => {
+                debug!("Ignoring reveal on revealed cell ({}, {})", pos.x, pos.y);
+                return;
+            }
+            RevealedState::Hidden | RevealedState::Marked => {}
+        }
+
+        self.push_history(self.field.snapshot());
+        // Hints reveal cells too, and those must stay where they are
+        if self.field.revealed == 0 {
+            self.field.ensure_opening(pos);
+        }
 
+        if let Some(cell) = self.field.cells.get_mut(index) {
             if cell.bomb {
                 warn!("Player hit bomb at ({}, {}) - game over!", pos.x, pos.y);
-                let mut updates = Vec::new();
-                self.field.reveal_bombs(&mut updates);
-                self.field.finished = true;
-                info!("Game ended with loss, revealed {} bombs", updates.len());
-                broadcast(
-                    &mut self.streams,
-                    &ServerMessage::Update {
-                        updates,
-                        won: false,
-                        lost: true,
-                    },
-                )
-                .await;
+                self.field.record_move();
+                self.field.stats.reveals += 1;
+                self.metrics.record_reveal();
+                self.end_with_loss(Vec::new()).await;
                 return;
             }
 
@@ -387,26 +1987,1062 @@ impl Game {
                 "Revealing cell ({}, {}) with {} adjacent bombs",
                 pos.x, pos.y, cell.adjacent
             );
+            self.field.record_move();
+            self.field.stats.reveals += 1;
+            self.metrics.record_reveal();
             let mut updates = Vec::new();
-            self.field.reveal_recursive(pos, &mut updates);
-            let won = self.field.has_won();
-            self.field.finished = won;
-
-            if won {
-                info!("Game won! All safe cells revealed.");
-            } else {
-                debug!("Revealed {} cells, game continues", updates.len());
-            }
+            self.field.reveal_flood(pos, &mut updates);
+            self.finish_reveal(updates).await;
+        }
+    }
 
-            broadcast(
-                &mut self.streams,
-                &ServerMessage::Update {
-                    updates,
-                    won,
-                    lost: false,
-                },
+    /// Reveals the first hidden, unflagged safe cell in board order. Hints are counted
+    /// separately from moves so they can be capped later.
+    #[instrument(level = "trace", skip(self))]
+    pub async fn hint(&mut self, stream_id: &Uuid) {
+        if self.field.finished {
+            debug!("Ignoring hint on finished game");
+            self.send_error(
+                stream_id,
+                "game_finished",
+                "The game is already over".to_string(),
             )
             .await;
+            self.send_terminal_state(stream_id).await;
+            return;
+        }
+
+        if self.reject_if_paused(stream_id).await {
+            return;
+        }
+
+        let Some(index) = self.field.cells.iter().position(|cell| {
+            !cell.bomb && matches!(cell.revealed, RevealedState::Hidden | RevealedState::Marked)
+        }) else {
+            debug!("No safe hidden cell left to hint");
+            self.send_error(stream_id, "no_hint", "No safe hidden cell left".to_string())
+                .await;
+            return;
+        };
+
+        let pos = Pos::from_index(index, self.field.width);
+        debug!("Hinting safe cell ({}, {})", pos.x, pos.y);
+
+        self.touch();
+        self.push_history(self.field.snapshot());
+        self.field.record_move();
+        self.field.stats.hints += 1;
+        let mut updates = Vec::new();
+        self.field.reveal_flood(pos, &mut updates);
+        self.finish_reveal(updates).await;
+    }
+
+    /// Reveals all hidden neighbours of a revealed number once the matching number of
+    /// neighbours is flagged, or at least that many when `chord_requires_exact_flags` is
+    /// off. A wrongly placed flag makes the chord hit a bomb and lose.
+    #[instrument(level = "trace", skip(self), fields(x = pos.x, y = pos.y))]
+    pub async fn chord(&mut self, stream_id: &Uuid, pos: Pos) {
+        if self.field.finished {
+            debug!(
+                "Ignoring chord action on finished game at ({}, {})",
+                pos.x, pos.y
+            );
+            self.send_error(
+                stream_id,
+                "game_finished",
+                "The game is already over".to_string(),
+            )
+            .await;
+            self.send_terminal_state(stream_id).await;
+            return;
+        }
+
+        if self.reject_if_paused(stream_id).await {
+            return;
+        }
+
+        if !self.field.validate_pos(&pos) {
+            warn!("Invalid chord position: ({}, {})", pos.x, pos.y);
+            self.send_out_of_bounds(stream_id, pos).await;
+            return;
+        }
+
+        let cell = &self.field.cells[pos.index(self.field.width)];
+        if cell.revealed != RevealedState::Revealed || cell.adjacent == 0 {
+            debug!("Ignoring chord on non-number cell ({}, {})", pos.x, pos.y);
+            return;
+        }
+
+        let neighbors = self.field.neighbors(pos);
+        let flagged = neighbors
+            .iter()
+            .filter(|neighbor| self.field.cell(neighbor).revealed == RevealedState::Flagged)
+            .count();
+        if !self.field.chord_allowed(flagged, cell.adjacent) {
+            debug!(
+                "Ignoring chord at ({}, {}): {} flags for {} adjacent bombs",
+                pos.x, pos.y, flagged, cell.adjacent
+            );
+            return;
+        }
+
+        let snapshot = self.field.snapshot();
+        let mut updates = Vec::new();
+        let mut hit_bomb = false;
+        for neighbor in neighbors {
+            let cell = self.field.cell(&neighbor);
+            if !matches!(cell.revealed, RevealedState::Hidden | RevealedState::Marked) {
+                continue;
+            }
+
+            if cell.bomb {
+                warn!(
+                    "Chord at ({}, {}) hit bomb at ({}, {}) - game over!",
+                    pos.x, pos.y, neighbor.x, neighbor.y
+                );
+                hit_bomb = true;
+            } else {
+                self.field.reveal_flood(neighbor, &mut updates);
+            }
+        }
+
+        // Every neighbour was already flagged or revealed, so there is nothing to undo
+        if !hit_bomb && updates.is_empty() {
+            debug!("Chord at ({}, {}) changed nothing", pos.x, pos.y);
+            return;
+        }
+
+        self.touch();
+        self.push_history(snapshot);
+        self.field.record_move();
+        if hit_bomb {
+            self.end_with_loss(updates).await;
+        } else {
+            self.finish_reveal(updates).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::MockClock;
+
+    use super::*;
+
+    #[test]
+    fn generate_bombs_places_exact_count() {
+        let boards = [
+            (9, 9, 10),
+            (16, 16, 40),
+            (30, 16, 99),
+            (2, 1, 1),
+            (5, 5, 24),
+        ];
+        for (width, height, bombs) in boards {
+            let params = GameParams {
+                width,
+                height,
+                bombs,
+                ..Default::default()
+            };
+            for seed in 0..200 {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                let placed = generate_bombs(&params, &mut rng);
+                assert_eq!(placed.len(), width * height);
+                assert_eq!(
+                    placed.iter().filter(|&&bomb| bomb).count(),
+                    bombs,
+                    "{}x{} with {} bombs, seed {}",
+                    width,
+                    height,
+                    bombs,
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn field_bomb_count_matches_params() {
+        let params = GameParams {
+            width: 16,
+            height: 16,
+            bombs: 40,
+            ..Default::default()
+        };
+        for seed in 0..50 {
+            let field = Field::new(GameParams {
+                seed: Some(seed),
+                ..params.clone()
+            });
+            assert_eq!(
+                field.cells.iter().filter(|cell| cell.bomb).count(),
+                field.bombs
+            );
+        }
+    }
+
+    /// Game on a fixed board, drawn row by row with `*` for bombs and `.` for safe cells,
+    /// with nothing revealed yet
+    fn game_with_board(rows: &[&str], params: GameParams) -> Game {
+        let bombs: Vec<bool> = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| c == '*'))
+            .collect();
+        let params = GameParams {
+            width: rows[0].len(),
+            height: rows.len(),
+            bombs: bombs.iter().filter(|&&bomb| bomb).count(),
+            ..params
+        };
+        let mut game = Game::new(
+            params.clone(),
+            Arc::new(Metrics::default()),
+            MaxCascadeRadius::default(),
+        );
+        for (i, cell) in game.field.cells.iter_mut().enumerate() {
+            cell.bomb = bombs[i];
+            cell.adjacent = count_adjacent_bombs(&bombs, i, &params);
+            cell.revealed = RevealedState::Hidden;
+        }
+        game
+    }
+
+    fn set_cells(game: &mut Game, cells: &[(usize, usize)], state: RevealedState) {
+        for &(x, y) in cells {
+            let index = Pos { x, y }.index(game.field.width);
+            game.field.cells[index].revealed = state;
+            if state == RevealedState::Revealed {
+                game.field.revealed += 1;
+            }
+        }
+    }
+
+    /// 3x3 game with bombs in the top corners and the centre (showing 2) revealed.
+    fn chord_game(chord_requires_exact_flags: bool) -> Game {
+        let params = GameParams {
+            chord_requires_exact_flags,
+            ..Default::default()
+        };
+        let mut game = game_with_board(&["*.*", "...", "..."], params);
+        set_cells(&mut game, &[(1, 1)], RevealedState::Revealed);
+        game
+    }
+
+    fn place_flags(game: &mut Game, flags: &[(usize, usize)]) {
+        set_cells(game, flags, RevealedState::Flagged);
+    }
+
+    const CENTER: Pos = Pos { x: 1, y: 1 };
+
+    #[tokio::test]
+    async fn under_flagged_chord_does_nothing() {
+        for exact in [true, false] {
+            let mut game = chord_game(exact);
+            place_flags(&mut game, &[(0, 0)]);
+            game.chord(&Uuid::new_v4(), CENTER).await;
+            assert_eq!(game.field.revealed, 1);
+            assert!(!game.field.finished);
+        }
+    }
+
+    #[tokio::test]
+    async fn exactly_flagged_chord_reveals_neighbours() {
+        for exact in [true, false] {
+            let mut game = chord_game(exact);
+            place_flags(&mut game, &[(0, 0), (2, 0)]);
+            game.chord(&Uuid::new_v4(), CENTER).await;
+            assert!(game.field.finished && game.field.won);
+            assert_eq!(game.field.revealed, 7);
+        }
+    }
+
+    #[tokio::test]
+    async fn chord_without_hidden_neighbours_is_not_a_move() {
+        let mut game = chord_game(true);
+        place_flags(&mut game, &[(0, 0), (2, 0)]);
+        set_cells(
+            &mut game,
+            &[(1, 0), (0, 1), (2, 1), (0, 2), (1, 2), (2, 2)],
+            RevealedState::Revealed,
+        );
+        game.chord(&Uuid::new_v4(), CENTER).await;
+        assert_eq!(game.field.stats.moves, 0);
+        assert!(game.history.is_empty());
+    }
+
+    #[tokio::test]
+    async fn over_flagged_chord_depends_on_exact_flags() {
+        let mut game = chord_game(true);
+        place_flags(&mut game, &[(0, 0), (2, 0), (0, 1)]);
+        game.chord(&Uuid::new_v4(), CENTER).await;
+        assert_eq!(game.field.revealed, 1);
+
+        let mut game = chord_game(false);
+        place_flags(&mut game, &[(0, 0), (2, 0), (0, 1)]);
+        game.chord(&Uuid::new_v4(), CENTER).await;
+        assert!(game.field.finished && game.field.won);
+    }
+
+    #[tokio::test]
+    async fn chord_with_misplaced_flag_loses() {
+        let mut game = chord_game(true);
+        place_flags(&mut game, &[(0, 0), (0, 1)]);
+        game.chord(&Uuid::new_v4(), CENTER).await;
+        assert!(game.field.finished && !game.field.won);
+
+        let mut game = chord_game(false);
+        place_flags(&mut game, &[(0, 0), (0, 1), (0, 2)]);
+        game.chord(&Uuid::new_v4(), CENTER).await;
+        assert!(game.field.finished && !game.field.won);
+    }
+
+    #[test]
+    fn board_code_loads_layout_and_visibility() {
+        let mut game = chord_game(true);
+        place_flags(&mut game, &[(0, 0)]);
+        let code = game.board_code();
+
+        let loaded = Game::new(
+            GameParams {
+                code: Some(code.clone()),
+                ..Default::default()
+            },
+            Arc::new(Metrics::default()),
+            MaxCascadeRadius::default(),
+        );
+        let field = &loaded.field;
+        assert_eq!((field.width, field.height, field.bombs), (3, 3, 2));
+        assert_eq!(field.revealed, 1);
+        assert_eq!(field.cell(&CENTER).adjacent, 2);
+        assert_eq!(field.cells[0].revealed, RevealedState::Flagged);
+        assert_eq!(loaded.board_code(), code);
+        assert_eq!(field.params().code, Some(code));
+    }
+
+    #[test]
+    fn no_forced_move_before_anything_is_revealed() {
+        let game = game_with_board(&["*.*", "...", "..."], GameParams::default());
+        assert!(!game.field.has_forced_move());
+    }
+
+    #[test]
+    fn number_with_too_many_hidden_neighbours_forces_nothing() {
+        let mut game = chord_game(true);
+        assert!(!game.field.has_forced_move());
+
+        place_flags(&mut game, &[(0, 0)]);
+        assert!(!game.field.has_forced_move());
+    }
+
+    #[test]
+    fn satisfied_number_forces_reveals() {
+        let mut game = chord_game(true);
+        place_flags(&mut game, &[(0, 0), (2, 0)]);
+        assert!(game.field.has_forced_move());
+    }
+
+    #[test]
+    fn number_matching_its_hidden_neighbours_forces_flags() {
+        let mut game = game_with_board(&["*..", "..."], GameParams::default());
+        set_cells(
+            &mut game,
+            &[(1, 0), (2, 0), (0, 1), (1, 1), (2, 1)],
+            RevealedState::Revealed,
+        );
+        assert!(game.field.has_forced_move());
+
+        place_flags(&mut game, &[(0, 0)]);
+        assert!(!game.field.has_forced_move());
+    }
+
+    #[test]
+    fn fifty_fifty_has_no_forced_move() {
+        // Two hidden cells in the left column hide one bomb; both numbers show 1
+        let mut game = game_with_board(&["*.", ".."], GameParams::default());
+        set_cells(&mut game, &[(1, 0), (1, 1)], RevealedState::Revealed);
+        assert!(!game.field.has_forced_move());
+    }
+
+    fn flagged(x: usize) -> CellUpdate {
+        CellUpdate {
+            pos: Pos { x, y: 0 },
+            value: minesweeper_common::models::Cell::Flagged,
+            depth: None,
+        }
+    }
+
+    #[test]
+    fn update_log_returns_updates_after_a_revision() {
+        let mut log = UpdateLog::default();
+        for revision in 1..=3 {
+            log.push(revision, &[flagged(revision as usize)], 10);
+        }
+
+        let positions = |updates: Vec<CellUpdate>| -> Vec<usize> {
+            updates.iter().map(|update| update.pos.x).collect()
+        };
+        assert_eq!(log.since(1, 3).map(positions), Some(vec![2, 3]));
+        assert_eq!(log.since(0, 3).map(positions), Some(vec![1, 2, 3]));
+        assert_eq!(log.since(3, 3).map(positions), Some(Vec::new()));
+        assert!(log.since(4, 3).is_none());
+    }
+
+    #[test]
+    fn update_log_forgets_revisions_beyond_its_capacity() {
+        let mut log = UpdateLog::default();
+        for revision in 1..=3 {
+            log.push(revision, &[flagged(0), flagged(1)], 4);
+        }
+
+        assert!(log.since(0, 3).is_none());
+        assert_eq!(log.since(1, 3).map(|updates| updates.len()), Some(4));
+    }
+
+    #[tokio::test]
+    async fn undo_invalidates_logged_updates() {
+        let mut game = game_with_board(&["*..", "...", "..."], GameParams::default());
+        let stream = Uuid::new_v4();
+        game.flag(&stream, Pos { x: 0, y: 0 }).await;
+        game.flag(&stream, Pos { x: 1, y: 0 }).await;
+        assert_eq!(game.field.revision, 2);
+        assert_eq!(
+            game.updates.since(1, 2).map(|updates| updates.len()),
+            Some(1)
+        );
+
+        game.undo(&stream).await;
+        assert_eq!(game.field.revision, 3);
+        assert!(game.updates.since(2, 3).is_none());
+    }
+
+    #[tokio::test]
+    async fn undo_rolls_back_the_statistics() {
+        let mut game = game_with_board(&["*..", "...", "..."], GameParams::default());
+        let stream = Uuid::new_v4();
+        game.flag(&stream, Pos { x: 2, y: 2 }).await;
+        game.reveal(&stream, Pos { x: 0, y: 0 }).await;
+        assert!(game.field.finished && game.field.stats.finished_at.is_some());
+        assert_eq!(game.field.stats.moves, 2);
+
+        game.undo(&stream).await;
+        assert!(!game.field.finished && game.field.stats.finished_at.is_none());
+        assert_eq!(game.field.stats.moves, 1);
+        assert_eq!(game.field.stats.flags, 1);
+
+        game.undo(&stream).await;
+        assert_eq!(game.field.stats.moves, 0);
+        assert_eq!(game.field.stats.flags, 0);
+        assert_eq!(game.field.stats.reveals, 0);
+    }
+
+    fn opening_params(seed: u64) -> GameParams {
+        GameParams {
+            min_opening_size: 20,
+            seed: Some(seed),
+            ..Default::default()
         }
     }
+
+    #[tokio::test]
+    async fn first_reveal_opens_at_least_min_opening_size() {
+        for seed in 0..20 {
+            let mut game = Game::new(
+                opening_params(seed),
+                Arc::new(Metrics::default()),
+                MaxCascadeRadius::default(),
+            );
+            game.reveal(&Uuid::new_v4(), Pos { x: 4, y: 4 }).await;
+            assert!(
+                game.field.revealed >= 20,
+                "seed {} opened {} cells",
+                seed,
+                game.field.revealed
+            );
+        }
+    }
+
+    #[test]
+    fn regenerating_for_an_opening_keeps_flags() {
+        for seed in 0..20 {
+            let mut field = Field::new(opening_params(seed));
+            field.cells[0].revealed = RevealedState::Flagged;
+            field.cells[1].revealed = RevealedState::Marked;
+            field.ensure_opening(Pos { x: 8, y: 8 });
+            assert_eq!(field.cells[0].revealed, RevealedState::Flagged);
+            assert_eq!(field.cells[1].revealed, RevealedState::Marked);
+            assert_eq!(field.flags_placed(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn reveal_after_a_hint_keeps_the_hinted_cells() {
+        for seed in 0..20 {
+            let mut game = Game::new(
+                opening_params(seed),
+                Arc::new(Metrics::default()),
+                MaxCascadeRadius::default(),
+            );
+            let stream = Uuid::new_v4();
+            game.hint(&stream).await;
+            let hinted: Vec<usize> = (0..game.field.cells.len())
+                .filter(|&i| game.field.cells[i].revealed == RevealedState::Revealed)
+                .collect();
+            assert!(!hinted.is_empty());
+
+            game.reveal(&stream, Pos { x: 8, y: 8 }).await;
+            for &i in &hinted {
+                assert_eq!(game.field.cells[i].revealed, RevealedState::Revealed);
+                assert!(!game.field.cells[i].bomb);
+            }
+            let revealed = game
+                .field
+                .cells
+                .iter()
+                .filter(|cell| cell.revealed == RevealedState::Revealed)
+                .count();
+            if !game.field.finished || game.field.won {
+                assert_eq!(game.field.revealed, revealed, "seed {}", seed);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn summary_reflects_the_game_played() {
+        let mut game = game_with_board(&[".....", "..*..", "....."], GameParams::default());
+        let clock = MockClock::new();
+        game.field.clock = Arc::new(clock.clone());
+        let stream = Uuid::new_v4();
+
+        // Opens the left two columns
+        game.reveal(&stream, Pos { x: 0, y: 0 }).await;
+        game.flag(&stream, Pos { x: 4, y: 0 }).await;
+        game.flag(&stream, Pos { x: 4, y: 0 }).await;
+        clock.advance(Duration::from_millis(2500));
+        game.reveal(&stream, Pos { x: 2, y: 1 }).await;
+        assert!(game.field.finished && !game.field.won);
+
+        match game.field.summary_message(false) {
+            ServerMessage::GameSummary {
+                won,
+                elapsed_ms,
+                moves,
+                reveals,
+                flags,
+                board_cleared_pct,
+                efficiency,
+            } => {
+                assert!(!won);
+                assert_eq!(elapsed_ms, 2500);
+                assert_eq!(moves, 4);
+                assert_eq!(reveals, 2);
+                assert_eq!(flags, 2);
+                assert_eq!(board_cleared_pct, 6.0 * 100.0 / 14.0);
+                assert!(efficiency.is_none());
+            }
+            other => panic!("expected a summary, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn efficiency_stats_count_useful_and_wasted_clicks() {
+        // Two openings on the sides plus the numbers above and below the bomb: 3BV of 4
+        let params = GameParams {
+            efficiency_stats: true,
+            ..Default::default()
+        };
+        let mut game = game_with_board(&[".....", "..*..", "....."], params);
+        let clock = MockClock::new();
+        game.field.clock = Arc::new(clock.clone());
+        let stream = Uuid::new_v4();
+
+        game.reveal(&stream, Pos { x: 0, y: 0 }).await;
+        clock.advance(Duration::from_secs(1));
+        game.reveal(&stream, Pos { x: 2, y: 0 }).await;
+        // Neither counts: the cell is already revealed
+        game.reveal(&stream, Pos { x: 2, y: 0 }).await;
+        clock.advance(Duration::from_secs(1));
+        game.flag(&stream, Pos { x: 2, y: 1 }).await;
+        clock.advance(Duration::from_secs(1));
+        game.reveal(&stream, Pos { x: 2, y: 2 }).await;
+        clock.advance(Duration::from_secs(1));
+        game.reveal(&stream, Pos { x: 4, y: 0 }).await;
+        assert!(game.field.finished && game.field.won);
+
+        match game.field.summary_message(true) {
+            ServerMessage::GameSummary {
+                moves,
+                efficiency: Some(efficiency),
+                ..
+            } => {
+                assert_eq!(moves, 5);
+                assert_eq!(efficiency.three_bv, 4);
+                assert_eq!(efficiency.useful_clicks, 4);
+                assert_eq!(efficiency.wasted_clicks, 1);
+                assert_eq!(efficiency.three_bv_per_second, 1.0);
+            }
+            other => panic!("expected a summary with efficiency, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn flood_fill_clears_a_huge_empty_board() {
+        let mut field = empty_field(1000, 1000);
+        let mut updates = Vec::new();
+        field.reveal_flood(Pos { x: 500, y: 500 }, &mut updates);
+        assert_eq!(updates.len(), 1_000_000);
+        assert_eq!(field.revealed, 1_000_000);
+        assert!(field.has_won());
+    }
+
+    fn empty_field(width: usize, height: usize) -> Field {
+        Field::new(GameParams {
+            width,
+            height,
+            bombs: 0,
+            ..Default::default()
+        })
+    }
+
+    fn distance(a: Pos, b: Pos) -> usize {
+        a.x.abs_diff(b.x).max(a.y.abs_diff(b.y))
+    }
+
+    #[tokio::test]
+    async fn restart_keeps_the_server_cascade_radius() {
+        let mut game = Game::new(
+            GameParams::default(),
+            Arc::new(Metrics::default()),
+            MaxCascadeRadius(Some(3)),
+        );
+        assert_eq!(game.field.max_cascade_radius, Some(3));
+        game.restart(GameParams::default()).await;
+        assert_eq!(game.field.max_cascade_radius, Some(3));
+    }
+
+    #[test]
+    fn cascade_stops_at_max_cascade_radius() {
+        let mut field = empty_field(9, 9);
+        field.max_cascade_radius = Some(2);
+        let center = Pos { x: 4, y: 4 };
+
+        let mut updates = Vec::new();
+        field.reveal_flood(center, &mut updates);
+        assert_eq!(updates.len(), 25);
+        assert!(
+            updates
+                .iter()
+                .all(|update| distance(update.pos, center) <= 2)
+        );
+        assert!(!field.has_won());
+
+        // The frontier left hidden needs another click, which cascades from there
+        let mut updates = Vec::new();
+        field.reveal_flood(Pos { x: 7, y: 4 }, &mut updates);
+        assert!(!updates.is_empty());
+        assert!(
+            updates
+                .iter()
+                .all(|update| distance(update.pos, center) > 2)
+        );
+    }
+
+    #[test]
+    fn cascade_depths_grow_outward_from_the_click() {
+        let mut field = empty_field(7, 5);
+        let start = Pos { x: 1, y: 2 };
+
+        let mut updates = Vec::new();
+        field.reveal_flood(start, &mut updates);
+        assert_eq!(updates.len(), 35);
+        for update in &updates {
+            assert_eq!(update.depth, Some(distance(update.pos, start)));
+        }
+        assert!(
+            updates
+                .windows(2)
+                .all(|pair| pair[0].depth <= pair[1].depth)
+        );
+    }
+
+    fn game_with_clock() -> (Game, MockClock) {
+        let clock = MockClock::new();
+        let game = Game::with_clock(
+            GameParams::default(),
+            Arc::new(Metrics::default()),
+            Arc::new(clock.clone()),
+        );
+        (game, clock)
+    }
+
+    #[test]
+    fn idle_game_without_connections_is_removed_after_timeout() {
+        let (game, clock) = game_with_clock();
+        clock.advance(Duration::from_secs(600));
+        assert_eq!(game.cleanup_action(600, 3600, 60), CleanupAction::Keep);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(game.cleanup_action(600, 3600, 60), CleanupAction::Remove);
+    }
+
+    #[tokio::test]
+    async fn activity_postpones_cleanup() {
+        let (mut game, clock) = game_with_clock();
+        clock.advance(Duration::from_secs(500));
+        game.flag(&Uuid::new_v4(), Pos { x: 0, y: 0 }).await;
+        clock.advance(Duration::from_secs(500));
+        assert_eq!(game.cleanup_action(600, 3600, 60), CleanupAction::Keep);
+        assert_eq!(game.summary(String::new()).idle_secs, 500);
+    }
+
+    #[tokio::test]
+    async fn timer_follows_the_game_clock_across_restarts() {
+        let (mut game, clock) = game_with_clock();
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(game.field.elapsed(), Duration::ZERO);
+
+        game.flag(&Uuid::new_v4(), Pos { x: 0, y: 0 }).await;
+        clock.advance(Duration::from_secs(42));
+        assert_eq!(game.field.elapsed(), Duration::from_secs(42));
+
+        game.restart(GameParams::default()).await;
+        game.flag(&Uuid::new_v4(), Pos { x: 0, y: 0 }).await;
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(game.field.elapsed(), Duration::from_secs(5));
+    }
+
+    /// Wraps a message but refuses to serialize, like a payload holding a map with
+    /// non-string keys would
+    struct Unserializable(ServerMessage);
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom(format!(
+                "cannot serialize {}",
+                self.0.kind()
+            )))
+        }
+    }
+
+    #[test]
+    fn serialization_failure_is_counted() {
+        let metrics = Metrics::default();
+        let message = ServerMessage::Presence { players: 1 };
+        assert!(render_server_message(&message, FrameFormat::Text, &metrics).is_some());
+
+        let failing = Unserializable(message);
+        assert!(render(&failing, failing.0.kind(), FrameFormat::Text, &metrics).is_none());
+
+        let mut out = String::new();
+        metrics.render(&mut out, 0, 0).unwrap();
+        assert!(out.contains("minesweeper_serialization_failures_total 1\n"));
+    }
+
+    #[test]
+    fn broadcast_renders_once_for_every_stream() {
+        let metrics = Metrics::default();
+        let message = ServerMessage::Presence { players: 3 };
+        let frames = Frames::new(&message, &metrics);
+        assert!(frames.text.get().is_none());
+
+        let first = frames.get(FrameFormat::Text).unwrap();
+        let rendered = frames.text.get().cloned();
+        for _ in 0..100 {
+            assert_eq!(frames.get(FrameFormat::Text), Some(first.clone()));
+        }
+        // The cell was filled by the first stream and reused for the other hundred
+        assert_eq!(frames.text.get().cloned(), rendered);
+        #[cfg(feature = "binary")]
+        assert!(frames.binary.get().is_none());
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn binary_connections_get_messagepack_frames() {
+        let metrics = Metrics::default();
+        let message = ServerMessage::Presence { players: 4 };
+        let frames = Frames::new(&message, &metrics);
+
+        assert!(matches!(
+            frames.get(FrameFormat::Text),
+            Some(Message::Text(_))
+        ));
+        let Some(Message::Binary(bytes)) = frames.get(FrameFormat::Binary) else {
+            panic!("expected a binary frame");
+        };
+        let decoded: ServerMessage = minesweeper_common::protocol::from_bytes(&bytes).unwrap();
+        assert!(matches!(decoded, ServerMessage::Presence { players: 4 }));
+    }
+
+    #[tokio::test]
+    async fn handle_message_dispatches_every_action() {
+        let mut game = chord_game(true);
+        let stream = Uuid::new_v4();
+        let limits = BoardLimits::default();
+        let sizes = AllowedBoardSizes::default();
+        let max_cells = MaxBoardCells::default();
+        let cell = |game: &Game, x, y| game.field.cell(&Pos { x, y }).revealed;
+
+        for message in [
+            ClientMessage::Flag {
+                pos: Pos { x: 0, y: 0 },
+            },
+            ClientMessage::Flag {
+                pos: Pos { x: 2, y: 0 },
+            },
+            ClientMessage::Undo,
+        ] {
+            game.handle_message(&stream, message, &limits, &sizes, max_cells)
+                .await;
+        }
+        assert_eq!(cell(&game, 0, 0), RevealedState::Flagged);
+        assert_eq!(cell(&game, 2, 0), RevealedState::Hidden);
+
+        game.handle_message(&stream, ClientMessage::Pause, &limits, &sizes, max_cells)
+            .await;
+        assert!(game.field.paused);
+        game.handle_message(&stream, ClientMessage::Resume, &limits, &sizes, max_cells)
+            .await;
+        assert!(!game.field.paused);
+
+        // Read-only messages leave the board alone
+        let revision = game.field.revision;
+        for message in [
+            ClientMessage::Viewport {
+                top_left: Pos { x: 0, y: 0 },
+                bottom_right: Pos { x: 2, y: 2 },
+            },
+            ClientMessage::GetState,
+            ClientMessage::Chat {
+                text: "hi".to_string(),
+            },
+        ] {
+            game.handle_message(&stream, message, &limits, &sizes, max_cells)
+                .await;
+        }
+        assert_eq!(game.field.revision, revision);
+
+        game.handle_message(&stream, ClientMessage::Hint, &limits, &sizes, max_cells)
+            .await;
+        assert_eq!(cell(&game, 1, 0), RevealedState::Revealed);
+        let pos = Pos { x: 0, y: 1 };
+        game.handle_message(
+            &stream,
+            ClientMessage::Reveal { pos },
+            &limits,
+            &sizes,
+            max_cells,
+        )
+        .await;
+        assert_eq!(cell(&game, 0, 1), RevealedState::Revealed);
+
+        game.handle_message(
+            &stream,
+            ClientMessage::Flag {
+                pos: Pos { x: 2, y: 0 },
+            },
+            &limits,
+            &sizes,
+            max_cells,
+        )
+        .await;
+        game.handle_message(
+            &stream,
+            ClientMessage::Chord { pos: CENTER },
+            &limits,
+            &sizes,
+            max_cells,
+        )
+        .await;
+        assert!(game.field.won);
+
+        let params = GameParams {
+            width: 4,
+            height: 4,
+            bombs: 2,
+            ..Default::default()
+        };
+        game.handle_message(
+            &stream,
+            ClientMessage::Restart { params },
+            &limits,
+            &sizes,
+            max_cells,
+        )
+        .await;
+        assert_eq!((game.field.width, game.field.height), (4, 4));
+        assert!(!game.field.finished);
+    }
+
+    #[tokio::test]
+    async fn handle_message_ignores_restarts_outside_the_limits() {
+        let mut game = chord_game(true);
+        let stream = Uuid::new_v4();
+        let limits = BoardLimits {
+            max_width: Some(5),
+            ..Default::default()
+        };
+        let sizes = AllowedBoardSizes::default();
+        let max_cells = MaxBoardCells::default();
+
+        for params in [
+            GameParams {
+                width: 6,
+                height: 4,
+                bombs: 2,
+                ..Default::default()
+            },
+            GameParams {
+                width: 4,
+                height: 4,
+                bombs: 16,
+                ..Default::default()
+            },
+            GameParams {
+                code: Some("not a code".to_string()),
+                ..Default::default()
+            },
+        ] {
+            game.handle_message(
+                &stream,
+                ClientMessage::Restart { params },
+                &limits,
+                &sizes,
+                max_cells,
+            )
+            .await;
+        }
+        assert_eq!((game.field.width, game.field.height), (3, 3));
+        assert_eq!(game.field.revealed, 1);
+    }
+
+    #[tokio::test]
+    async fn only_applied_actions_count_as_moves() {
+        let mut game = chord_game(true);
+        let stream = Uuid::new_v4();
+        // Revealed, flagged, unsatisfied or off-board targets leave the game unchanged
+        game.reveal(&stream, CENTER).await;
+        game.flag(&stream, CENTER).await;
+        game.chord(&stream, CENTER).await;
+        game.reveal(&stream, Pos { x: 5, y: 5 }).await;
+        assert_eq!(game.field.stats.moves, 0);
+
+        game.flag(&stream, Pos { x: 0, y: 0 }).await;
+        assert_eq!(game.field.stats.moves, 1);
+        game.reveal(&stream, Pos { x: 0, y: 0 }).await;
+        game.chord(&stream, CENTER).await;
+        assert_eq!(game.field.stats.moves, 1);
+
+        game.flag(&stream, Pos { x: 2, y: 0 }).await;
+        game.chord(&stream, CENTER).await;
+        assert!(game.field.won);
+        assert_eq!(game.field.stats.moves, 3);
+
+        game.reveal(&stream, Pos { x: 0, y: 2 }).await;
+        assert_eq!(game.field.stats.moves, 3);
+        assert!(matches!(
+            game.field.update_message(Vec::new(), true, false),
+            ServerMessage::Update { moves: 3, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn reveals_and_chords_are_blocked_while_paused() {
+        let mut game = chord_game(true);
+        let stream = Uuid::new_v4();
+        place_flags(&mut game, &[(0, 0), (2, 0)]);
+        game.set_paused(&stream, true).await;
+
+        game.reveal(&stream, Pos { x: 0, y: 2 }).await;
+        game.chord(&stream, CENTER).await;
+        game.hint(&stream).await;
+        assert_eq!(game.field.revealed, 1);
+        assert_eq!(game.field.stats.moves, 0);
+
+        game.set_paused(&stream, false).await;
+        game.chord(&stream, CENTER).await;
+        assert!(game.field.won);
+    }
+
+    #[tokio::test]
+    async fn flags_while_paused_is_a_game_setting() {
+        for flags_while_paused in [true, false] {
+            let params = GameParams {
+                flags_while_paused,
+                ..Default::default()
+            };
+            let mut game = game_with_board(&["*..", "...", "..."], params);
+            let stream = Uuid::new_v4();
+            game.set_paused(&stream, true).await;
+            game.flag(&stream, Pos { x: 0, y: 0 }).await;
+            assert_eq!(game.field.flags_placed(), flags_while_paused as usize);
+        }
+    }
+
+    #[tokio::test]
+    async fn timer_stops_while_paused() {
+        let (mut game, clock) = game_with_clock();
+        let stream = Uuid::new_v4();
+        game.flag(&stream, Pos { x: 0, y: 0 }).await;
+        clock.advance(Duration::from_secs(10));
+
+        game.set_paused(&stream, true).await;
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(game.field.elapsed(), Duration::from_secs(10));
+
+        game.set_paused(&stream, false).await;
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(game.field.elapsed(), Duration::from_secs(15));
+    }
+
+    #[tokio::test]
+    async fn winning_cancels_the_time_limit() {
+        let params = GameParams {
+            time_limit_secs: Some(60),
+            ..Default::default()
+        };
+        let shared = game_with_board(&["*..", "...", "..."], params).into_shared();
+        let mut game = shared.lock().await;
+        let stream = Uuid::new_v4();
+
+        game.reveal(&stream, Pos { x: 1, y: 0 }).await;
+        assert!(game.time_limit.is_some());
+
+        set_cells(
+            &mut game,
+            &[(2, 0), (0, 1), (1, 1), (2, 1), (0, 2), (1, 2)],
+            RevealedState::Revealed,
+        );
+        game.reveal(&stream, Pos { x: 2, y: 2 }).await;
+        assert!(game.field.won);
+        assert!(game.time_limit.is_none());
+    }
+
+    #[tokio::test]
+    async fn undoing_a_loss_resumes_the_time_limit_until_it_runs_out() {
+        let clock = MockClock::new();
+        let params = GameParams {
+            time_limit_secs: Some(60),
+            ..Default::default()
+        };
+        let mut game = game_with_board(&["*..", "...", "..."], params);
+        game.field.clock = Arc::new(clock.clone());
+        let shared = game.into_shared();
+        let mut game = shared.lock().await;
+        let stream = Uuid::new_v4();
+
+        game.reveal(&stream, Pos { x: 1, y: 0 }).await;
+        game.reveal(&stream, Pos { x: 0, y: 0 }).await;
+        assert!(game.field.finished && game.time_limit.is_none());
+
+        game.undo(&stream).await;
+        assert!(!game.field.finished);
+        assert!(game.time_limit.is_some());
+
+        game.reveal(&stream, Pos { x: 0, y: 0 }).await;
+        assert!(game.field.finished);
+        clock.advance(Duration::from_secs(60));
+        game.undo(&stream).await;
+        assert!(game.field.finished);
+        assert_eq!(game.history.len(), 2);
+    }
 }
@@ -0,0 +1,107 @@
+use std::{
+    fmt::{self, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Server-wide counters exposed on `GET /metrics`. Gauges for active games and
+/// connections are read from `Games` and `ConnectionLimiter` when rendering.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    games_created: AtomicU64,
+    reveals: AtomicU64,
+    flags: AtomicU64,
+    wins: AtomicU64,
+    losses: AtomicU64,
+    serialization_failures: AtomicU64,
+}
+
+impl Metrics {
+    pub fn record_game_created(&self) {
+        self.games_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_reveal(&self) {
+        self.reveals.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_flag(&self) {
+        self.flags.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_win(&self) {
+        self.wins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_loss(&self) {
+        self.losses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_serialization_failure(&self) {
+        self.serialization_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Writes all metrics in the Prometheus text exposition format.
+    pub fn render(
+        &self,
+        out: &mut impl Write,
+        active_games: usize,
+        active_connections: usize,
+    ) -> fmt::Result {
+        let gauge = |out: &mut dyn Write, name: &str, help: &str, value: u64| {
+            writeln!(out, "# HELP {} {}", name, help)?;
+            writeln!(out, "# TYPE {} gauge", name)?;
+            writeln!(out, "{} {}", name, value)
+        };
+        let counter = |out: &mut dyn Write, name: &str, help: &str, value: &AtomicU64| {
+            writeln!(out, "# HELP {} {}", name, help)?;
+            writeln!(out, "# TYPE {} counter", name)?;
+            writeln!(out, "{} {}", name, value.load(Ordering::Relaxed))
+        };
+
+        gauge(
+            out,
+            "minesweeper_active_games",
+            "Games currently held by the server.",
+            active_games as u64,
+        )?;
+        gauge(
+            out,
+            "minesweeper_active_connections",
+            "Open WebSocket connections.",
+            active_connections as u64,
+        )?;
+        counter(
+            out,
+            "minesweeper_games_created_total",
+            "Games created via /create.",
+            &self.games_created,
+        )?;
+        counter(
+            out,
+            "minesweeper_reveals_total",
+            "Reveal actions applied.",
+            &self.reveals,
+        )?;
+        counter(
+            out,
+            "minesweeper_flags_total",
+            "Flag actions that changed a cell.",
+            &self.flags,
+        )?;
+        counter(out, "minesweeper_wins_total", "Games won.", &self.wins)?;
+        counter(out, "minesweeper_losses_total", "Games lost.", &self.losses)?;
+        counter(
+            out,
+            "minesweeper_serialization_failures_total",
+            "Server messages dropped because they failed to serialize.",
+            &self.serialization_failures,
+        )
+    }
+}
+
+pub fn create_metrics() -> Arc<Metrics> {
+    Arc::new(Metrics::default())
+}
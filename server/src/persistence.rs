@@ -0,0 +1,264 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    clock::system_clock,
+    data::{Cell, Field, RevealedState, Stats},
+    logic::{Game, Games, MaxCascadeRadius},
+    metrics::Metrics,
+};
+
+/// On-disk form of a game. Connections and undo history are not saved; clients
+/// reconnect and resync through the normal `Init`.
+#[derive(Serialize, Deserialize, Debug)]
+struct PersistedGame {
+    id: String,
+    width: usize,
+    height: usize,
+    bombs: usize,
+    min_opening_size: usize,
+    efficiency_stats: bool,
+    #[serde(default = "default_true")]
+    enable_question_marks: bool,
+    #[serde(default = "default_true")]
+    auto_flag_on_win: bool,
+    #[serde(default)]
+    public: bool,
+    #[serde(default)]
+    max_players: usize,
+    #[serde(default = "default_true")]
+    chord_requires_exact_flags: bool,
+    #[serde(default)]
+    no_guess: bool,
+    #[serde(default = "default_true")]
+    flags_while_paused: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time_limit_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    seed: u64,
+    /// Position in the seeded RNG stream, so a board regenerated on the first reveal
+    /// comes out the same as it would have before the restart
+    rng_word_pos: u128,
+    revealed: usize,
+    finished: bool,
+    #[serde(default)]
+    won: bool,
+    #[serde(default)]
+    paused: bool,
+    #[serde(default)]
+    revision: u64,
+    cells: Vec<PersistedCell>,
+    moves: u64,
+    useful_clicks: usize,
+    reveals: usize,
+    flags: usize,
+    hints: usize,
+    /// Play time so far, `None` if no move has been made yet
+    elapsed_ms: Option<u64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PersistedCell {
+    bomb: bool,
+    adjacent: u8,
+    revealed: RevealedState,
+}
+
+impl PersistedGame {
+    fn from_field(id: String, field: &Field) -> Self {
+        Self {
+            id,
+            width: field.width,
+            height: field.height,
+            bombs: field.bombs,
+            min_opening_size: field.min_opening_size,
+            efficiency_stats: field.efficiency_stats,
+            enable_question_marks: field.enable_question_marks,
+            auto_flag_on_win: field.auto_flag_on_win,
+            public: field.public,
+            max_players: field.max_players,
+            chord_requires_exact_flags: field.chord_requires_exact_flags,
+            no_guess: field.no_guess,
+            flags_while_paused: field.flags_while_paused,
+            time_limit_secs: field.time_limit_secs,
+            code: field.code.clone(),
+            seed: field.seed,
+            rng_word_pos: field.rng.get_word_pos(),
+            revealed: field.revealed,
+            finished: field.finished,
+            won: field.won,
+            paused: field.paused,
+            revision: field.revision,
+            cells: field
+                .cells
+                .iter()
+                .map(|cell| PersistedCell {
+                    bomb: cell.bomb,
+                    adjacent: cell.adjacent,
+                    revealed: cell.revealed,
+                })
+                .collect(),
+            moves: field.stats.moves,
+            useful_clicks: field.stats.useful_clicks,
+            reveals: field.stats.reveals,
+            flags: field.stats.flags,
+            hints: field.stats.hints,
+            elapsed_ms: field
+                .stats
+                .started_at
+                .map(|_| field.elapsed().as_millis() as u64),
+        }
+    }
+
+    /// Rebuilds the field, or returns `None` if the cell count doesn't match the size.
+    fn into_field(self, max_cascade_radius: MaxCascadeRadius) -> Option<Field> {
+        if self.width.checked_mul(self.height) != Some(self.cells.len()) {
+            return None;
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        rng.set_word_pos(self.rng_word_pos);
+
+        // Instants can't be stored, so rebase the clock on the time played so far
+        let clock = system_clock();
+        let now = clock.now();
+        let started_at = self
+            .elapsed_ms
+            .map(|ms| now.checked_sub(Duration::from_millis(ms)).unwrap_or(now));
+        let finished_at = started_at.filter(|_| self.finished).map(|_| now);
+        let paused_at = self.paused.then_some(now);
+
+        Some(Field {
+            width: self.width,
+            height: self.height,
+            bombs: self.bombs,
+            min_opening_size: self.min_opening_size,
+            efficiency_stats: self.efficiency_stats,
+            enable_question_marks: self.enable_question_marks,
+            auto_flag_on_win: self.auto_flag_on_win,
+            public: self.public,
+            max_players: self.max_players,
+            chord_requires_exact_flags: self.chord_requires_exact_flags,
+            no_guess: self.no_guess,
+            flags_while_paused: self.flags_while_paused,
+            time_limit_secs: self.time_limit_secs,
+            code: self.code,
+            max_cascade_radius: max_cascade_radius.0,
+            seed: self.seed,
+            rng,
+            revealed: self.revealed,
+            finished: self.finished,
+            won: self.won,
+            paused: self.paused,
+            revision: self.revision,
+            cells: self
+                .cells
+                .into_iter()
+                .map(|cell| Cell {
+                    bomb: cell.bomb,
+                    adjacent: cell.adjacent,
+                    revealed: cell.revealed,
+                })
+                .collect(),
+            stats: Stats {
+                started_at,
+                finished_at,
+                paused_at,
+                paused_for: Duration::ZERO,
+                moves: self.moves,
+                useful_clicks: self.useful_clicks,
+                reveals: self.reveals,
+                flags: self.flags,
+                hints: self.hints,
+            },
+            clock,
+        })
+    }
+}
+
+/// File games are saved to on shutdown and reloaded from on startup (`PERSIST_PATH`).
+/// Persistence is disabled when unset.
+pub fn persist_path() -> Option<PathBuf> {
+    env::var_os("PERSIST_PATH").map(PathBuf::from)
+}
+
+/// Loads saved games into `games`. A missing file is not an error; an unreadable one is
+/// logged and skipped so a bad snapshot never keeps the server from starting.
+pub fn load_games(
+    path: &Path,
+    games: &Games,
+    metrics: &Arc<Metrics>,
+    max_cascade_radius: MaxCascadeRadius,
+) {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            info!("No saved games at {}", path.display());
+            return;
+        }
+        Err(e) => {
+            error!("Failed to read saved games from {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let saved: Vec<PersistedGame> = match serde_json::from_str(&json) {
+        Ok(saved) => saved,
+        Err(e) => {
+            error!("Failed to parse saved games in {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let mut loaded = 0;
+    for game in saved {
+        let id = game.id.clone();
+        let Some(field) = game.into_field(max_cascade_radius) else {
+            warn!("Skipping saved game {} with inconsistent board size", id);
+            continue;
+        };
+        let game = Game::from_field(field, metrics.clone());
+        games.insert(id, game.into_shared());
+        loaded += 1;
+    }
+
+    info!("Restored {} games from {}", loaded, path.display());
+}
+
+/// Writes every game to `path`. The snapshot goes to a temporary file first and is
+/// renamed into place, so a crash mid-write leaves the previous snapshot intact.
+pub async fn save_games(path: &Path, games: &Games) -> io::Result<()> {
+    let entries: Vec<_> = games
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+
+    let mut saved = Vec::with_capacity(entries.len());
+    for (id, game) in entries {
+        let game = game.lock().await;
+        saved.push(PersistedGame::from_field(id, game.field()));
+    }
+
+    let json = serde_json::to_string(&saved).map_err(io::Error::other)?;
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    tokio::fs::write(&tmp_path, json).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+
+    info!("Saved {} games to {}", saved.len(), path.display());
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use std::{env, str::FromStr, sync::Arc};
+
+use minesweeper_common::models::{BoardLimits, GameParams};
+use tracing::{info, warn};
+
+/// A board preset in `WxHxB` form, e.g. `16x16x40`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BoardSize {
+    pub width: usize,
+    pub height: usize,
+    pub bombs: usize,
+}
+
+impl FromStr for BoardSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split(['x', 'X']).collect();
+        let [width, height, bombs] = parts.as_slice() else {
+            return Err(format!("expected WxHxB, got '{}'", s));
+        };
+
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<usize>()
+                .map_err(|e| format!("invalid number '{}' in '{}': {}", value, s, e))
+        };
+
+        Ok(Self {
+            width: parse(width)?,
+            height: parse(height)?,
+            bombs: parse(bombs)?,
+        })
+    }
+}
+
+impl BoardSize {
+    pub fn matches(&self, params: &GameParams) -> bool {
+        self.width == params.width && self.height == params.height && self.bombs == params.bombs
+    }
+}
+
+/// Boards `/create` and restarts may use (`ALLOWED_BOARD_SIZES`, comma-separated `WxHxB`
+/// entries). `None` when unset or empty, meaning any board size is allowed.
+#[derive(Debug, Clone, Default)]
+pub struct AllowedBoardSizes(pub Option<Arc<[BoardSize]>>);
+
+impl AllowedBoardSizes {
+    /// Parses `ALLOWED_BOARD_SIZES` once at startup, warning about invalid entries.
+    pub fn from_env() -> Self {
+        let sizes = env::var("ALLOWED_BOARD_SIZES")
+            .ok()
+            .and_then(|value| parse_board_sizes(&value));
+        if let Some(sizes) = &sizes {
+            info!("Allowed board sizes: {:?}", sizes);
+        }
+        Self(sizes.map(Arc::from))
+    }
+
+    /// Whether `params` match one of the presets; anything goes without an allowlist.
+    pub fn allows(&self, params: &GameParams) -> bool {
+        match &self.0 {
+            Some(sizes) => sizes.iter().any(|size| size.matches(params)),
+            None => true,
+        }
+    }
+}
+
+fn parse_board_sizes(value: &str) -> Option<Vec<BoardSize>> {
+    let sizes: Vec<BoardSize> = value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| match entry.parse() {
+            Ok(size) => Some(size),
+            Err(e) => {
+                warn!("Ignoring invalid ALLOWED_BOARD_SIZES entry: {}", e);
+                None
+            }
+        })
+        .collect();
+
+    if sizes.is_empty() { None } else { Some(sizes) }
+}
+
+/// Largest `width * height` a game may have (`MAX_BOARD_CELLS`, default 1,000,000).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxBoardCells(pub usize);
+
+impl Default for MaxBoardCells {
+    fn default() -> Self {
+        Self(1_000_000)
+    }
+}
+
+impl MaxBoardCells {
+    /// Reads `MAX_BOARD_CELLS` once at startup, warning about an invalid value.
+    pub fn from_env() -> Self {
+        let Ok(value) = env::var("MAX_BOARD_CELLS") else {
+            return Self::default();
+        };
+        match value.trim().parse::<usize>() {
+            Ok(cells) if cells > 0 => {
+                info!("Limiting boards to {} cells", cells);
+                Self(cells)
+            }
+            _ => {
+                warn!("Ignoring invalid MAX_BOARD_CELLS '{}'", value);
+                Self::default()
+            }
+        }
+    }
+
+    /// Whether the board fits. Dimensions whose product overflows `usize` are always
+    /// too large.
+    pub fn allows(&self, params: &GameParams) -> bool {
+        params
+            .width
+            .checked_mul(params.height)
+            .is_some_and(|cells| cells <= self.0)
+    }
+}
+
+/// Reads the custom board bounds once at startup:
+///
+/// - `MAX_BOARD_WIDTH` / `MAX_BOARD_HEIGHT`: largest width and height (default unlimited)
+/// - `MAX_BOMB_DENSITY`: largest fraction of bombs, above `0` and at most `1` (default
+///   unlimited)
+pub fn board_limits_from_env() -> BoardLimits {
+    let limits = BoardLimits {
+        max_width: env_dimension("MAX_BOARD_WIDTH"),
+        max_height: env_dimension("MAX_BOARD_HEIGHT"),
+        max_bomb_density: env::var("MAX_BOMB_DENSITY").ok().and_then(|value| {
+            match value.trim().parse::<f64>() {
+                Ok(density) if density > 0.0 && density <= 1.0 => Some(density),
+                _ => {
+                    warn!("Ignoring invalid MAX_BOMB_DENSITY '{}'", value);
+                    None
+                }
+            }
+        }),
+    };
+    if limits != BoardLimits::default() {
+        info!("Board limits: {:?}", limits);
+    }
+    limits
+}
+
+fn env_dimension(name: &str) -> Option<usize> {
+    let value = env::var(name).ok()?;
+    match value.trim().parse::<usize>() {
+        Ok(parsed) if parsed > 0 => Some(parsed),
+        _ => {
+            warn!("Ignoring invalid {} '{}'", name, value);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(width: usize, height: usize) -> GameParams {
+        GameParams {
+            width,
+            height,
+            bombs: 10,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn allowlist_parses_presets_and_skips_invalid_entries() {
+        let sizes = parse_board_sizes("9x9x10, 16X16x40,,oops,30x16").unwrap();
+        assert_eq!(
+            sizes,
+            [
+                BoardSize {
+                    width: 9,
+                    height: 9,
+                    bombs: 10
+                },
+                BoardSize {
+                    width: 16,
+                    height: 16,
+                    bombs: 40
+                }
+            ]
+        );
+        assert_eq!(parse_board_sizes(" , oops"), None);
+    }
+
+    #[test]
+    fn active_allowlist_rejects_non_presets() {
+        let sizes = parse_board_sizes("9x9x10,16x16x40").unwrap();
+        let preset = GameParams {
+            width: 16,
+            height: 16,
+            bombs: 40,
+            ..Default::default()
+        };
+        let custom = GameParams {
+            bombs: 41,
+            ..preset.clone()
+        };
+
+        let allowed = AllowedBoardSizes(Some(sizes.into()));
+
+        assert!(allowed.allows(&preset));
+        assert!(!allowed.allows(&custom));
+        assert!(AllowedBoardSizes::default().allows(&custom));
+    }
+
+    #[test]
+    fn board_limit_checks_the_cell_count() {
+        let max_cells = MaxBoardCells::default();
+        assert!(max_cells.allows(&params(50, 50)));
+        assert!(max_cells.allows(&params(1000, 1000)));
+        assert!(!max_cells.allows(&params(2000, 2000)));
+        assert!(!max_cells.allows(&params(usize::MAX, 2)));
+        assert!(!MaxBoardCells(100).allows(&params(20, 20)));
+    }
+}
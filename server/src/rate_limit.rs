@@ -1,12 +1,97 @@
 use std::{
     env,
     net::IpAddr,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
 use dashmap::DashMap;
-use rocket::{State, http::Status};
-use tracing::{debug, instrument, warn};
+use rocket::{
+    Request, Response, State,
+    http::Status,
+    response::{self, Responder},
+};
+use tracing::{debug, info, instrument, warn};
+
+use crate::clock::{Clock, system_clock};
+
+/// Token bucket settings, parsed once at startup and kept in Rocket state so the
+/// `/create` hot path never touches the environment.
+///
+/// - `RATE_LIMIT_ENABLED`: `false`, `0`, `no` or `off` disables limiting (default on)
+/// - `RATE_LIMIT_CAPACITY`: bucket size (falls back to `RATE_LIMIT_GAMES_PER_MINUTE`, default 10)
+/// - `RATE_LIMIT_REFILL_TOKENS`: tokens added per window (default: the capacity)
+/// - `RATE_LIMIT_WINDOW_SECS`: refill window, fractions allowed (default 60)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub capacity: u32,
+    pub refill_rate: u32,
+    pub refill_interval: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            capacity: 10,
+            refill_rate: 10,
+            refill_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        let capacity = env_positive("RATE_LIMIT_CAPACITY")
+            .or_else(|| env_positive("RATE_LIMIT_GAMES_PER_MINUTE"))
+            .unwrap_or(default.capacity);
+        let refill_rate = env_positive("RATE_LIMIT_REFILL_TOKENS").unwrap_or(capacity);
+        let refill_interval = match env::var("RATE_LIMIT_WINDOW_SECS") {
+            Err(_) => default.refill_interval,
+            Ok(value) => match value.trim().parse::<f64>() {
+                Ok(secs) if secs.is_finite() && secs > 0.0 => Duration::from_secs_f64(secs),
+                _ => {
+                    warn!(
+                        "Ignoring invalid RATE_LIMIT_WINDOW_SECS '{}', using {:?}",
+                        value, default.refill_interval
+                    );
+                    default.refill_interval
+                }
+            },
+        };
+
+        let config = Self {
+            enabled: rate_limit_enabled(),
+            capacity,
+            refill_rate,
+            refill_interval,
+        };
+        if config.enabled {
+            info!(
+                "Rate limit: {} tokens, refilling {} every {:?}",
+                config.capacity, config.refill_rate, config.refill_interval
+            );
+        } else {
+            info!("Rate limiting disabled");
+        }
+        config
+    }
+}
+
+/// Parses a positive integer env var, warning about and ignoring invalid values.
+fn env_positive(name: &str) -> Option<u32> {
+    let value = env::var(name).ok()?;
+    match value.trim().parse::<u32>() {
+        Ok(parsed) if parsed > 0 => Some(parsed),
+        _ => {
+            warn!("Ignoring invalid {} '{}'", name, value);
+            None
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct TokenBucket {
@@ -15,26 +100,31 @@ pub struct TokenBucket {
     capacity: u32,
     refill_rate: u32,
     refill_interval: Duration,
+    clock: Arc<dyn Clock>,
 }
 
 impl TokenBucket {
-    fn new(capacity: u32, refill_rate: u32, refill_interval: Duration) -> Self {
+    fn new(config: &RateLimitConfig) -> Self {
+        Self::with_clock(config, system_clock())
+    }
+
+    /// A full bucket that refills according to `clock` instead of the system clock
+    pub fn with_clock(config: &RateLimitConfig, clock: Arc<dyn Clock>) -> Self {
         debug!(
-            "Creating new token bucket: capacity={}, refill_rate={}, interval={}s",
-            capacity,
-            refill_rate,
-            refill_interval.as_secs()
+            "Creating new token bucket: capacity={}, refill_rate={}, interval={:?}",
+            config.capacity, config.refill_rate, config.refill_interval
         );
         Self {
-            last_refill: Instant::now(),
-            tokens: capacity,
-            capacity,
-            refill_rate,
-            refill_interval,
+            last_refill: clock.now(),
+            tokens: config.capacity,
+            capacity: config.capacity,
+            refill_rate: config.refill_rate,
+            refill_interval: config.refill_interval,
+            clock,
         }
     }
 
-    fn try_consume(&mut self) -> bool {
+    pub(crate) fn try_consume(&mut self) -> bool {
         self.refill();
         if self.tokens > 0 {
             self.tokens -= 1;
@@ -46,51 +136,281 @@ impl TokenBucket {
         }
     }
 
+    pub fn tokens(&self) -> u32 {
+        self.tokens
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Whole seconds until the next refill adds a token, rounded up; `0` if a token is
+    /// available now.
+    pub fn seconds_until_token(&self) -> u64 {
+        if self.tokens > 0 {
+            return 0;
+        }
+
+        let elapsed = self.clock.now().duration_since(self.last_refill);
+        let remaining = self.refill_interval.saturating_sub(elapsed);
+        remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+    }
+
+    fn reset(&mut self) {
+        self.tokens = self.capacity;
+        self.last_refill = self.clock.now();
+    }
+
+    /// Adds `refill_rate` tokens per whole `refill_interval` elapsed. Works in nanoseconds
+    /// so sub-second intervals refill too, and keeps the partial interval so refills
+    /// stay on schedule.
     fn refill(&mut self) {
-        let now = Instant::now();
+        let now = self.clock.now();
         let elapsed = now.duration_since(self.last_refill);
-        let intervals = elapsed.as_secs() / self.refill_interval.as_secs();
+        let intervals = elapsed.as_nanos() / self.refill_interval.as_nanos().max(1);
+        if intervals == 0 {
+            return;
+        }
 
-        if intervals > 0 {
-            let old_tokens = self.tokens;
-            let tokens_to_add = (intervals as u32) * self.refill_rate;
-            self.tokens = (self.tokens + tokens_to_add).min(self.capacity);
+        let old_tokens = self.tokens;
+        let intervals = u32::try_from(intervals).unwrap_or(u32::MAX);
+        let tokens_to_add = intervals.saturating_mul(self.refill_rate);
+        self.tokens = self.tokens.saturating_add(tokens_to_add).min(self.capacity);
+
+        if self.tokens == self.capacity {
             self.last_refill = now;
-            if self.tokens != old_tokens {
-                debug!(
-                    "Token bucket refilled: {} -> {} tokens",
-                    old_tokens, self.tokens
-                );
-            }
+        } else {
+            // Not full, so `intervals` is at most `capacity / refill_rate`
+            self.last_refill += self.refill_interval * intervals;
+        }
+
+        if self.tokens != old_tokens {
+            debug!(
+                "Token bucket refilled: {} -> {} tokens",
+                old_tokens, self.tokens
+            );
         }
     }
 }
 
+/// Limit on messages per WebSocket connection, parsed once at startup like
+/// [`RateLimitConfig`]. Every connection gets its own [`TokenBucket`] that lives only as
+/// long as the connection, so one client flooding actions can't monopolize its game.
+/// `build_rocket` reads it from the environment unless the instance already manages one.
+///
+/// - `WS_MESSAGES_PER_SECOND`: sustained rate and burst size (default 50, `0` disables)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MessageRateLimit(pub RateLimitConfig);
+
+impl Default for MessageRateLimit {
+    fn default() -> Self {
+        Self::per_second(50)
+    }
+}
+
+impl MessageRateLimit {
+    /// Up to `messages` per second, in bursts of at most as many; `0` disables the limit
+    pub fn per_second(messages: u32) -> Self {
+        Self(RateLimitConfig {
+            enabled: messages > 0,
+            capacity: messages,
+            refill_rate: messages,
+            refill_interval: Duration::from_secs(1),
+        })
+    }
+
+    pub fn from_env() -> Self {
+        let limit = match env::var("WS_MESSAGES_PER_SECOND") {
+            Err(_) => Self::default(),
+            Ok(value) => match value.trim().parse() {
+                Ok(messages) => Self::per_second(messages),
+                Err(_) => {
+                    warn!("Ignoring invalid WS_MESSAGES_PER_SECOND '{}'", value);
+                    Self::default()
+                }
+            },
+        };
+
+        if limit.0.enabled {
+            info!(
+                "Limiting WebSocket connections to {} messages per second",
+                limit.0.capacity
+            );
+        } else {
+            info!("WebSocket message rate limiting disabled");
+        }
+        limit
+    }
+
+    /// Messages accepted per second, `0` when disabled
+    pub fn per_second_limit(&self) -> u32 {
+        if self.0.enabled { self.0.capacity } else { 0 }
+    }
+
+    /// A full bucket for a new connection, `None` when limiting is disabled
+    pub fn bucket(&self) -> Option<TokenBucket> {
+        self.0.enabled.then(|| TokenBucket::new(&self.0))
+    }
+}
+
 pub type RateLimiter = DashMap<IpAddr, TokenBucket>;
 
+/// `429 Too Many Requests` with a `Retry-After` header in seconds.
+#[derive(Debug)]
+pub struct RateLimited(pub u64);
+
+impl<'r> Responder<'r, 'static> for RateLimited {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::TooManyRequests)
+            .raw_header("Retry-After", self.0.to_string())
+            .ok()
+    }
+}
+
 pub fn create_rate_limiter() -> RateLimiter {
     DashMap::new()
 }
 
-#[instrument(level = "trace", skip(rate_limiter))]
-pub fn check_rate_limit(rate_limiter: &State<RateLimiter>, ip: &IpAddr) -> Result<(), Status> {
-    let capacity: u32 = env::var("RATE_LIMIT_GAMES_PER_MINUTE")
-        .unwrap_or_else(|_| "10".to_string())
-        .parse()
-        .unwrap_or(10);
+fn rate_limit_enabled() -> bool {
+    env::var("RATE_LIMIT_ENABLED")
+        .map(|value| {
+            !matches!(
+                value.trim().to_lowercase().as_str(),
+                "false" | "0" | "no" | "off"
+            )
+        })
+        .unwrap_or(true)
+}
 
-    let refill_interval = Duration::from_secs(60); // 1 minute
-    let refill_rate = capacity; // Refill to full capacity every minute
+#[instrument(level = "trace", skip(rate_limiter, config))]
+pub fn check_rate_limit(
+    rate_limiter: &State<RateLimiter>,
+    config: &RateLimitConfig,
+    ip: &IpAddr,
+) -> Result<(), RateLimited> {
+    if !config.enabled {
+        return Ok(());
+    }
 
     let mut entry = rate_limiter
         .entry(*ip)
-        .or_insert_with(|| TokenBucket::new(capacity, refill_rate, refill_interval));
+        .or_insert_with(|| TokenBucket::new(config));
 
     if entry.try_consume() {
         debug!("Rate limit check passed for {}", ip);
         Ok(())
     } else {
-        warn!("Rate limit exceeded for {} - rejecting request", ip);
-        Err(Status::TooManyRequests)
+        let retry_after = entry.seconds_until_token();
+        warn!(
+            "Rate limit exceeded for {} - rejecting request, retry after {}s",
+            ip, retry_after
+        );
+        Err(RateLimited(retry_after))
+    }
+}
+
+/// Refills the bucket for `ip`. Returns `false` if the IP has no bucket yet.
+pub fn reset_rate_limit(rate_limiter: &RateLimiter, ip: &IpAddr) -> bool {
+    match rate_limiter.get_mut(ip) {
+        Some(mut bucket) => {
+            bucket.reset();
+            info!("Rate limit reset for {}", ip);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::MockClock;
+
+    use super::*;
+
+    fn bucket(clock: &MockClock) -> TokenBucket {
+        let config = RateLimitConfig {
+            capacity: 2,
+            refill_rate: 1,
+            refill_interval: Duration::from_secs(10),
+            ..Default::default()
+        };
+        TokenBucket::with_clock(&config, Arc::new(clock.clone()))
+    }
+
+    #[test]
+    fn refills_one_token_per_interval() {
+        let clock = MockClock::new();
+        let mut bucket = bucket(&clock);
+        assert!(bucket.try_consume());
+        assert!(bucket.try_consume());
+        assert!(!bucket.try_consume());
+        assert_eq!(bucket.seconds_until_token(), 10);
+
+        clock.advance(Duration::from_millis(9_500));
+        assert!(!bucket.try_consume());
+        assert_eq!(bucket.seconds_until_token(), 1);
+
+        clock.advance(Duration::from_millis(500));
+        assert!(bucket.try_consume());
+        assert!(!bucket.try_consume());
+    }
+
+    #[test]
+    fn reset_refills_a_drained_bucket() {
+        let clock = MockClock::new();
+        let limiter = create_rate_limiter();
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        assert!(!reset_rate_limit(&limiter, &ip));
+
+        limiter.insert(ip, bucket(&clock));
+        {
+            let mut bucket = limiter.get_mut(&ip).unwrap();
+            while bucket.try_consume() {}
+            assert_eq!(bucket.seconds_until_token(), 10);
+        }
+
+        clock.advance(Duration::from_secs(4));
+        assert!(reset_rate_limit(&limiter, &ip));
+        let mut bucket = limiter.get_mut(&ip).unwrap();
+        assert_eq!(bucket.tokens(), 2);
+        assert_eq!(bucket.seconds_until_token(), 0);
+        assert!(bucket.try_consume());
+        assert!(bucket.try_consume());
+        // The refill schedule restarts from the reset
+        assert_eq!(bucket.seconds_until_token(), 10);
+    }
+
+    #[test]
+    fn throttling_follows_the_enabled_setting() {
+        let limiter = create_rate_limiter();
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let disabled = RateLimitConfig {
+            enabled: false,
+            capacity: 2,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            assert!(check_rate_limit(State::from(&limiter), &disabled, &ip).is_ok());
+        }
+        assert!(limiter.is_empty());
+
+        let enabled = RateLimitConfig {
+            enabled: true,
+            ..disabled
+        };
+        assert!(check_rate_limit(State::from(&limiter), &enabled, &ip).is_ok());
+        assert!(check_rate_limit(State::from(&limiter), &enabled, &ip).is_ok());
+        assert!(check_rate_limit(State::from(&limiter), &enabled, &ip).is_err());
+    }
+
+    #[test]
+    fn refill_stops_at_capacity() {
+        let clock = MockClock::new();
+        let mut bucket = bucket(&clock);
+        bucket.try_consume();
+        clock.advance(Duration::from_secs(3600));
+        bucket.refill();
+        assert_eq!(bucket.tokens(), 2);
     }
 }
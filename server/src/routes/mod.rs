@@ -1,29 +1,124 @@
-use std::{net::IpAddr, sync::Arc};
+use std::{env, net::IpAddr, sync::Arc, time::Instant};
 
 use dashmap::Entry;
 use nanoid::nanoid;
-use rocket::{State, futures::StreamExt, get, http::Status, post, serde::json::Json};
+use rocket::{
+    Responder, State, delete, futures::StreamExt, get, http::Status, post,
+    response::content::RawText, serde::json::Json,
+};
 use rocket_ws::{Channel, Message, WebSocket};
-use tokio::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::time;
 use tracing::{debug, error, info, instrument, warn};
+use uuid::Uuid;
 
 use minesweeper_common::{
-    models::{CreateResponse, GameParams},
+    mask::RevealedMask,
+    models::{
+        BoardLimits, BoardSolution, CreateResponse, GameParams, GameStatus, GameSummary,
+        JoinResponse,
+    },
     protocol::ClientMessage,
 };
 
 use crate::{
-    logic::{Game, Games},
-    rate_limit::{RateLimiter, check_rate_limit},
+    action_log::ActionLogger,
+    auth::{AdminApiEnabled, AdminToken, ApiToken},
+    connections::{ConnectionLimiter, IpConnectionLimiter, ping_interval, pong_timeout},
+    logic::{ConnectionOptions, FrameFormat, Game, GameLimit, Games, MaxCascadeRadius},
+    metrics::Metrics,
+    presets::{AllowedBoardSizes, MaxBoardCells},
+    rate_limit::{
+        MessageRateLimit, RateLimitConfig, RateLimited, RateLimiter, check_rate_limit,
+        reset_rate_limit,
+    },
 };
 
-#[instrument(level = "trace", skip(games, game))]
-fn add_game(games: &State<Games>, game: Game) -> String {
-    let mut id_length = 5;
-    let max_attempts_per_length = 10;
+#[derive(Deserialize, Debug)]
+pub struct RateLimitResetRequest {
+    pub ip: IpAddr,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RateLimitStatus {
+    pub ip: IpAddr,
+    pub tokens: u32,
+    pub capacity: u32,
+}
+
+/// When the server process started, managed by Rocket for the health check.
+pub struct ServerStart(pub Instant);
+
+#[derive(Serialize, Debug)]
+pub struct HealthStatus {
+    pub status: &'static str,
+    pub games: usize,
+    pub uptime_secs: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+#[derive(Responder, Debug)]
+pub enum CreateError {
+    #[response(status = 400)]
+    InvalidParams(Json<ErrorResponse>),
+    RateLimited(RateLimited),
+    Rejected(Status),
+}
+
+/// Longest game ID `add_game` tries, the length of a default nanoid
+const MAX_ID_LENGTH: usize = 21;
 
-    loop {
-        for _ in 0..max_attempts_per_length {
+/// How game IDs are generated, parsed once at startup.
+///
+/// - `GAME_ID_LENGTH`: length of new IDs before any collision (default `5`, at most 21)
+/// - `GAME_ID_ATTEMPTS_PER_LENGTH`: colliding IDs tolerated before trying one character
+///   longer (default `10`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameIdConfig {
+    pub initial_length: usize,
+    pub attempts_per_length: usize,
+}
+
+impl Default for GameIdConfig {
+    fn default() -> Self {
+        Self {
+            initial_length: 5,
+            attempts_per_length: 10,
+        }
+    }
+}
+
+impl GameIdConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let parse = |name: &str, default: usize| match env::var(name) {
+            Err(_) => default,
+            Ok(value) => match value.trim().parse() {
+                Ok(parsed) if parsed > 0 => parsed,
+                _ => {
+                    warn!("Ignoring invalid {} '{}'", name, value);
+                    default
+                }
+            },
+        };
+
+        Self {
+            initial_length: parse("GAME_ID_LENGTH", default.initial_length).min(MAX_ID_LENGTH),
+            attempts_per_length: parse("GAME_ID_ATTEMPTS_PER_LENGTH", default.attempts_per_length),
+        }
+    }
+}
+
+/// Stores `game` under a fresh random ID, lengthening the ID after repeated collisions.
+/// Gives up with `500 Internal Server Error` if even 21 characters keep colliding.
+#[instrument(level = "trace", skip(games, config, game))]
+fn add_game(games: &Games, config: &GameIdConfig, game: Game) -> Result<String, Status> {
+    for id_length in config.initial_length..=MAX_ID_LENGTH {
+        for _ in 0..config.attempts_per_length {
             let id = nanoid!(id_length);
             match games.entry(id.clone()) {
                 Entry::Occupied(_) => {
@@ -31,54 +126,398 @@ fn add_game(games: &State<Games>, game: Game) -> String {
                     continue;
                 }
                 Entry::Vacant(entry) => {
-                    entry.insert(Arc::new(Mutex::new(game)));
+                    entry.insert(game.into_shared());
                     info!("Created new game with ID: {}", id);
-                    return id;
+                    return Ok(id);
                 }
             }
         }
 
-        warn!(
-            "Exhausted ID attempts at length {}, increasing to {}",
-            id_length,
-            id_length + 1
-        );
-        id_length += 1;
+        if id_length < MAX_ID_LENGTH {
+            warn!(
+                "Exhausted ID attempts at length {}, increasing to {}",
+                id_length,
+                id_length + 1
+            );
+        }
     }
+
+    error!(
+        "Could not allocate a game ID up to length {} with {} games stored",
+        MAX_ID_LENGTH,
+        games.len()
+    );
+    Err(Status::InternalServerError)
 }
 
-#[post("/create", data = "<params>")]
-#[instrument(level = "trace", skip(games, rate_limiter), fields(width = params.width, height = params.height, bombs = params.bombs))]
+#[post("/create?<return_seed>", data = "<params>")]
+#[instrument(level = "trace", skip(_token, games, id_config, game_limit, board_limits, allowed_sizes, max_cells, max_cascade_radius, rate_limiter, rate_limit_config, metrics), fields(width = params.width, height = params.height, bombs = params.bombs))]
+#[allow(clippy::too_many_arguments)]
 pub fn create_game(
-    params: Json<GameParams>,
+    _token: ApiToken,
+    mut params: Json<GameParams>,
+    return_seed: Option<bool>,
     games: &State<Games>,
+    id_config: &State<GameIdConfig>,
+    game_limit: &State<GameLimit>,
+    board_limits: &State<BoardLimits>,
+    allowed_sizes: &State<AllowedBoardSizes>,
+    max_cells: &State<MaxBoardCells>,
+    max_cascade_radius: &State<MaxCascadeRadius>,
     rate_limiter: &State<RateLimiter>,
+    rate_limit_config: &State<RateLimitConfig>,
+    metrics: &State<Arc<Metrics>>,
     ip: IpAddr,
-) -> Result<Json<CreateResponse>, Status> {
+) -> Result<Json<CreateResponse>, CreateError> {
     info!(
         "Game creation request from {}: {}x{} with {} bombs",
         ip, params.width, params.height, params.bombs
     );
 
-    if let Err(status) = check_rate_limit(rate_limiter, &ip) {
+    if let Err(error) = params.resolve_code() {
+        warn!("Rejected invalid board code from {}: {}", ip, error);
+        return Err(CreateError::InvalidParams(Json(ErrorResponse { error })));
+    }
+
+    if !max_cells.allows(&params) {
+        warn!(
+            "Rejected oversized board {}x{} from {}",
+            params.width, params.height, ip
+        );
+        return Err(CreateError::Rejected(Status::PayloadTooLarge));
+    }
+
+    if let Err(error) = params.validate_within(board_limits) {
+        warn!("Rejected invalid game parameters from {}: {}", ip, error);
+        return Err(CreateError::InvalidParams(Json(ErrorResponse { error })));
+    }
+
+    if !allowed_sizes.allows(&params) {
+        warn!(
+            "Rejected non-preset board {}x{} with {} bombs from {}",
+            params.width, params.height, params.bombs, ip
+        );
+        let error = format!(
+            "board {}x{}x{} is not an allowed preset",
+            params.width, params.height, params.bombs
+        );
+        return Err(CreateError::InvalidParams(Json(ErrorResponse { error })));
+    }
+
+    if !game_limit.allows(games.len()) {
+        warn!(
+            "Rejected game creation from {}: server at capacity with {} games",
+            ip,
+            games.len()
+        );
+        return Err(CreateError::Rejected(Status::ServiceUnavailable));
+    }
+
+    if let Err(rate_limited) = check_rate_limit(rate_limiter, rate_limit_config, &ip) {
         warn!("Rate limit exceeded for client {}", ip);
-        return Err(status);
+        return Err(CreateError::RateLimited(rate_limited));
     }
 
-    let game = Game::new(params.0);
-    let id = add_game(games, game);
+    let game = Game::new(params.0, metrics.inner().clone(), **max_cascade_radius);
+    let seed = return_seed.unwrap_or(false).then(|| game.seed());
+    let params = GameParams {
+        seed,
+        ..game.params()
+    };
+    let id = add_game(games, id_config, game).map_err(CreateError::Rejected)?;
+    metrics.record_game_created();
 
     info!("Successfully created game {} for client {}", id, ip);
-    Ok(Json(CreateResponse { id }))
+    Ok(Json(CreateResponse { id, params, seed }))
+}
+
+/// Hands out a running public game with room for another player, or creates a public
+/// game with default parameters if there is none. Private games are never returned.
+/// Busy games are skipped rather than waited on.
+#[post("/join")]
+#[instrument(
+    level = "trace",
+    skip(
+        _token,
+        games,
+        id_config,
+        game_limit,
+        max_cascade_radius,
+        rate_limiter,
+        rate_limit_config,
+        metrics
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn join_public_game(
+    _token: ApiToken,
+    games: &State<Games>,
+    id_config: &State<GameIdConfig>,
+    game_limit: &State<GameLimit>,
+    max_cascade_radius: &State<MaxCascadeRadius>,
+    rate_limiter: &State<RateLimiter>,
+    rate_limit_config: &State<RateLimitConfig>,
+    metrics: &State<Arc<Metrics>>,
+    ip: IpAddr,
+) -> Result<Json<JoinResponse>, CreateError> {
+    let open_game = games.iter().find_map(|entry| {
+        let game = entry.value().try_lock().ok()?;
+        game.accepts_lobby_players().then(|| entry.key().clone())
+    });
+    if let Some(id) = open_game {
+        info!("Lobby sent client {} to public game {}", ip, id);
+        return Ok(Json(JoinResponse { id, created: false }));
+    }
+
+    if !game_limit.allows(games.len()) {
+        warn!(
+            "Rejected lobby game for {}: server at capacity with {} games",
+            ip,
+            games.len()
+        );
+        return Err(CreateError::Rejected(Status::ServiceUnavailable));
+    }
+
+    if let Err(rate_limited) = check_rate_limit(rate_limiter, rate_limit_config, &ip) {
+        warn!("Rate limit exceeded for client {}", ip);
+        return Err(CreateError::RateLimited(rate_limited));
+    }
+
+    let params = GameParams {
+        public: true,
+        ..GameParams::default()
+    };
+    let game = Game::new(params, metrics.inner().clone(), **max_cascade_radius);
+    let id = add_game(games, id_config, game).map_err(CreateError::Rejected)?;
+    metrics.record_game_created();
+
+    info!("Lobby created public game {} for client {}", id, ip);
+    Ok(Json(JoinResponse { id, created: true }))
+}
+
+#[get("/games/<id>/status")]
+#[instrument(level = "trace", skip(games))]
+pub async fn get_game_status(games: &State<Games>, id: &str) -> Result<Json<GameStatus>, Status> {
+    let game = match games.get(id) {
+        None => {
+            debug!("Status requested for non-existent game: {}", id);
+            return Err(Status::NotFound);
+        }
+        Some(value) => value.value().clone(),
+    };
+
+    let game = game.lock().await;
+    Ok(Json(game.status()))
+}
+
+#[get("/games/<id>/mask")]
+#[instrument(level = "trace", skip(games))]
+pub async fn get_revealed_mask(
+    games: &State<Games>,
+    id: &str,
+) -> Result<Json<RevealedMask>, Status> {
+    let game = match games.get(id) {
+        None => {
+            debug!("Mask requested for non-existent game: {}", id);
+            return Err(Status::NotFound);
+        }
+        Some(value) => value.value().clone(),
+    };
+
+    let game = game.lock().await;
+    Ok(Json(game.revealed_mask()))
+}
+
+/// Bomb positions and adjacency counts of a finished game. Unfinished games answer
+/// `403 Forbidden`, since the layout would give the game away.
+#[get("/games/<id>/reveal")]
+#[instrument(level = "trace", skip(games))]
+pub async fn get_solution(games: &State<Games>, id: &str) -> Result<Json<BoardSolution>, Status> {
+    let game = match games.get(id) {
+        None => {
+            debug!("Solution requested for non-existent game: {}", id);
+            return Err(Status::NotFound);
+        }
+        Some(value) => value.value().clone(),
+    };
+
+    let game = game.lock().await;
+    match game.solution() {
+        Some(solution) => Ok(Json(solution)),
+        None => {
+            debug!("Solution requested for unfinished game: {}", id);
+            Err(Status::Forbidden)
+        }
+    }
 }
 
-#[get("/ws?<id>")]
-#[instrument(level = "trace", skip(ws, games), fields(game_id = %id))]
+/// Liveness and readiness probe. Only reads the game count, so it takes no game locks.
+#[get("/health")]
+pub fn health(games: &State<Games>, started: &State<ServerStart>) -> Json<HealthStatus> {
+    Json(HealthStatus {
+        status: "ok",
+        games: games.len(),
+        uptime_secs: started.0.elapsed().as_secs(),
+    })
+}
+
+/// Prometheus scrape endpoint.
+#[get("/metrics")]
+pub fn metrics(
+    games: &State<Games>,
+    connection_limiter: &State<ConnectionLimiter>,
+    metrics: &State<Arc<Metrics>>,
+) -> Result<RawText<String>, Status> {
+    let mut body = String::new();
+    metrics
+        .render(
+            &mut body,
+            games.len(),
+            connection_limiter.active_connections(),
+        )
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(RawText(body))
+}
+
+/// Lists all games for an admin dashboard. Games that are busy are skipped rather than
+/// waited on, so the listing never blocks on gameplay.
+#[get("/games")]
+#[instrument(level = "trace", skip(_enabled, _admin, games))]
+pub fn list_games(
+    _enabled: AdminApiEnabled,
+    _admin: AdminToken,
+    games: &State<Games>,
+) -> Json<Vec<GameSummary>> {
+    let summaries: Vec<GameSummary> = games
+        .iter()
+        .filter_map(|entry| match entry.value().try_lock() {
+            Ok(game) => Some(game.summary(entry.key().clone())),
+            Err(_) => {
+                debug!("Skipping busy game {} in listing", entry.key());
+                None
+            }
+        })
+        .collect();
+
+    debug!("Listing {} of {} games", summaries.len(), games.len());
+    Json(summaries)
+}
+
+/// Shuts a game down immediately: connected clients get a `closed` error and are
+/// disconnected before the game is removed.
+#[delete("/games/<id>")]
+#[instrument(level = "trace", skip(_enabled, _admin, games))]
+pub async fn delete_game(
+    _enabled: AdminApiEnabled,
+    _admin: AdminToken,
+    games: &State<Games>,
+    id: &str,
+) -> Status {
+    let game = match games.get(id) {
+        None => {
+            debug!("Delete requested for non-existent game: {}", id);
+            return Status::NotFound;
+        }
+        Some(value) => value.value().clone(),
+    };
+
+    game.lock().await.close_for_shutdown().await;
+    games.remove(id);
+    info!("Game {} deleted by admin", id);
+    Status::Ok
+}
+
+#[get("/admin/rate-limits/<ip>")]
+#[instrument(level = "trace", skip(_admin, rate_limiter))]
+pub fn get_rate_limit(
+    _admin: AdminToken,
+    rate_limiter: &State<RateLimiter>,
+    ip: IpAddr,
+) -> Result<Json<RateLimitStatus>, Status> {
+    let bucket = rate_limiter.get(&ip).ok_or(Status::NotFound)?;
+    Ok(Json(RateLimitStatus {
+        ip,
+        tokens: bucket.tokens(),
+        capacity: bucket.capacity(),
+    }))
+}
+
+#[post("/admin/rate-limits/reset", data = "<request>")]
+#[instrument(level = "trace", skip(_admin, rate_limiter), fields(ip = %request.ip))]
+pub fn reset_rate_limit_for_ip(
+    _admin: AdminToken,
+    request: Json<RateLimitResetRequest>,
+    rate_limiter: &State<RateLimiter>,
+) -> Status {
+    info!("Admin requested rate limit reset for {}", request.ip);
+
+    if reset_rate_limit(rate_limiter, &request.ip) {
+        Status::NoContent
+    } else {
+        debug!("No rate limit bucket for {}, nothing to reset", request.ip);
+        Status::NotFound
+    }
+}
+
+/// Frame format a connection asked for with `binary=true`, which requires the `binary`
+/// feature. Without it the connection gets JSON text frames.
+fn frame_format(binary: bool) -> FrameFormat {
+    #[cfg(feature = "binary")]
+    if binary {
+        return FrameFormat::Binary;
+    }
+    #[cfg(not(feature = "binary"))]
+    if binary {
+        warn!("Binary frames requested, but the server is built without the `binary` feature");
+    }
+    FrameFormat::Text
+}
+
+/// Decodes a MessagePack binary frame
+#[cfg(feature = "binary")]
+fn decode_binary(bytes: &[u8]) -> Result<ClientMessage, String> {
+    minesweeper_common::protocol::from_bytes(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "binary"))]
+fn decode_binary(_bytes: &[u8]) -> Result<ClientMessage, String> {
+    Err("Only JSON text frames are supported".to_string())
+}
+
+#[get("/ws?<id>&<cascade_depth>&<spectate>&<token>&<rev>&<binary>")]
+#[instrument(level = "trace", skip(ws, games, action_log, connection_limiter, ip_limiter, board_limits, allowed_sizes, max_cells, message_limit), fields(game_id = %id))]
+#[allow(clippy::too_many_arguments)]
 pub fn websocket_handler(
     ws: WebSocket,
     games: &State<Games>,
+    action_log: &State<ActionLogger>,
+    connection_limiter: &State<ConnectionLimiter>,
+    ip_limiter: &State<IpConnectionLimiter>,
+    board_limits: &State<BoardLimits>,
+    allowed_sizes: &State<AllowedBoardSizes>,
+    max_cells: &State<MaxBoardCells>,
+    message_limit: &State<MessageRateLimit>,
+    ip: IpAddr,
     id: String,
+    cascade_depth: Option<bool>,
+    spectate: Option<bool>,
+    token: Option<&str>,
+    rev: Option<u64>,
+    binary: Option<bool>,
 ) -> Result<Channel<'static>, Status> {
+    let action_log = action_log.inner().clone();
+    let board_limits = *board_limits.inner();
+    let allowed_sizes = allowed_sizes.inner().clone();
+    let max_cells = *max_cells.inner();
+    let message_limit = *message_limit.inner();
+    let options = ConnectionOptions {
+        cascade_depth: cascade_depth.unwrap_or(false),
+        spectator: spectate.unwrap_or(false),
+        token: token.and_then(|token| Uuid::parse_str(token).ok()),
+        revision: rev,
+        format: frame_format(binary.unwrap_or(false)),
+    };
+
     let game = match games.get(&id) {
         None => {
             warn!("WebSocket connection attempt for non-existent game: {}", id);
@@ -90,14 +529,36 @@ pub fn websocket_handler(
         }
     };
 
+    let Some(ip_guard) = ip_limiter.try_acquire(ip) else {
+        warn!(
+            "Rejecting WebSocket connection for game {}: too many connections from {}",
+            id, ip
+        );
+        return Err(Status::TooManyRequests);
+    };
+
+    let Some(connection_guard) = connection_limiter.try_acquire() else {
+        warn!(
+            "Rejecting WebSocket connection for game {}: server at connection capacity",
+            id
+        );
+        return Err(Status::ServiceUnavailable);
+    };
+
     Ok(ws.channel(move |stream| {
         let game_id = id.clone();
         Box::pin(async move {
+            let _connection_guard = connection_guard;
+            let _ip_guard = ip_guard;
             let (write, mut read) = stream.split();
 
             let stream_id = {
                 let mut game = game.lock().await;
-                game.add_stream(write).await
+                game.add_stream(write, options).await
+            };
+            let Some(stream_id) = stream_id else {
+                warn!("Rejected player for full game {}", game_id);
+                return Ok(());
             };
 
             info!(
@@ -105,45 +566,92 @@ pub fn websocket_handler(
                 game_id, stream_id
             );
 
-            while let Some(message) = read.next().await {
-                match message {
-                    Ok(Message::Text(text)) => match serde_json::from_str::<ClientMessage>(&text) {
-                        Ok(message) => {
-                            debug!("Received message from game {}: {:?}", game_id, message);
-                            match message {
-                                ClientMessage::Reveal { pos } => {
-                                    debug!(
-                                        "Player revealing cell at ({}, {}) in game {}",
-                                        pos.x, pos.y, game_id
-                                    );
-                                    let mut game = game.lock().await;
-                                    game.reveal(pos).await;
-                                }
-                                ClientMessage::Flag { pos } => {
-                                    debug!(
-                                        "Player flagging cell at ({}, {}) in game {}",
-                                        pos.x, pos.y, game_id
-                                    );
-                                    let mut game = game.lock().await;
-                                    game.flag(pos).await;
-                                }
-                                ClientMessage::Restart { params } => {
-                                    info!(
-                                        "Player restarting game {}: {}x{} with {} bombs",
-                                        game_id, params.width, params.height, params.bombs
-                                    );
-                                    let mut game = game.lock().await;
-                                    game.restart(params).await;
-                                }
-                            }
+            // Ping periodically and drop connections that stop answering, so half-open
+            // sockets don't keep a game alive forever
+            let ping_every = ping_interval();
+            let pong_wait = pong_timeout();
+            let mut ping_timer = time::interval_at(time::Instant::now() + ping_every, ping_every);
+            let mut pong_deadline: Option<time::Instant> = None;
+            let mut message_bucket = message_limit.bucket();
+            let mut throttled = false;
+
+            loop {
+                let message = tokio::select! {
+                    message = read.next() => match message {
+                        Some(message) => message,
+                        None => break,
+                    },
+                    _ = ping_timer.tick() => {
+                        if pong_deadline.is_none() {
+                            let mut game = game.lock().await;
+                            game.ping(&stream_id).await;
+                            pong_deadline = Some(time::Instant::now() + pong_wait);
+                        }
+                        continue;
+                    }
+                    _ = time::sleep_until(pong_deadline.unwrap_or_else(time::Instant::now)),
+                        if pong_deadline.is_some() => {
+                        warn!(
+                            "No pong within {:?} in game {} (stream: {}), dropping connection",
+                            pong_wait, game_id, stream_id
+                        );
+                        break;
+                    }
+                };
+
+                if matches!(message, Ok(Message::Text(_) | Message::Binary(_)))
+                    && let Some(bucket) = &mut message_bucket
+                {
+                    if !bucket.try_consume() {
+                        // Only the first dropped message of a burst gets an error, so a
+                        // flood isn't answered with another one
+                        if !throttled {
+                            warn!(
+                                "Throttling messages in game {} (stream: {})",
+                                game_id, stream_id
+                            );
+                            let mut game = game.lock().await;
+                            game.send_error(
+                                &stream_id,
+                                "too_fast",
+                                format!(
+                                    "At most {} messages per second are accepted",
+                                    message_limit.per_second_limit()
+                                ),
+                            )
+                            .await;
                         }
-                        Err(e) => {
+                        throttled = true;
+                        continue;
+                    }
+                    throttled = false;
+                }
+
+                let parsed = match message {
+                    Ok(Message::Text(text)) => serde_json::from_str::<ClientMessage>(&text)
+                        .map_err(|e| {
                             warn!(
                                 "Invalid message format in game {}: {} - Error: {}",
                                 game_id, text, e
                             );
-                        }
-                    },
+                            e.to_string()
+                        }),
+                    Ok(Message::Binary(bytes)) => decode_binary(&bytes).map_err(|e| {
+                        warn!("Invalid binary message in game {}: {}", game_id, e);
+                        e
+                    }),
+                    Ok(Message::Pong(_)) => {
+                        pong_deadline = None;
+                        continue;
+                    }
+                    Ok(Message::Ping(_)) => {
+                        // tungstenite queues the pong reply itself
+                        continue;
+                    }
+                    Ok(Message::Frame(_)) => {
+                        // Raw frames only appear when writing, never when reading
+                        continue;
+                    }
                     Ok(Message::Close(_)) => {
                         info!(
                             "WebSocket connection closed for game {} (stream: {})",
@@ -158,11 +666,42 @@ pub fn websocket_handler(
                         );
                         break;
                     }
-                    _ => {
-                        debug!("Received non-text message in game {}, ignoring", game_id);
-                        break;
+                };
+
+                let message = match parsed {
+                    Ok(message) => message,
+                    Err(e) => {
+                        let mut game = game.lock().await;
+                        game.send_error(&stream_id, "invalid_message", e).await;
+                        continue;
                     }
+                };
+
+                debug!("Received message from game {}: {:?}", game_id, message);
+                if options.spectator && !message.is_read_only() {
+                    debug!(
+                        "Rejecting action from spectator {} in game {}",
+                        stream_id, game_id
+                    );
+                    let mut game = game.lock().await;
+                    game.send_error(
+                        &stream_id,
+                        "spectator",
+                        "Spectators cannot change the board".to_string(),
+                    )
+                    .await;
+                    continue;
                 }
+                action_log.log(&game_id, &stream_id, &message);
+                let mut game = game.lock().await;
+                game.handle_message(
+                    &stream_id,
+                    message,
+                    &board_limits,
+                    &allowed_sizes,
+                    max_cells,
+                )
+                .await;
             }
 
             {
@@ -178,3 +717,89 @@ pub fn websocket_handler(
         })
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use minesweeper_common::models::GameParams;
+    use nanoid::alphabet::SAFE;
+    use rocket::{local::asynchronous::Client, routes};
+
+    use super::*;
+    use crate::rate_limit::create_rate_limiter;
+
+    fn new_game() -> Game {
+        Game::new(
+            GameParams::default(),
+            Arc::new(Metrics::default()),
+            MaxCascadeRadius::default(),
+        )
+    }
+
+    #[test]
+    fn full_id_space_moves_to_longer_ids() {
+        let games = Games::default();
+        for id in SAFE {
+            games.insert(id.to_string(), new_game().into_shared());
+        }
+        let config = GameIdConfig {
+            initial_length: 1,
+            attempts_per_length: 10,
+        };
+
+        let id = add_game(&games, &config, new_game()).unwrap();
+        assert_eq!(id.len(), 2);
+        assert_eq!(games.len(), SAFE.len() + 1);
+    }
+
+    #[test]
+    fn gives_up_instead_of_looping_forever() {
+        let games = Games::default();
+        let config = GameIdConfig {
+            initial_length: 5,
+            attempts_per_length: 0,
+        };
+
+        assert_eq!(
+            add_game(&games, &config, new_game()),
+            Err(Status::InternalServerError)
+        );
+        assert!(games.is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn creation_fails_at_max_active_games_until_one_is_removed() {
+        let rocket = rocket::build()
+            .manage(Games::default())
+            .manage(GameIdConfig::default())
+            .manage(GameLimit(Some(2)))
+            .manage(BoardLimits::default())
+            .manage(AllowedBoardSizes::default())
+            .manage(MaxBoardCells::default())
+            .manage(MaxCascadeRadius::default())
+            .manage(create_rate_limiter())
+            .manage(RateLimitConfig {
+                enabled: false,
+                ..Default::default()
+            })
+            .manage(Arc::new(Metrics::default()))
+            .mount("/", routes![create_game]);
+        let client = Client::untracked(rocket).await.unwrap();
+        let create = || {
+            client
+                .post("/create")
+                .remote("192.0.2.1:4000".parse().unwrap())
+                .json(&GameParams::default())
+                .dispatch()
+        };
+
+        assert_eq!(create().await.status(), Status::Ok);
+        assert_eq!(create().await.status(), Status::Ok);
+        assert_eq!(create().await.status(), Status::ServiceUnavailable);
+
+        let games = client.rocket().state::<Games>().unwrap();
+        let id = games.iter().next().unwrap().key().clone();
+        games.remove(&id);
+        assert_eq!(create().await.status(), Status::Ok);
+        assert_eq!(games.len(), 2);
+    }
+}
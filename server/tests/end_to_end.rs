@@ -0,0 +1,1411 @@
+mod support;
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use minesweeper_client::{
+    Cell, ClientError, ClientMessage, ConnectOptions, GameEvent, GameParams, GameState, MaskState,
+    MinesweeperClient, MinesweeperGame, MinesweeperWebSocket, Pos, ServerMessage,
+};
+use minesweeper_common::board_code::BoardCode;
+use minesweeper_server::{
+    app::build_rocket,
+    cleanup::cleanup_games,
+    clock::MockClock,
+    logic::{Game, Games},
+    metrics::Metrics,
+    presets::{AllowedBoardSizes, BoardSize},
+    rate_limit::{MessageRateLimit, RateLimitConfig, RateLimiter},
+};
+use rocket::http::Status;
+use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
+
+/// Next message other than `Presence`, which arrives whenever someone joins or leaves
+async fn next_game_message(ws: &mut MinesweeperWebSocket) -> Option<ServerMessage> {
+    loop {
+        let message = timeout(Duration::from_secs(5), ws.receive_message())
+            .await
+            .expect("no message within 5s")
+            .unwrap();
+        if !matches!(message, Some(ServerMessage::Presence { .. })) {
+            return message;
+        }
+    }
+}
+
+#[tokio::test]
+async fn reveal_over_websocket_broadcasts_update() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let game_id = client.create_game(GameParams::default()).await.unwrap();
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(&game_id).unwrap())
+        .await
+        .unwrap();
+
+    let init = ws.receive_message().await.unwrap();
+    assert!(matches!(
+        init,
+        Some(ServerMessage::Init {
+            width: 9,
+            height: 9,
+            ..
+        })
+    ));
+
+    let pos = Pos { x: 0, y: 0 };
+    ws.send_message(ClientMessage::Reveal { pos })
+        .await
+        .unwrap();
+
+    let update = next_game_message(&mut ws).await;
+    match update {
+        Some(ServerMessage::Update { updates, .. }) => {
+            assert!(updates.iter().any(|update| update.pos == pos));
+        }
+        other => panic!("expected an update, got {:?}", other),
+    }
+
+    ws.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn winning_flags_remaining_bombs() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let params = GameParams {
+        width: 3,
+        height: 3,
+        bombs: 2,
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(&game_id).unwrap())
+        .await
+        .unwrap();
+    ws.receive_message().await.unwrap();
+
+    // Hints only reveal safe cells, so repeating them wins the game
+    let final_updates = loop {
+        ws.send_message(ClientMessage::Hint).await.unwrap();
+        let message = next_game_message(&mut ws).await;
+        match message {
+            Some(ServerMessage::Update {
+                updates, won: true, ..
+            }) => break updates,
+            Some(ServerMessage::Update { .. }) => {}
+            other => panic!("expected an update, got {:?}", other),
+        }
+    };
+
+    let flagged = final_updates
+        .iter()
+        .filter(|update| matches!(update.value, Cell::Flagged))
+        .count();
+    assert_eq!(flagged, 2);
+
+    ws.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn reveal_many_stops_when_the_game_ends() {
+    let base_url = support::spawn_server().await;
+    let game = MinesweeperGame::new(&base_url).unwrap();
+    let params = GameParams {
+        width: 3,
+        height: 3,
+        bombs: 2,
+        ..Default::default()
+    };
+    game.start_game(params).await.unwrap();
+    while game.get_state().await.is_none() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // Every cell is requested, so the batch either hits a bomb or wins first
+    let positions: Vec<Pos> = (0..9).map(|index| Pos::from_index(index, 3)).collect();
+    let result = game.reveal_many(&positions).await.unwrap();
+
+    let state = game.get_state().await.unwrap();
+    assert!(state.is_game_over());
+    assert!(!result.timed_out);
+    assert_eq!(result.lost, !state.is_won());
+}
+
+#[tokio::test]
+async fn board_updates_carry_the_new_cell_values() {
+    let base_url = support::spawn_server().await;
+    let game = MinesweeperGame::new(&base_url).unwrap();
+    let mut events = game.subscribe_to_events().await;
+    game.start_game(GameParams::default()).await.unwrap();
+    while game.get_state().await.is_none() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let pos = Pos { x: 2, y: 5 };
+    game.flag(pos).await.unwrap();
+    let event = timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.recv().await.unwrap();
+            if matches!(event, GameEvent::BoardUpdated { .. }) {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("no board update within 5s");
+
+    assert_eq!(event.changed_positions(), vec![pos]);
+    let GameEvent::BoardUpdated { changes } = event else {
+        unreachable!()
+    };
+    assert_eq!(changes[0].value, Cell::Flagged);
+
+    game.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn out_of_bounds_reveal_fails_on_the_client() {
+    let base_url = support::spawn_server().await;
+    let game = MinesweeperGame::new(&base_url).unwrap();
+    game.start_game(GameParams::default()).await.unwrap();
+    while game.get_state().await.is_none() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let pos = Pos { x: 9, y: 3 };
+    let error = game.reveal(pos).await.unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<ClientError>(),
+        Some(&ClientError::OutOfBounds {
+            pos,
+            width: 9,
+            height: 9,
+        })
+    );
+    assert!(game.flag(pos).await.is_err());
+}
+
+#[tokio::test]
+async fn reveal_without_a_board_skips_the_bounds_check() {
+    let base_url = support::spawn_server().await;
+    let game = MinesweeperGame::new(&base_url).unwrap();
+
+    // No board is known, so the reveal goes on to sending and fails for lack of a connection
+    let error = game.reveal(Pos { x: 100, y: 100 }).await.unwrap_err();
+    assert!(error.downcast_ref::<ClientError>().is_none());
+}
+
+#[tokio::test]
+async fn disconnect_delivers_actions_sent_just_before() {
+    // The flags are sent far faster than a client is normally allowed to
+    let base_url =
+        support::spawn_server_with(|rocket| rocket.manage(MessageRateLimit::per_second(0))).await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+    let params = GameParams {
+        width: 40,
+        height: 25,
+        bombs: 10,
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    // Enough messages that the writer can't flush them all in one go
+    let flags: Vec<Pos> = (0..1000).map(|index| Pos::from_index(index, 40)).collect();
+
+    // Play on a runtime of its own that is shut down as soon as `disconnect` returns, like
+    // a program exiting right after its last move. Anything not yet written is lost then.
+    let (url, positions) = (base_url.clone(), flags.clone());
+    let id = game_id.clone();
+    tokio::task::spawn_blocking(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let game = MinesweeperGame::new(&url).unwrap();
+            game.join_game(id).await.unwrap();
+            for pos in positions {
+                game.flag(pos).await.unwrap();
+            }
+            game.disconnect_with_timeout(Duration::from_secs(5))
+                .await
+                .unwrap();
+        });
+    })
+    .await
+    .unwrap();
+
+    // The server applies the flags asynchronously, so allow it a moment to catch up
+    let all_flagged = timeout(Duration::from_secs(5), async {
+        loop {
+            let mask = client.get_revealed_mask(&game_id).await.unwrap();
+            if flags
+                .iter()
+                .all(|&pos| mask.get(pos) == Some(MaskState::Flagged))
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    assert!(
+        all_flagged.is_ok(),
+        "flags sent before disconnecting were lost"
+    );
+}
+
+#[tokio::test]
+async fn join_reuses_open_public_games_only() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+    let private_id = client.create_game(GameParams::default()).await.unwrap();
+
+    let first = client.join_public_game().await.unwrap();
+    assert!(first.created);
+    assert_ne!(first.id, private_id);
+
+    let second = client.join_public_game().await.unwrap();
+    assert!(!second.created);
+    assert_eq!(second.id, first.id);
+}
+
+#[tokio::test]
+async fn player_beyond_max_players_is_rejected() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+    let params = GameParams {
+        max_players: 2,
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let url = client.websocket_url(&game_id).unwrap();
+
+    let mut players = Vec::new();
+    for _ in 0..2 {
+        let mut ws = MinesweeperWebSocket::connect(&url).await.unwrap();
+        let init = ws.receive_message().await.unwrap();
+        assert!(matches!(init, Some(ServerMessage::Init { .. })));
+        players.push(ws);
+    }
+
+    let mut rejected = MinesweeperWebSocket::connect(&url).await.unwrap();
+    match rejected.receive_message().await.unwrap() {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, "game_full"),
+        other => panic!("expected a game_full error, got {:?}", other),
+    }
+
+    // Spectators are counted separately and still get in
+    let spectator_url = client
+        .websocket_url_with_options(
+            &game_id,
+            &ConnectOptions {
+                spectate: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let mut spectator = MinesweeperWebSocket::connect(&spectator_url).await.unwrap();
+    let init = spectator.receive_message().await.unwrap();
+    assert!(matches!(init, Some(ServerMessage::Init { .. })));
+}
+
+#[tokio::test]
+async fn connected_players_see_presence_when_someone_joins() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+    let game_id = client.create_game(GameParams::default()).await.unwrap();
+    let url = client.websocket_url(&game_id).unwrap();
+
+    let mut first = MinesweeperWebSocket::connect(&url).await.unwrap();
+    let mut second = MinesweeperWebSocket::connect(&url).await.unwrap();
+    let _third = MinesweeperWebSocket::connect(&url).await.unwrap();
+
+    for ws in [&mut first, &mut second] {
+        loop {
+            let message = timeout(Duration::from_secs(5), ws.receive_message())
+                .await
+                .expect("no presence within 5s")
+                .unwrap();
+            if let Some(ServerMessage::Presence { players: 3 }) = message {
+                break;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn revealing_a_revealed_cell_sends_nothing() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    // A column of bombs splits the board, so the first reveal doesn't win the game
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(&game_id).unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Init { .. })
+    ));
+
+    let pos = Pos { x: 0, y: 0 };
+    ws.send_message(ClientMessage::Reveal { pos })
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Update {
+            won: false,
+            lost: false,
+            ..
+        })
+    ));
+
+    // The state request is answered after the redundant reveal, so an update for the
+    // reveal would arrive first
+    ws.send_message(ClientMessage::Reveal { pos })
+        .await
+        .unwrap();
+    ws.send_message(ClientMessage::GetState).await.unwrap();
+    let reply = next_game_message(&mut ws).await;
+    assert!(
+        matches!(reply, Some(ServerMessage::Init { .. })),
+        "expected the state reply, got {:?}",
+        reply
+    );
+
+    ws.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn reconnecting_with_a_revision_receives_only_missed_updates() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(&game_id).unwrap())
+        .await
+        .unwrap();
+    let token = match next_game_message(&mut ws).await {
+        Some(ServerMessage::Init {
+            your_token: Some(token),
+            revision: 0,
+            ..
+        }) => token,
+        other => panic!("expected an init, got {:?}", other),
+    };
+
+    ws.send_message(ClientMessage::Reveal {
+        pos: Pos { x: 0, y: 0 },
+    })
+    .await
+    .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Update { revision: 1, .. })
+    ));
+    let flag = Pos { x: 2, y: 0 };
+    ws.send_message(ClientMessage::Flag { pos: flag })
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Update { revision: 2, .. })
+    ));
+
+    let resume = |revision| ConnectOptions {
+        token: Some(token),
+        revision: Some(revision),
+        ..Default::default()
+    };
+    let url = client
+        .websocket_url_with_options(&game_id, &resume(1))
+        .unwrap();
+    let mut resumed = MinesweeperWebSocket::connect(&url).await.unwrap();
+    match next_game_message(&mut resumed).await {
+        Some(ServerMessage::Update {
+            updates, revision, ..
+        }) => {
+            assert_eq!(revision, 2);
+            assert_eq!(updates.len(), 1);
+            assert_eq!(updates[0].pos, flag);
+            assert!(matches!(updates[0].value, Cell::Flagged));
+        }
+        other => panic!("expected the missed update, got {:?}", other),
+    }
+    resumed.close().await.unwrap();
+
+    // A revision the server never reached can't be diffed against
+    let url = client
+        .websocket_url_with_options(&game_id, &resume(7))
+        .unwrap();
+    let mut resumed = MinesweeperWebSocket::connect(&url).await.unwrap();
+    assert!(matches!(
+        next_game_message(&mut resumed).await,
+        Some(ServerMessage::Init { revision: 2, .. })
+    ));
+    resumed.close().await.unwrap();
+
+    ws.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn time_limit_ends_the_game_as_lost() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        time_limit_secs: Some(1),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(&game_id).unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Init { .. })
+    ));
+
+    ws.send_message(ClientMessage::Reveal {
+        pos: Pos { x: 0, y: 0 },
+    })
+    .await
+    .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Update { lost: false, .. })
+    ));
+
+    match next_game_message(&mut ws).await {
+        Some(ServerMessage::Update {
+            updates,
+            won: false,
+            lost: true,
+            ..
+        }) => assert_eq!(updates.len(), 5, "every bomb is revealed"),
+        other => panic!("expected the time limit loss, got {:?}", other),
+    }
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::GameSummary { won: false, .. })
+    ));
+
+    ws.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn snapshots_differ_only_in_changed_cells() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let game_id = client.create_game(GameParams::default()).await.unwrap();
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(&game_id).unwrap())
+        .await
+        .unwrap();
+    let init = next_game_message(&mut ws).await.unwrap();
+    let mut state = GameState::from_init(&init).unwrap();
+    let before = state.snapshot();
+    assert_eq!(before.cells.len(), 81);
+    assert!(before.cells.iter().all(|cell| *cell == Cell::Hidden));
+
+    let pos = Pos { x: 4, y: 7 };
+    ws.send_message(ClientMessage::Flag { pos }).await.unwrap();
+    state.apply(&next_game_message(&mut ws).await.unwrap());
+    let after = state.snapshot();
+
+    let changed: Vec<usize> = (0..after.cells.len())
+        .filter(|&index| before.cells[index] != after.cells[index])
+        .collect();
+    assert_eq!(changed, vec![pos.index(9)]);
+    assert_eq!(after.cell(pos), Some(Cell::Flagged));
+
+    let json = serde_json::to_value(&after).unwrap();
+    assert_eq!(json["cells"][pos.index(9)]["state"], "flagged");
+    assert_eq!(json["game_over"], false);
+
+    ws.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn solution_is_only_revealed_once_the_game_is_finished() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+
+    let error = client.get_solution(&game_id).await.unwrap_err();
+    assert!(error.to_string().contains("403"), "{}", error);
+
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(&game_id).unwrap())
+        .await
+        .unwrap();
+    next_game_message(&mut ws).await.unwrap();
+    ws.send_message(ClientMessage::Reveal {
+        pos: Pos { x: 2, y: 0 },
+    })
+    .await
+    .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Update { lost: true, .. })
+    ));
+
+    let solution = client.get_solution(&game_id).await.unwrap();
+    assert_eq!((solution.width, solution.height), (5, 5));
+    assert_eq!(
+        solution.bombs,
+        (0..5).map(|y| Pos { x: 2, y }).collect::<Vec<_>>()
+    );
+    assert_eq!(solution.adjacent[..5], [0, 2, 1, 2, 0]);
+
+    ws.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn invalid_cell_actions_are_reported_to_the_actor_only() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let url = client.websocket_url(&game_id).unwrap();
+    let mut actor = MinesweeperWebSocket::connect(&url).await.unwrap();
+    let mut other = MinesweeperWebSocket::connect(&url).await.unwrap();
+    for ws in [&mut actor, &mut other] {
+        assert!(matches!(
+            next_game_message(ws).await,
+            Some(ServerMessage::Init { .. })
+        ));
+    }
+
+    let revealed = Pos { x: 0, y: 0 };
+    let flagged = Pos { x: 4, y: 4 };
+    actor
+        .send_message(ClientMessage::Reveal { pos: revealed })
+        .await
+        .unwrap();
+    actor
+        .send_message(ClientMessage::Flag { pos: flagged })
+        .await
+        .unwrap();
+    for ws in [&mut actor, &mut other] {
+        for _ in 0..2 {
+            assert!(matches!(
+                next_game_message(ws).await,
+                Some(ServerMessage::Update { .. })
+            ));
+        }
+    }
+
+    actor
+        .send_message(ClientMessage::Flag { pos: revealed })
+        .await
+        .unwrap();
+    actor
+        .send_message(ClientMessage::Reveal { pos: flagged })
+        .await
+        .unwrap();
+    for code in ["cell_revealed", "cell_flagged"] {
+        match next_game_message(&mut actor).await {
+            Some(ServerMessage::Error { code: received, .. }) => assert_eq!(received, code),
+            other => panic!("expected a {} error, got {:?}", code, other),
+        }
+    }
+
+    // Anything sent to the other player because of the rejected actions would arrive
+    // before the reply to this request
+    other.send_message(ClientMessage::GetState).await.unwrap();
+    let reply = next_game_message(&mut other).await;
+    assert!(
+        matches!(reply, Some(ServerMessage::Init { .. })),
+        "expected the state reply, got {:?}",
+        reply
+    );
+
+    actor.close().await.unwrap();
+    other.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn flooding_messages_are_dropped_with_one_error() {
+    let base_url =
+        support::spawn_server_with(|rocket| rocket.manage(MessageRateLimit::per_second(5))).await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+    let game_id = client.create_game(GameParams::default()).await.unwrap();
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(&game_id).unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Init { .. })
+    ));
+
+    for x in 0..9 {
+        ws.send_message(ClientMessage::Flag {
+            pos: Pos { x, y: 0 },
+        })
+        .await
+        .unwrap();
+    }
+    for _ in 0..5 {
+        assert!(matches!(
+            next_game_message(&mut ws).await,
+            Some(ServerMessage::Update { .. })
+        ));
+    }
+    match next_game_message(&mut ws).await {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, "too_fast"),
+        other => panic!("expected a too_fast error, got {:?}", other),
+    }
+
+    // The bucket refills after a second, and only the first dropped message was answered
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    ws.send_message(ClientMessage::GetState).await.unwrap();
+    let reply = next_game_message(&mut ws).await;
+    assert!(
+        matches!(
+            reply,
+            Some(ServerMessage::Init {
+                flags_placed: 5,
+                ..
+            })
+        ),
+        "expected the state reply, got {:?}",
+        reply
+    );
+
+    ws.close().await.unwrap();
+}
+
+#[cfg(feature = "binary")]
+#[tokio::test]
+async fn binary_and_text_connections_share_a_game() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let url = client.websocket_url(&game_id).unwrap();
+    let mut binary = MinesweeperWebSocket::connect_binary(&url).await.unwrap();
+    let mut text = MinesweeperWebSocket::connect(&url).await.unwrap();
+    for ws in [&mut binary, &mut text] {
+        assert!(matches!(
+            next_game_message(ws).await,
+            Some(ServerMessage::Init { width: 5, .. })
+        ));
+    }
+
+    binary
+        .send_message(ClientMessage::Reveal {
+            pos: Pos { x: 0, y: 0 },
+        })
+        .await
+        .unwrap();
+    for ws in [&mut binary, &mut text] {
+        match next_game_message(ws).await {
+            Some(ServerMessage::Update { updates, .. }) => assert_eq!(updates.len(), 10),
+            other => panic!("expected an update, got {:?}", other),
+        }
+    }
+
+    binary.close().await.unwrap();
+    text.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn concurrent_actions_from_two_connections_apply_in_one_order() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    // The bomb column leaves a 10 cell region on the left that any reveal in it opens
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let url = client.websocket_url(&game_id).unwrap();
+    let mut first = MinesweeperWebSocket::connect(&url).await.unwrap();
+    let mut second = MinesweeperWebSocket::connect(&url).await.unwrap();
+    for ws in [&mut first, &mut second] {
+        assert!(matches!(
+            next_game_message(ws).await,
+            Some(ServerMessage::Init { .. })
+        ));
+    }
+
+    let actions = |cells: [(usize, usize); 3], flag: (usize, usize)| {
+        let mut messages: Vec<_> = cells
+            .into_iter()
+            .map(|(x, y)| ClientMessage::Reveal { pos: Pos { x, y } })
+            .collect();
+        messages.insert(
+            1,
+            ClientMessage::Flag {
+                pos: Pos {
+                    x: flag.0,
+                    y: flag.1,
+                },
+            },
+        );
+        messages
+    };
+    let first_sender = first.get_sender();
+    let second_sender = second.get_sender();
+    let first_actions = actions([(0, 0), (1, 1), (0, 2)], (2, 0));
+    let second_actions = actions([(0, 4), (1, 3), (0, 3)], (2, 4));
+    tokio::join!(
+        async {
+            for message in first_actions {
+                first_sender.send(message).unwrap();
+                tokio::task::yield_now().await;
+            }
+        },
+        async {
+            for message in second_actions {
+                second_sender.send(message).unwrap();
+                tokio::task::yield_now().await;
+            }
+        }
+    );
+    // `close` waits for the writers, which only finish once every sender is gone
+    drop((first_sender, second_sender));
+
+    // One cascade plus two flags; the other reveals hit revealed cells and send nothing
+    let mut sequences = Vec::new();
+    for ws in [&mut first, &mut second] {
+        let mut sequence = Vec::new();
+        while sequence.len() < 3 {
+            match next_game_message(ws).await {
+                Some(ServerMessage::Update {
+                    updates, revision, ..
+                }) => sequence.push((revision, updates)),
+                other => panic!("expected an update, got {:?}", other),
+            }
+        }
+        sequences.push(sequence);
+    }
+
+    for sequence in &sequences {
+        let revisions: Vec<u64> = sequence.iter().map(|(revision, _)| *revision).collect();
+        assert_eq!(revisions, [1, 2, 3]);
+
+        let mut positions: Vec<Pos> = sequence
+            .iter()
+            .flat_map(|(_, updates)| updates.iter().map(|update| update.pos))
+            .collect();
+        let total = positions.len();
+        positions.sort_by_key(|pos| (pos.y, pos.x));
+        positions.dedup();
+        assert_eq!(positions.len(), total, "a cell was sent twice");
+        assert_eq!(total, 12);
+    }
+    let order = |sequence: &Vec<(u64, Vec<_>)>| -> Vec<Vec<Pos>> {
+        sequence
+            .iter()
+            .map(
+                |(_, updates): &(u64, Vec<minesweeper_client::CellUpdate>)| {
+                    updates.iter().map(|update| update.pos).collect()
+                },
+            )
+            .collect()
+    };
+    assert_eq!(order(&sequences[0]), order(&sequences[1]));
+
+    // Both ask for the board after everything above was broadcast
+    let mut fields = Vec::new();
+    for ws in [&mut first, &mut second] {
+        ws.send_message(ClientMessage::GetState).await.unwrap();
+        match next_game_message(ws).await {
+            Some(ServerMessage::Init {
+                field,
+                revision,
+                flags_placed,
+                ..
+            }) => {
+                assert_eq!(revision, 3);
+                assert_eq!(flags_placed, 2);
+                fields.push(field);
+            }
+            other => panic!("expected the state reply, got {:?}", other),
+        }
+    }
+    assert_eq!(fields[0], fields[1]);
+    let revealed = fields[0]
+        .iter()
+        .flatten()
+        .filter(|cell| cell.is_revealed())
+        .count();
+    assert_eq!(revealed, 10);
+
+    first.close().await.unwrap();
+    second.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn late_reveal_on_a_finished_game_gets_the_outcome() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let url = client.websocket_url(&game_id).unwrap();
+    let mut actor = MinesweeperWebSocket::connect(&url).await.unwrap();
+    assert!(matches!(
+        next_game_message(&mut actor).await,
+        Some(ServerMessage::Init { .. })
+    ));
+
+    actor
+        .send_message(ClientMessage::Reveal {
+            pos: Pos { x: 2, y: 0 },
+        })
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut actor).await,
+        Some(ServerMessage::Update { lost: true, .. })
+    ));
+    assert!(matches!(
+        next_game_message(&mut actor).await,
+        Some(ServerMessage::GameSummary { won: false, .. })
+    ));
+
+    let mut late = MinesweeperWebSocket::connect(&url).await.unwrap();
+    assert!(matches!(
+        next_game_message(&mut late).await,
+        Some(ServerMessage::Init { .. })
+    ));
+    late.send_message(ClientMessage::Reveal {
+        pos: Pos { x: 0, y: 0 },
+    })
+    .await
+    .unwrap();
+
+    match next_game_message(&mut late).await {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, "game_finished"),
+        other => panic!("expected a game_finished error, got {:?}", other),
+    }
+    match next_game_message(&mut late).await {
+        Some(ServerMessage::Update {
+            updates,
+            won: false,
+            lost: true,
+            ..
+        }) => assert!(updates.is_empty()),
+        other => panic!("expected the terminal state, got {:?}", other),
+    }
+    assert!(matches!(
+        next_game_message(&mut late).await,
+        Some(ServerMessage::GameSummary { won: false, .. })
+    ));
+
+    // The late reveal is answered to its sender only
+    actor.send_message(ClientMessage::GetState).await.unwrap();
+    let reply = next_game_message(&mut actor).await;
+    assert!(
+        matches!(reply, Some(ServerMessage::Init { .. })),
+        "expected the state reply, got {:?}",
+        reply
+    );
+
+    late.close().await.unwrap();
+    actor.close().await.unwrap();
+}
+
+/// Reveals every cell in board order until the game ends, usually on a bomb
+async fn play_until_finished(
+    client: &MinesweeperClient,
+    game_id: &str,
+    width: usize,
+    height: usize,
+) {
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url(game_id).unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Init { .. })
+    ));
+
+    for y in 0..height {
+        for x in 0..width {
+            ws.send_message(ClientMessage::Reveal { pos: Pos { x, y } })
+                .await
+                .unwrap();
+        }
+    }
+    while !matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Update { won, lost, .. }) if won || lost
+    ) {}
+
+    ws.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn returned_seed_recreates_the_layout() {
+    let base_url =
+        support::spawn_server_with(|rocket| rocket.manage(MessageRateLimit::per_second(0))).await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+    let params = GameParams {
+        width: 6,
+        height: 6,
+        bombs: 8,
+        ..Default::default()
+    };
+
+    let practice = client.create_practice_game(params.clone()).await.unwrap();
+    let seed = practice.seed.expect("the seed was asked for");
+    let replay = client
+        .create_game(GameParams {
+            seed: Some(seed),
+            ..params
+        })
+        .await
+        .unwrap();
+
+    let mut solutions = Vec::new();
+    for game_id in [&practice.id, &replay] {
+        play_until_finished(&client, game_id, 6, 6).await;
+        solutions.push(client.get_solution(game_id).await.unwrap());
+    }
+    assert_eq!(solutions[0].bombs.len(), 8);
+    assert_eq!(solutions[0].bombs, solutions[1].bombs);
+    assert_eq!(solutions[0].adjacent, solutions[1].adjacent);
+}
+
+/// Params, `params_changed` and `new_layout` of the next `GameRestarted` event
+async fn next_restart(events: &mut UnboundedReceiver<GameEvent>) -> (GameParams, bool, bool) {
+    timeout(Duration::from_secs(5), async {
+        loop {
+            if let GameEvent::GameRestarted {
+                params,
+                params_changed,
+                new_layout,
+            } = events.recv().await.unwrap()
+            {
+                return (params, params_changed, new_layout);
+            }
+        }
+    })
+    .await
+    .expect("no restart within 5s")
+}
+
+#[tokio::test]
+async fn restart_event_carries_the_new_params() {
+    let base_url = support::spawn_server().await;
+    let game = MinesweeperGame::new(&base_url).unwrap();
+    let mut events = game.subscribe_to_events().await;
+    game.start_game(GameParams::default()).await.unwrap();
+    while game.get_state().await.is_none() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let bigger = GameParams {
+        width: 12,
+        height: 10,
+        bombs: 15,
+        ..Default::default()
+    };
+    game.restart(bigger.clone()).await.unwrap();
+    let (params, params_changed, new_layout) = next_restart(&mut events).await;
+    assert_eq!((params.width, params.height, params.bombs), (12, 10, 15));
+    assert!(params_changed && new_layout);
+    let seed = params.seed.expect("the effective params include the seed");
+
+    // Same params and seed again: the same board comes back
+    game.restart(GameParams {
+        seed: Some(seed),
+        ..bigger
+    })
+    .await
+    .unwrap();
+    let (params, params_changed, new_layout) = next_restart(&mut events).await;
+    assert_eq!(params.seed, Some(seed));
+    assert!(!params_changed && !new_layout);
+
+    game.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+async fn viewport_withholds_updates_outside_it_until_it_moves() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    // The bomb column splits the board into a left and a right region
+    let bombs: Vec<bool> = (0..25).map(|index| index % 5 == 2).collect();
+    let board = BoardCode {
+        width: 5,
+        height: 5,
+        bombs,
+        states: vec![MaskState::Hidden; 25],
+    };
+    let params = GameParams {
+        code: Some(board.to_code()),
+        ..Default::default()
+    };
+    let game_id = client.create_game(params).await.unwrap();
+    let url = client.websocket_url(&game_id).unwrap();
+    let mut actor = MinesweeperWebSocket::connect(&url).await.unwrap();
+    let mut viewer = MinesweeperWebSocket::connect(&url).await.unwrap();
+    for ws in [&mut actor, &mut viewer] {
+        assert!(matches!(
+            next_game_message(ws).await,
+            Some(ServerMessage::Init { .. })
+        ));
+    }
+
+    viewer
+        .send_message(ClientMessage::Viewport {
+            top_left: Pos { x: 3, y: 0 },
+            bottom_right: Pos { x: 4, y: 4 },
+        })
+        .await
+        .unwrap();
+    match next_game_message(&mut viewer).await {
+        Some(ServerMessage::Update { updates, .. }) => {
+            assert_eq!(updates.len(), 10);
+            assert!(updates.iter().all(|update| update.value == Cell::Hidden));
+        }
+        other => panic!("expected the viewport's cells, got {:?}", other),
+    }
+
+    actor
+        .send_message(ClientMessage::Reveal {
+            pos: Pos { x: 0, y: 0 },
+        })
+        .await
+        .unwrap();
+    actor
+        .send_message(ClientMessage::Flag {
+            pos: Pos { x: 4, y: 4 },
+        })
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        assert!(matches!(
+            next_game_message(&mut actor).await,
+            Some(ServerMessage::Update { .. })
+        ));
+    }
+
+    // The reveal on the left was withheld, so the flag is the first thing to arrive
+    match next_game_message(&mut viewer).await {
+        Some(ServerMessage::Update {
+            updates,
+            flags_placed,
+            ..
+        }) => {
+            assert_eq!(updates.len(), 1);
+            assert_eq!(updates[0].pos, Pos { x: 4, y: 4 });
+            assert_eq!(updates[0].value, Cell::Flagged);
+            assert_eq!(flags_placed, 1);
+        }
+        other => panic!("expected the flag update, got {:?}", other),
+    }
+
+    viewer
+        .send_message(ClientMessage::Viewport {
+            top_left: Pos { x: 0, y: 0 },
+            bottom_right: Pos { x: 1, y: 4 },
+        })
+        .await
+        .unwrap();
+    match next_game_message(&mut viewer).await {
+        Some(ServerMessage::Update { updates, .. }) => {
+            assert_eq!(updates.len(), 10);
+            assert!(updates.iter().all(|update| update.value.is_revealed()));
+        }
+        other => panic!("expected the left region's cells, got {:?}", other),
+    }
+
+    actor.close().await.unwrap();
+    viewer.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn boards_over_max_board_cells_are_rejected() {
+    let base_url = support::spawn_server().await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+    let params = |size| GameParams {
+        width: size,
+        height: size,
+        bombs: 10,
+        ..Default::default()
+    };
+
+    // 4,000,000 cells against the default limit of 1,000,000
+    let error = client.create_game(params(2000)).await.unwrap_err();
+    assert!(error.to_string().contains("413"), "{}", error);
+    assert!(client.create_game(params(50)).await.is_ok());
+}
+
+async fn local_create<'c>(
+    client: &'c rocket::local::asynchronous::Client,
+    params: &GameParams,
+) -> rocket::local::asynchronous::LocalResponse<'c> {
+    client
+        .post("/create")
+        .remote("192.0.2.2:4000".parse().unwrap())
+        .json(params)
+        .dispatch()
+        .await
+}
+
+fn beginner_only() -> AllowedBoardSizes {
+    AllowedBoardSizes(Some(
+        [BoardSize {
+            width: 9,
+            height: 9,
+            bombs: 10,
+        }]
+        .into(),
+    ))
+}
+
+#[tokio::test]
+async fn allowlist_rejects_non_preset_boards_on_create() {
+    let custom = GameParams {
+        width: 16,
+        height: 16,
+        bombs: 40,
+        ..Default::default()
+    };
+    let client =
+        rocket::local::asynchronous::Client::untracked(build_rocket().manage(beginner_only()))
+            .await
+            .unwrap();
+    let response = local_create(&client, &custom).await;
+    assert_eq!(response.status(), Status::BadRequest);
+    let body = response.into_string().await.unwrap();
+    assert!(
+        body.contains(r#""error":"board 16x16x40 is not an allowed preset""#),
+        "{}",
+        body
+    );
+    let response = local_create(&client, &GameParams::default()).await;
+    assert_eq!(response.status(), Status::Ok);
+
+    // Without an allowlist any valid board goes
+    let client = rocket::local::asynchronous::Client::untracked(
+        build_rocket().manage(AllowedBoardSizes::default()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(local_create(&client, &custom).await.status(), Status::Ok);
+}
+
+#[tokio::test]
+async fn non_preset_restart_is_reported_only_to_the_sender() {
+    let base_url = support::spawn_server_with(|rocket| rocket.manage(beginner_only())).await;
+    let client = MinesweeperClient::new(&base_url).unwrap();
+    let game_id = client.create_game(GameParams::default()).await.unwrap();
+    let url = client.websocket_url(&game_id).unwrap();
+
+    let mut sender = MinesweeperWebSocket::connect(&url).await.unwrap();
+    assert!(matches!(
+        next_game_message(&mut sender).await,
+        Some(ServerMessage::Init { .. })
+    ));
+    let mut other = MinesweeperWebSocket::connect(&url).await.unwrap();
+    assert!(matches!(
+        next_game_message(&mut other).await,
+        Some(ServerMessage::Init { .. })
+    ));
+
+    sender
+        .send_message(ClientMessage::Restart {
+            params: GameParams {
+                width: 16,
+                height: 16,
+                bombs: 40,
+                ..Default::default()
+            },
+        })
+        .await
+        .unwrap();
+    match next_game_message(&mut sender).await {
+        Some(ServerMessage::Error { code, message }) => {
+            assert_eq!(code, "invalid_params");
+            assert_eq!(message, "board 16x16x40 is not an allowed preset");
+        }
+        other => panic!("expected an invalid_params error, got {:?}", other),
+    }
+
+    // The next broadcast is the first thing the other player sees
+    sender
+        .send_message(ClientMessage::Flag {
+            pos: Pos { x: 0, y: 0 },
+        })
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut other).await,
+        Some(ServerMessage::Update { .. })
+    ));
+
+    sender.close().await.unwrap();
+    other.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn drained_create_bucket_answers_429_with_retry_after() {
+    let client = rocket::local::asynchronous::Client::untracked(build_rocket())
+        .await
+        .unwrap();
+    let remote: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+    let create = || {
+        client
+            .post("/create")
+            .remote(remote)
+            .json(&GameParams::default())
+            .dispatch()
+    };
+
+    let capacity = client.rocket().state::<RateLimitConfig>().unwrap().capacity;
+    for _ in 0..capacity {
+        assert_eq!(create().await.status(), Status::Ok);
+    }
+
+    let response = create().await;
+    assert_eq!(response.status(), Status::TooManyRequests);
+    let retry_after: u64 = response
+        .headers()
+        .get_one("Retry-After")
+        .expect("a Retry-After header")
+        .parse()
+        .unwrap();
+    let bucket = client
+        .rocket()
+        .state::<RateLimiter>()
+        .unwrap()
+        .get(&remote.ip())
+        .unwrap()
+        .seconds_until_token();
+    assert!(retry_after > 0);
+    assert_eq!(retry_after, bucket);
+}
+
+#[tokio::test]
+async fn cleanup_warns_before_removing_an_idle_game() {
+    let mut games = None;
+    let base_url = support::spawn_server_with(|rocket| {
+        games = rocket.state::<Games>().cloned();
+        rocket
+    })
+    .await;
+    let games = games.unwrap();
+    let client = MinesweeperClient::new(&base_url).unwrap();
+
+    let clock = MockClock::new();
+    let game = Game::with_clock(
+        GameParams::default(),
+        Arc::new(Metrics::default()),
+        Arc::new(clock.clone()),
+    );
+    games.insert("expiring".to_string(), game.into_shared());
+    let mut ws = MinesweeperWebSocket::connect(&client.websocket_url("expiring").unwrap())
+        .await
+        .unwrap();
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::Init { .. })
+    ));
+
+    // Active games expire after an hour of inactivity, with a minute's warning
+    clock.advance(Duration::from_secs(3541));
+    cleanup_games(&games, 600, 3600, 60).await;
+    assert!(matches!(
+        next_game_message(&mut ws).await,
+        Some(ServerMessage::ExpiryWarning {
+            seconds_remaining: 59
+        })
+    ));
+    assert!(games.contains_key("expiring"));
+
+    clock.advance(Duration::from_secs(60));
+    cleanup_games(&games, 600, 3600, 60).await;
+    assert!(!games.contains_key("expiring"));
+    assert!(next_game_message(&mut ws).await.is_none());
+}
@@ -0,0 +1,36 @@
+use minesweeper_server::app::build_rocket;
+use rocket::{Build, Config, Rocket, fairing::AdHoc};
+use tokio::sync::oneshot;
+
+/// Launches the full server on an ephemeral localhost port in the background and
+/// returns its base URL once it is accepting connections.
+pub async fn spawn_server() -> String {
+    spawn_server_with(|rocket| rocket).await
+}
+
+/// Like [`spawn_server`], letting `configure` add state before the server ignites, e.g.
+/// a `MessageRateLimit` that would otherwise be read from the environment
+pub async fn spawn_server_with(configure: impl FnOnce(Rocket<Build>) -> Rocket<Build>) -> String {
+    let (port_sender, port_receiver) = oneshot::channel();
+    let figment = Config::figment()
+        .merge(("address", "127.0.0.1"))
+        .merge(("port", 0))
+        .merge(("log_level", "off"));
+
+    let rocket = configure(build_rocket())
+        .configure(figment)
+        .attach(AdHoc::on_liftoff("Report Port", move |rocket| {
+            Box::pin(async move {
+                let _ = port_sender.send(rocket.config().port);
+            })
+        }));
+
+    tokio::spawn(async move {
+        if let Err(e) = rocket.launch().await {
+            panic!("test server failed: {}", e);
+        }
+    });
+
+    let port = port_receiver.await.expect("test server did not start");
+    format!("http://127.0.0.1:{}", port)
+}